@@ -0,0 +1,38 @@
+use wem_converter::options::WemOptions;
+use wem_converter::wwriff::{WwiseRiffVorbis, ForcePacketFormat};
+use std::io::Cursor;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let input_wem = "input.wem";
+    let codebooks_file = "bin/packed_codebooks.bin";
+    let options = WemOptions::new(codebooks_file, ForcePacketFormat::ModPackets);
+
+    let input = match tokio::fs::File::open(input_wem).await {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Error opening {}: {:?}", input_wem, e);
+            return;
+        }
+    };
+    let vorbis = match WwiseRiffVorbis::<Cursor<Vec<u8>>>::from_async_reader(input, "input.ogg", &options).await {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error creating WwiseRiffVorbis: {:?}", e);
+            return;
+        }
+    };
+
+    let output = match tokio::fs::File::create("input.ogg").await {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Error creating input.ogg: {:?}", e);
+            return;
+        }
+    };
+    if let Err(e) = vorbis.generate_ogg_async(output).await {
+        eprintln!("Error generating OGG file: {:?}", e);
+    } else {
+        println!("OGG file generated successfully!");
+    }
+}
@@ -0,0 +1,34 @@
+use wem_converter::options::WemOptions;
+use wem_converter::wwriff::{generate_chained_ogg, WwiseRiffVorbis, ForcePacketFormat};
+use std::fs::File;
+use std::io::BufWriter;
+
+fn main() {
+    let inputs = ["line_01.wem", "line_02.wem", "line_03.wem"];
+    let codebooks_file = "bin/packed_codebooks.bin";
+    let options = WemOptions::new(codebooks_file, ForcePacketFormat::ModPackets);
+
+    let mut sources = Vec::new();
+    for input_wem in inputs {
+        match WwiseRiffVorbis::<File>::new(input_wem, &options) {
+            Ok(v) => sources.push(v),
+            Err(e) => {
+                eprintln!("Error creating WwiseRiffVorbis for {}: {:?}", input_wem, e);
+                return;
+            }
+        }
+    }
+
+    let output = match File::create("chained.ogg") {
+        Ok(f) => BufWriter::new(f),
+        Err(e) => {
+            eprintln!("Error creating output file: {:?}", e);
+            return;
+        }
+    };
+    if let Err(e) = generate_chained_ogg(&mut sources, output) {
+        eprintln!("Error generating chained OGG file: {:?}", e);
+    } else {
+        println!("Chained OGG file generated successfully!");
+    }
+}
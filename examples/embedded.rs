@@ -0,0 +1,33 @@
+use wem_converter::sub_reader::SubReader;
+use wem_converter::options::WemOptions;
+use wem_converter::wwriff::{WwiseRiffVorbis, ForcePacketFormat};
+use std::fs::File;
+
+// Converts a wem stored inside a bigger file, e.g. a sound bank, without extracting it.
+fn main() {
+    let bank_file = "input.bnk";
+    let codebooks_file = "bin/packed_codebooks.bin";
+    let options = WemOptions::new(codebooks_file, ForcePacketFormat::ModPackets);
+    // Where the wem lives in the bank, as read from the bank's index.
+    let (start, len) = (0x1000, 0x20000);
+
+    let window = match File::open(bank_file).and_then(|f| SubReader::new(f, start, len)) {
+        Ok(w) => w,
+        Err(e) => {
+            eprintln!("Error opening {}: {:?}", bank_file, e);
+            return;
+        }
+    };
+    let mut vorbis = match WwiseRiffVorbis::from_reader(window, "embedded.wem", &options) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error creating WwiseRiffVorbis: {:?}", e);
+            return;
+        }
+    };
+    if let Err(e) = vorbis.generate_ogg() {
+        eprintln!("Error generating OGG file: {:?}", e);
+    } else {
+        println!("OGG file generated successfully!");
+    }
+}
@@ -1,3 +1,4 @@
+use wem_converter::options::WemOptions;
 use wem_converter::wwriff::{WwiseRiffVorbis, ForcePacketFormat};
 use tracing_error::ErrorLayer;
 use tracing_subscriber::{layer::SubscriberExt, Registry};
@@ -13,6 +14,7 @@ fn main() {
 
     let input_wem = "input.wem";
     let codebooks_file = "bin/packed_codebooks.bin";
+    let options = WemOptions::new(codebooks_file, ForcePacketFormat::ModPackets);
 
     let buffer = match fs::read(input_wem) {
         Ok(data) => Cursor::new(data),
@@ -23,14 +25,7 @@ fn main() {
     };
 
 
-    let mut vorbis = match WwiseRiffVorbis::<Cursor<Vec<u8>>>::new(
-        buffer,
-        "input.ogg",
-        codebooks_file,
-        false,
-        false,
-        ForcePacketFormat::ModPackets,
-    ) {
+    let mut vorbis = match WwiseRiffVorbis::<Cursor<Vec<u8>>>::new(buffer, "input.ogg", &options) {
         Ok(v) => v,
         Err(e) => {
             eprintln!("Error creating WwiseRiffVorbis: {:?}", e);
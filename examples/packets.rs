@@ -0,0 +1,38 @@
+use wem_converter::options::WemOptions;
+use wem_converter::wwriff::{WwiseRiffVorbis, ForcePacketFormat};
+use std::fs::File;
+
+fn main() {
+    let input_wem = "input.wem";
+    let codebooks_file = "bin/packed_codebooks.bin";
+    let options = WemOptions::new(codebooks_file, ForcePacketFormat::ModPackets);
+    let mut vorbis = match WwiseRiffVorbis::<File>::new(input_wem, &options) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error creating WwiseRiffVorbis: {:?}", e);
+            return;
+        }
+    };
+
+    let mut packets = vorbis.packets();
+    while let Some(packet) = packets.next() {
+        let packet = match packet {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("Error reading packet: {:?}", e);
+                return;
+            }
+        };
+        let first_byte = match packets.read_payload(&packet) {
+            Ok(payload) => payload.first().copied(),
+            Err(e) => {
+                eprintln!("Error reading packet {}: {:?}", packet.index, e);
+                return;
+            }
+        };
+        println!(
+            "{:6} header@{:#x} payload@{:#x} size {:5} granule {:?} first byte {:02x?}",
+            packet.index, packet.header_offset, packet.payload_offset, packet.size, packet.granule, first_byte
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use wem_converter::progress::{CancellationToken, Progress};
+use wem_converter::options::WemOptions;
+use wem_converter::wwriff::{WwiseRiffVorbis, ForcePacketFormat};
+use std::fs::File;
+use std::io::BufWriter;
+
+fn main() {
+    let input_wem = "input.wem";
+    let codebooks_file = "bin/packed_codebooks.bin";
+    let options = WemOptions::new(codebooks_file, ForcePacketFormat::ModPackets);
+    let mut vorbis = match WwiseRiffVorbis::<File>::new(input_wem, &options) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error creating WwiseRiffVorbis: {:?}", e);
+            return;
+        }
+    };
+    let output = match File::create("input.ogg") {
+        Ok(f) => BufWriter::new(f),
+        Err(e) => {
+            eprintln!("Error creating input.ogg: {:?}", e);
+            return;
+        }
+    };
+
+    // A GUI would call cancel() from its cancel button; here we stop halfway.
+    let cancel = CancellationToken::new();
+    let token = cancel.clone();
+    let mut observer = |progress: Progress| {
+        println!("{} packets, {:.1}%", progress.packets, progress.fraction() * 100.0);
+        if progress.fraction() >= 0.5 {
+            token.cancel();
+        }
+    };
+    match vorbis.generate_ogg_observed(output, Some(&mut observer), Some(&cancel)) {
+        Ok(()) => println!("OGG file generated successfully!"),
+        Err(e) => eprintln!("Error generating OGG file: {}", e),
+    }
+}
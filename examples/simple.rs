@@ -1,3 +1,4 @@
+use wem_converter::options::WemOptions;
 use wem_converter::wwriff::{WwiseRiffVorbis, ForcePacketFormat};
 use tracing_error::ErrorLayer;
 use tracing_subscriber::{layer::SubscriberExt, Registry};
@@ -13,13 +14,8 @@ fn main() {
 
     let input_wem = "input.wem";
     let codebooks_file = "bin/packed_codebooks.bin";
-    let mut vorbis = match WwiseRiffVorbis::<File>::new(
-        input_wem,
-        codebooks_file,
-        false,
-        false,
-        ForcePacketFormat::ModPackets,
-    ) {
+    let options = WemOptions::new(codebooks_file, ForcePacketFormat::ModPackets);
+    let mut vorbis = match WwiseRiffVorbis::<File>::new(input_wem, &options) {
         Ok(v) => v,
         Err(e) => {
             eprintln!("Error creating WwiseRiffVorbis: {:?}", e);
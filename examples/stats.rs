@@ -0,0 +1,36 @@
+use wem_converter::options::WemOptions;
+use wem_converter::wwriff::{WwiseRiffVorbis, ForcePacketFormat};
+use std::fs::File;
+
+fn main() {
+    let input_wem = "input.wem";
+    let codebooks_file = "bin/packed_codebooks.bin";
+    let options = WemOptions::new(codebooks_file, ForcePacketFormat::ModPackets);
+    let mut vorbis = match WwiseRiffVorbis::<File>::new(input_wem, &options) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Error creating WwiseRiffVorbis: {:?}", e);
+            return;
+        }
+    };
+
+    let stats = match vorbis.bitrate_stats() {
+        Ok(stats) => stats,
+        Err(e) => {
+            eprintln!("Error reading packets: {:?}", e);
+            return;
+        }
+    };
+    println!(
+        "{} packets, {} to {} bytes, {:.1} on average",
+        stats.packet_count, stats.min_packet_size, stats.max_packet_size, stats.average_packet_size
+    );
+    println!(
+        "{:.0} bps actual, {} bps in the header",
+        stats.average_bitrate, stats.header_bitrate
+    );
+    println!("{} short blocks, {} long blocks", stats.short_blocks, stats.long_blocks);
+    for (second, bits) in stats.bitrate_per_second.iter().enumerate() {
+        println!("{:5}s {:8} bps", second, bits);
+    }
+}
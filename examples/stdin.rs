@@ -0,0 +1,20 @@
+use wem_converter::stream::WemStream;
+use wem_converter::options::WemOptions;
+use wem_converter::wwriff::ForcePacketFormat;
+use std::io::{self, BufWriter};
+
+// Usage: cat input.wem | cargo run --example stdin > input.ogg
+fn main() {
+    let codebooks_file = "bin/packed_codebooks.bin";
+    let options = WemOptions::new(codebooks_file, ForcePacketFormat::ModPackets);
+    let stream = match WemStream::new(io::stdin().lock(), &options) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Error reading wem header: {:?}", e);
+            return;
+        }
+    };
+    if let Err(e) = stream.generate_ogg_to(BufWriter::new(io::stdout().lock())) {
+        eprintln!("Error generating OGG stream: {:?}", e);
+    }
+}
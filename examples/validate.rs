@@ -0,0 +1,31 @@
+use wem_converter::ogg::validate_ogg;
+use std::fs::File;
+use std::io::BufReader;
+
+fn main() {
+    let input_ogg = "input.ogg";
+    let file = match File::open(input_ogg) {
+        Ok(f) => BufReader::new(f),
+        Err(e) => {
+            eprintln!("Error opening {}: {:?}", input_ogg, e);
+            return;
+        }
+    };
+
+    let report = match validate_ogg(file) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Error reading {}: {:?}", input_ogg, e);
+            return;
+        }
+    };
+    println!("{} pages, {} packets, {} logical streams", report.pages, report.packets, report.streams.len());
+    for issue in &report.issues {
+        println!("{}", issue);
+    }
+    if report.is_valid() {
+        println!("OGG file is valid!");
+    } else {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,34 @@
+#![no_main]
+
+use std::io::{self, Cursor};
+
+use libfuzzer_sys::fuzz_target;
+use wem_converter::options::WemOptions;
+use wem_converter::wwriff::{ForcePacketFormat, WwiseRiffVorbis};
+
+const CODEBOOKS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../bin/packed_codebooks.bin");
+
+// The first byte picks the conversion options, the next 16 the start and end
+// sample, the rest is the wem.
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 17 {
+        return;
+    }
+    let (flags, bounds, wem) = (data[0], &data[1..17], &data[17..]);
+    let start_sample = u64::from_le_bytes(bounds[..8].try_into().unwrap());
+    let end_sample = u64::from_le_bytes(bounds[8..].try_into().unwrap());
+    let force_packet_format = if flags & 4 != 0 {
+        ForcePacketFormat::ModPackets
+    } else {
+        ForcePacketFormat::NoModPackets
+    };
+    let options = WemOptions {
+        inline_codebooks: flags & 1 != 0,
+        full_setup: flags & 2 != 0,
+        ..WemOptions::new(CODEBOOKS, force_packet_format)
+    };
+    let Ok(mut vorbis) = WwiseRiffVorbis::<Cursor<Vec<u8>>>::new(Cursor::new(wem.to_vec()), "fuzz.wem", &options) else {
+        return;
+    };
+    let _ = vorbis.generate_ogg_range(start_sample, end_sample, io::sink());
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wem_converter::ogg::validate_ogg;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = validate_ogg(data);
+});
@@ -0,0 +1,31 @@
+#![no_main]
+
+use std::io::{self, Cursor};
+
+use libfuzzer_sys::fuzz_target;
+use wem_converter::options::WemOptions;
+use wem_converter::wwriff::{ForcePacketFormat, WwiseRiffVorbis};
+
+const CODEBOOKS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../bin/packed_codebooks.bin");
+
+// The first byte picks the conversion options, the rest is the wem.
+fuzz_target!(|data: &[u8]| {
+    let Some((&flags, wem)) = data.split_first() else {
+        return;
+    };
+    let force_packet_format = if flags & 4 != 0 {
+        ForcePacketFormat::ModPackets
+    } else {
+        ForcePacketFormat::NoModPackets
+    };
+    let options = WemOptions {
+        inline_codebooks: flags & 1 != 0,
+        full_setup: flags & 2 != 0,
+        ..WemOptions::new(CODEBOOKS, force_packet_format)
+    };
+    let Ok(mut vorbis) = WwiseRiffVorbis::<Cursor<Vec<u8>>>::new(Cursor::new(wem.to_vec()), "fuzz.wem", &options) else {
+        return;
+    };
+    vorbis.verify = flags & 8 != 0;
+    let _ = vorbis.generate_ogg_to(io::sink());
+});
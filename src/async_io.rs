@@ -0,0 +1,103 @@
+//! Async front-end for tokio (`async` feature).
+//!
+//! The converter seeks around the data chunk and loads the codebook library
+//! with blocking file I/O, so it never runs on the async executor: parsing and
+//! conversion go to `tokio::task::spawn_blocking`. Finished pages cross back
+//! through a bounded channel of `PAGES_IN_FLIGHT` pages, which the async side
+//! writes out; a slow writer holds the conversion back rather than letting
+//! pages pile up in memory.
+
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::task::{spawn_blocking, JoinError};
+
+use crate::errors::{ParseError, Result};
+use crate::options::WemOptions;
+use crate::wwriff::WwiseRiffVorbis;
+
+/// Pages the blocking conversion may run ahead of the async writer.
+const PAGES_IN_FLIGHT: usize = 16;
+
+impl WwiseRiffVorbis<Cursor<Vec<u8>>> {
+    /// Same as `new` for an in-memory wem, reading it from an async reader
+    /// first. The whole wem is buffered in memory, since the converter needs
+    /// to seek in it from blocking code, so it must fit in
+    /// `options.limits.max_allocation`; the header is then parsed on the
+    /// blocking thread pool.
+    pub async fn from_async_reader<R: AsyncRead + AsyncSeek + Unpin>(
+        mut reader: R,
+        file_name: &str,
+        options: &WemOptions,
+    ) -> Result<Self> {
+        reader.seek(SeekFrom::Start(0)).await?;
+        let max_allocation = options.limits.max_allocation as u64;
+        let mut buf = Vec::new();
+        (&mut reader).take(max_allocation.saturating_add(1)).read_to_end(&mut buf).await?;
+        if buf.len() as u64 > max_allocation {
+            return Err(ParseError::Message("wem larger than max_allocation".into()));
+        }
+        let (file_name, options) = (file_name.to_string(), options.clone());
+        spawn_blocking(move || Self::new(Cursor::new(buf), &file_name, &options))
+        .await
+        .unwrap_or_else(|e| Err(joined(e)))
+    }
+}
+
+impl<R: Read + Seek + Send + 'static> WwiseRiffVorbis<R> {
+    /// Same as `generate_ogg_to`, but writes to an async writer. The
+    /// conversion runs on the blocking thread pool, so the wem is moved there
+    /// and handed back once it is done. With `verify` set the stream is
+    /// checked before any of it is written.
+    ///
+    /// Dropping the future stops the conversion at the next page.
+    pub async fn generate_ogg_async<W: AsyncWrite + Unpin>(mut self, mut writer: W) -> Result<Self> {
+        let (pages, mut received) = mpsc::channel(PAGES_IN_FLIGHT);
+        let conversion = spawn_blocking(move || {
+            let result = self.generate_ogg_to(PageSender(pages));
+            (self, result)
+        });
+
+        let mut written = Ok(());
+        while let Some(page) = received.recv().await {
+            if let Err(e) = writer.write_all(&page).await {
+                written = Err(e);
+                break;
+            }
+        }
+        // Fails the conversion's next send if writing stopped early.
+        drop(received);
+
+        let (wem, result) = conversion.await.map_err(joined)?;
+        written?;
+        result?;
+        writer.flush().await?;
+        Ok(wem)
+    }
+}
+
+/// Hands every write to the async side as one page.
+struct PageSender(mpsc::Sender<Vec<u8>>);
+
+impl Write for PageSender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(buf.to_vec())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "async writer stopped"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Passes on a panic in a blocking task; the runtime shutting down under it
+/// becomes an error.
+fn joined(e: JoinError) -> ParseError {
+    match e.try_into_panic() {
+        Ok(panic) => std::panic::resume_unwind(panic),
+        Err(e) => ParseError::Message(format!("conversion task stopped: {}", e)),
+    }
+}
@@ -1,9 +1,110 @@
-use std::io::{self, Write, Seek, SeekFrom, ErrorKind};
-use byteorder::{LittleEndian, ReadBytesExt};
+use std::collections::VecDeque;
+use std::io::{self, Write, Seek, ErrorKind};
 use std::io::Read;
 
 use crate::errors::{ParseError, Result};
 
+//
+// BitWriter: packing bits LSB-first into bytes
+//
+
+/// Accumulates bits LSB-first in a 64-bit word and spills them into the
+/// byte buffer 32 bits at a time.
+#[derive(Debug, Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    bits_stored: u32,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bytes: Vec::with_capacity(capacity),
+            acc: 0,
+            bits_stored: 0,
+        }
+    }
+
+    /// Appends the low `bits` bits of `value` (at most 32).
+    pub fn write_bits(&mut self, value: u32, bits: u8) {
+        debug_assert!(bits <= 32);
+        if bits == 0 {
+            return;
+        }
+        let mask = u64::MAX >> (64 - bits as u32);
+        self.acc |= (value as u64 & mask) << self.bits_stored;
+        self.bits_stored += bits as u32;
+        if self.bits_stored >= 32 {
+            self.bytes.extend_from_slice(&(self.acc as u32).to_le_bytes());
+            self.acc >>= 32;
+            self.bits_stored -= 32;
+        }
+    }
+
+    /// Appends whole bytes. Byte-aligned data is copied straight into the
+    /// buffer; otherwise it is shifted in four bytes at a time.
+    pub fn write_bytes(&mut self, buf: &[u8]) {
+        if self.bits_stored.is_multiple_of(8) {
+            self.spill_bytes();
+            self.bytes.extend_from_slice(buf);
+            return;
+        }
+        let mut words = buf.chunks_exact(4);
+        for word in &mut words {
+            self.write_bits(u32::from_le_bytes([word[0], word[1], word[2], word[3]]), 32);
+        }
+        for &b in words.remainder() {
+            self.write_bits(b as u32, 8);
+        }
+    }
+
+    /// Pads the last partial byte with zero bits.
+    pub fn align(&mut self) {
+        self.bits_stored = (self.bits_stored + 7) & !7;
+        self.spill_bytes();
+    }
+
+    /// Number of bytes written so far, counting a partial byte as a whole one.
+    pub fn len(&self) -> usize {
+        self.bytes.len() + (self.bits_stored as usize).div_ceil(8)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The completed bytes. Call `align` first to include a partial byte.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Takes the written bytes (including a padded partial byte) and resets the writer.
+    pub fn take_bytes(&mut self) -> Vec<u8> {
+        self.align();
+        std::mem::take(&mut self.bytes)
+    }
+
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+        self.acc = 0;
+        self.bits_stored = 0;
+    }
+
+    /// Moves every complete byte out of the accumulator.
+    fn spill_bytes(&mut self) {
+        while self.bits_stored >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits_stored -= 8;
+        }
+    }
+}
+
 //
 // BitOggStream: writing bits and constructing Ogg pages
 //
@@ -13,14 +114,17 @@ const SEGMENT_SIZE: usize = 255;
 
 pub struct BitOggStream<W: Write> {
     writer: W,
-    bit_buffer: u8,
-    bits_stored: u8,
+    payload: BitWriter,
     page_buffer: Vec<u8>,
-    payload_bytes: usize,
     first: bool,
     continued: bool,
     granule: u32,
     seqno: u32,
+    serial: u32,
+    /// Ends of the packets already finished on the pending page.
+    packet_ends: Vec<usize>,
+    /// Lacing values those packets take up.
+    finished_laces: usize,
 }
 
 impl<W: Write> BitOggStream<W> {
@@ -28,115 +132,150 @@ impl<W: Write> BitOggStream<W> {
         let capacity = HEADER_BYTES + MAX_SEGMENTS + SEGMENT_SIZE * MAX_SEGMENTS;
         Self {
             writer,
-            bit_buffer: 0,
-            bits_stored: 0,
-            page_buffer: vec![0u8; capacity],
-            payload_bytes: 0,
+            payload: BitWriter::with_capacity(SEGMENT_SIZE * MAX_SEGMENTS),
+            page_buffer: Vec::with_capacity(capacity),
             first: true,
             continued: false,
             granule: 0,
             seqno: 0,
+            serial: 1,
+            packet_ends: Vec::new(),
+            finished_laces: 0,
         }
     }
 
-    pub fn put_bit(&mut self, bit: bool) -> Result<()> {
-        if bit {
-            self.bit_buffer |= 1 << self.bits_stored;
-        }
-        self.bits_stored += 1;
-        if self.bits_stored == 8 {
-            self.flush_bits()?;
-        }
+    /// Start a new logical bitstream (the next link of a chained Ogg file).
+    /// Any pending page is flushed first; the next page is marked as the
+    /// beginning of stream and page sequence numbers and granule restart at 0.
+    pub fn begin_link(&mut self, serial: u32) -> Result<()> {
+        self.flush_page_internal(false, false)?;
+        self.serial = serial;
+        self.first = true;
+        self.continued = false;
+        self.granule = 0;
+        self.seqno = 0;
         Ok(())
     }
 
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    /// The underlying writer; finished pages have already been written to it.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn put_bit(&mut self, bit: bool) -> Result<()> {
+        self.payload.write_bits(bit as u32, 1);
+        self.check_payload_size()
+    }
+
+    /// Pads the current packet to a whole byte.
     pub fn flush_bits(&mut self) -> Result<()> {
-        if self.bits_stored != 0 {
-            if self.payload_bytes == SEGMENT_SIZE * MAX_SEGMENTS {
-                self.flush_page_internal(true, false);
-                return Err(ParseError::Message("ran out of space in an Ogg packet".into()));
-            }
-            let pos = HEADER_BYTES + MAX_SEGMENTS + self.payload_bytes;
-            if pos >= self.page_buffer.len() {
-                return Err(ParseError::Message("page buffer overflow".into()));
-            }
-            self.page_buffer[pos] = self.bit_buffer;
-            self.payload_bytes += 1;
-            self.bits_stored = 0;
-            self.bit_buffer = 0;
-        }
-        Ok(())
+        self.payload.align();
+        self.check_payload_size()
     }
 
     pub fn set_granule(&mut self, g: u32) {
         self.granule = g;
     }
 
+    /// Ends the current packet but not the page, so that the next packet
+    /// starts on the same page. The page gets the granule set for the last
+    /// packet on it; the ones ended here have none of their own.
+    pub fn end_packet(&mut self) -> Result<()> {
+        self.flush_bits()?;
+        let len = self.payload.len() - self.packet_start();
+        let laces = len / SEGMENT_SIZE + 1;
+        if self.finished_laces + laces >= MAX_SEGMENTS {
+            return Err(ParseError::Message("ran out of space in an Ogg page".into()));
+        }
+        self.packet_ends.push(self.payload.len());
+        self.finished_laces += laces;
+        Ok(())
+    }
+
+    fn packet_start(&self) -> usize {
+        self.packet_ends.last().copied().unwrap_or(0)
+    }
+
+    fn check_payload_size(&self) -> Result<()> {
+        let len = self.payload.len() - self.packet_start();
+        // A packet sharing the page must end on it.
+        let fits = if self.packet_ends.is_empty() {
+            len <= SEGMENT_SIZE * MAX_SEGMENTS
+        } else {
+            self.finished_laces + len / SEGMENT_SIZE < MAX_SEGMENTS
+        };
+        if !fits {
+            return Err(ParseError::Message("ran out of space in an Ogg packet".into()));
+        }
+        Ok(())
+    }
+
     /// Flush the current Ogg page.
     /// (Renamed from flush_page to flush_page_internal so the trait implementation can call it.)
     pub fn flush_page_internal(&mut self, next_continued: bool, last: bool) -> Result<()> {
         self.flush_bits()?;
-        if self.payload_bytes == 0 {
+        let payload = self.payload.as_bytes();
+        if payload.is_empty() {
             return Ok(());
         }
-        let mut segments = (self.payload_bytes + SEGMENT_SIZE) / SEGMENT_SIZE;
-        if segments > MAX_SEGMENTS + 1 {
-            segments = MAX_SEGMENTS;
-        }
-        for i in 0..self.payload_bytes {
-            let src = HEADER_BYTES + MAX_SEGMENTS + i;
-            let dst = HEADER_BYTES + segments + i;
-            self.page_buffer[dst] = self.page_buffer[src];
-        }
-        self.page_buffer[0..4].copy_from_slice(b"OggS");
-        self.page_buffer[4] = 0; // stream_structure_version
-        self.page_buffer[5] = (if self.continued { 1 } else { 0 })
-            | (if self.first { 2 } else { 0 })
-            | (if last { 4 } else { 0 });
-        {
-            let mut tmp = [0u8; 4];
-            write_32_le(&mut tmp, self.granule);
-            self.page_buffer[6..10].copy_from_slice(&tmp);
-        }
-        self.page_buffer[10..14].fill(0);
-        {
-            let mut tmp = [0u8; 4];
-            write_32_le(&mut tmp, 1); // stream serial number (dummy)
-            self.page_buffer[14..18].copy_from_slice(&tmp);
-        }
-        {
-            let mut tmp = [0u8; 4];
-            write_32_le(&mut tmp, self.seqno);
-            self.page_buffer[18..22].copy_from_slice(&tmp);
-        }
-        {
-            let mut tmp = [0u8; 4];
-            write_32_le(&mut tmp, 0); // checksum placeholder
-            self.page_buffer[22..26].copy_from_slice(&tmp);
-        }
-        self.page_buffer[26] = segments as u8;
-        let mut bytes_left = self.payload_bytes;
-        for i in 0..segments {
-            let lace = if bytes_left >= SEGMENT_SIZE {
-                SEGMENT_SIZE as u8
-            } else {
-                bytes_left as u8
-            };
-            self.page_buffer[27 + i] = lace;
-            bytes_left = bytes_left.saturating_sub(SEGMENT_SIZE);
-        }
-        let total = HEADER_BYTES + segments + self.payload_bytes;
-        let crc = checksum(&self.page_buffer[0..total], total as i32);
-        {
-            let mut tmp = [0u8; 4];
-            write_32_le(&mut tmp, crc);
-            self.page_buffer[22..26].copy_from_slice(&tmp);
-        }
-        self.writer.write_all(&self.page_buffer[0..(HEADER_BYTES + segments + self.payload_bytes)])?;
+        let page = &mut self.page_buffer;
+        page.clear();
+        page.extend_from_slice(b"OggS");
+        page.push(0); // stream_structure_version
+        page.push(
+            (if self.continued { 1 } else { 0 })
+                | (if self.first { 2 } else { 0 })
+                | (if last { 4 } else { 0 }),
+        );
+        page.extend_from_slice(&self.granule.to_le_bytes());
+        page.extend_from_slice(&[0u8; 4]);
+        page.extend_from_slice(&self.serial.to_le_bytes());
+        page.extend_from_slice(&self.seqno.to_le_bytes());
+        page.extend_from_slice(&[0u8; 4]); // checksum placeholder
+        page.push(0); // segment count, filled in below
+
+        // packets ended with end_packet, each with its terminating lace
+        let mut start = 0;
+        for &end in &self.packet_ends {
+            let len = end - start;
+            page.extend(std::iter::repeat_n(SEGMENT_SIZE as u8, len / SEGMENT_SIZE));
+            page.push((len % SEGMENT_SIZE) as u8);
+            start = end;
+        }
+        let len = payload.len() - start;
+        if len != 0 || self.packet_ends.is_empty() {
+            let mut segments = (len + SEGMENT_SIZE) / SEGMENT_SIZE;
+            if segments == MAX_SEGMENTS + 1 {
+                // a full page leaves out the terminating 0 lace; the packet continues
+                segments = MAX_SEGMENTS;
+            }
+            let mut bytes_left = len;
+            for _ in 0..segments {
+                let lace = bytes_left.min(SEGMENT_SIZE);
+                page.push(lace as u8);
+                bytes_left -= lace;
+            }
+        }
+        page[HEADER_BYTES - 1] = (page.len() - HEADER_BYTES) as u8;
+        page.extend_from_slice(payload);
+
+        let crc = checksum(page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+        self.writer.write_all(page)?;
         self.seqno += 1;
         self.first = false;
         self.continued = next_continued;
-        self.payload_bytes = 0;
+        self.payload.clear();
+        self.packet_ends.clear();
+        self.finished_laces = 0;
         Ok(())
     }
 }
@@ -148,14 +287,14 @@ impl<W: Write> Drop for BitOggStream<W> {
 }
 
 pub fn write_32_le(buf: &mut [u8; 4], mut v: u32) {
-    for i in 0..4 {
-        buf[i] = (v & 0xFF) as u8;
+    for b in buf.iter_mut() {
+        *b = (v & 0xFF) as u8;
         v >>= 8;
     }
 }
 pub fn write_16_le(buf: &mut [u8; 2], mut v: u16) {
-    for i in 0..2 {
-        buf[i] = (v & 0xFF) as u8;
+    for b in buf.iter_mut() {
+        *b = (v & 0xFF) as u8;
         v >>= 8;
     }
 }
@@ -163,100 +302,184 @@ pub fn write_16_le(buf: &mut [u8; 2], mut v: u16) {
 pub trait BitOggStreamT {
     fn write_bits(&mut self, value: u32, bits: u8) -> Result<()>;
     fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+    fn set_granule(&mut self, granule: u32);
     fn flush_page(&mut self, next_continued: bool, last: bool) -> Result<()>;
 }
 
 impl<W: Write> BitOggStreamT for BitOggStream<W> {
     fn write_bits(&mut self, value: u32, bits: u8) -> Result<()> {
-        if bits % 8 == 0 {
-            let byte_count = bits / 8;
-            for i in 0..byte_count {
-                let pos = HEADER_BYTES + MAX_SEGMENTS + self.payload_bytes;
-                if pos >= self.page_buffer.len() {
-                    return Err(ParseError::Message("page buffer overflow".into()));
-                }
-                self.page_buffer[pos] = ((value >> (i * 8)) & 0xFF) as u8;
-                self.payload_bytes += 1;
-            }
-            Ok(())
-        } else {
-            for i in 0..bits {
-                let bit = (value >> i) & 1;
-                self.put_bit(bit != 0)?;
-            }
-            Ok(())
+        if bits > 32 {
+            return Err(ParseError::Message("Too many bits".into()));
         }
+        self.payload.write_bits(value, bits);
+        self.check_payload_size()
     }
-    
+
     fn write_all(&mut self, buf: &[u8]) -> Result<()> {
-        for &byte in buf {
-            let pos = HEADER_BYTES + MAX_SEGMENTS + self.payload_bytes;
-            if pos >= self.page_buffer.len() {
-                return Err(ParseError::Message("page buffer overflow".into()));
-            }
-            self.page_buffer[pos] = byte;
-            self.payload_bytes += 1;
-        }
-        Ok(())
+        self.payload.write_bytes(buf);
+        self.check_payload_size()
+    }
+
+    fn set_granule(&mut self, granule: u32) {
+        self.granule = granule;
     }
-    
+
     fn flush_page(&mut self, next_continued: bool, last: bool) -> Result<()> {
         self.flush_page_internal(next_continued, last)
     }
 }
 
+//
+// BitPacketStream: writing bits into bare Vorbis packets, without Ogg framing
+//
+#[derive(Debug, Default)]
+pub struct BitPacketStream {
+    payload: BitWriter,
+    granule: u32,
+    packets: VecDeque<(Vec<u8>, u32)>,
+}
+
+impl BitPacketStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes the oldest finished packet and the granule that was set for it.
+    pub fn pop_packet(&mut self) -> Option<(Vec<u8>, u32)> {
+        self.packets.pop_front()
+    }
+}
 
+impl BitOggStreamT for BitPacketStream {
+    fn write_bits(&mut self, value: u32, bits: u8) -> Result<()> {
+        if bits > 32 {
+            return Err(ParseError::Message("Too many bits".into()));
+        }
+        self.payload.write_bits(value, bits);
+        Ok(())
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.payload.write_bytes(buf);
+        Ok(())
+    }
+
+    fn set_granule(&mut self, granule: u32) {
+        self.granule = granule;
+    }
+
+    /// Ends the current packet; there are no pages, so both flags are ignored.
+    /// Empty packets are dropped, as they are when writing Ogg pages.
+    fn flush_page(&mut self, _next_continued: bool, _last: bool) -> Result<()> {
+        self.payload.align();
+        if !self.payload.is_empty() {
+            self.packets.push_back((self.payload.take_bytes(), self.granule));
+        }
+        Ok(())
+    }
+}
+
+
+const READ_BLOCK_SIZE: usize = 4096;
+
+/// Reads bit fields LSB-first. Bytes are pulled from the underlying reader a
+/// block at a time and fed through a 64-bit cache, so the reader is usually
+/// positioned past the bits consumed so far; seek it explicitly before
+/// reading from it directly again.
 pub struct BitStream<R: Read> {
     reader: R,
-    bit_buffer: u8,
-    pub bits_left: u8,
+    block: Vec<u8>,
+    block_pos: usize,
+    cache: u64,
+    cache_bits: u32,
     total_bits_read: u64,
 }
 
-impl<R: Read + Seek> BitStream<R> {
+impl<R: Read> BitStream<R> {
     pub fn new(reader: R) -> Self {
         Self {
             reader,
-            bit_buffer: 0,
-            bits_left: 0,
+            block: Vec::new(),
+            block_pos: 0,
+            cache: 0,
+            cache_bits: 0,
             total_bits_read: 0,
         }
     }
 
-    /// Reads a single bit (LSB-first within the byte).
-    pub fn get_bit(&mut self) -> Result<bool> {
-        if self.bits_left == 0 {
-            let mut buf = [0u8; 1];
-            self.reader.read_exact(&mut buf).map_err(|e| {
-                if e.kind() == ErrorKind::UnexpectedEof {
-                    ParseError::Message("Out of bits".into())
-                } else {
-                    e.into()
+    /// Tops up the cache with as many whole bytes as fit.
+    fn refill(&mut self) -> Result<()> {
+        while self.cache_bits <= 56 {
+            if self.block_pos == self.block.len() {
+                self.block.resize(READ_BLOCK_SIZE, 0);
+                let n = loop {
+                    match self.reader.read(&mut self.block) {
+                        Ok(n) => break n,
+                        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                        Err(e) => {
+                            self.block.clear();
+                            self.block_pos = 0;
+                            return Err(e.into());
+                        }
+                    }
+                };
+                self.block.truncate(n);
+                self.block_pos = 0;
+                if n == 0 {
+                    return Ok(());
                 }
-            })?;
-            self.bit_buffer = buf[0];
-            self.bits_left = 8;
+            }
+            let take = ((64 - self.cache_bits) / 8) as usize;
+            let end = (self.block_pos + take).min(self.block.len());
+            for &b in &self.block[self.block_pos..end] {
+                self.cache |= (b as u64) << self.cache_bits;
+                self.cache_bits += 8;
+            }
+            self.block_pos = end;
+        }
+        Ok(())
+    }
+
+    /// Reads an n-bit field (n <= 32), first bit read ending up in bit 0.
+    pub fn read_bits(&mut self, bits: u8) -> Result<u32> {
+        if bits > 32 {
+            return Err(ParseError::Message("Too many bits".into()));
+        }
+        if bits == 0 {
+            return Ok(0);
+        }
+        if self.cache_bits < bits as u32 {
+            self.refill()?;
+            if self.cache_bits < bits as u32 {
+                return Err(ParseError::Message("Out of bits".into()));
+            }
         }
-        self.total_bits_read += 1;
-        self.bits_left -= 1;
-        // Return the bit at position: 0x80 >> bits_left.
-        Ok((self.bit_buffer & (0x80 >> self.bits_left)) != 0)
+        let value = (self.cache & (u64::MAX >> (64 - bits as u32))) as u32;
+        self.cache >>= bits;
+        self.cache_bits -= bits as u32;
+        self.total_bits_read += bits as u64;
+        Ok(value)
+    }
+
+    /// Reads a single bit (LSB-first within the byte).
+    pub fn get_bit(&mut self) -> Result<bool> {
+        Ok(self.read_bits(1)? != 0)
     }
 
     /// Returns the total number of bits read so far.
     pub fn get_total_bits_read(&self) -> u64 {
         self.total_bits_read
     }
+}
 
-    /// Returns the current byte position in the underlying reader.
-    /// If some bits are buffered, it subtracts one byte.
+impl<R: Read + Seek> BitStream<R> {
+    /// Returns the byte position of the next unread bit in the underlying
+    /// reader, not counting bytes that were read ahead into the buffer.
+    /// A partially consumed byte counts as the current one.
     pub fn get_position(&mut self) -> io::Result<u64> {
-        let pos = self.reader.seek(SeekFrom::Current(0))?;
-        if self.bits_left < 8 {
-            Ok(pos - 1)
-        } else {
-            Ok(pos)
-        }
+        let pos = self.reader.stream_position()?;
+        let buffered = (self.block.len() - self.block_pos) as u64 + (self.cache_bits as u64).div_ceil(8);
+        Ok(pos - buffered)
     }
 }
 
@@ -279,22 +502,12 @@ impl<const BIT_SIZE: usize> BitUint<BIT_SIZE> {
         Ok(Self { total: v })
     }
 
-    pub fn read_from<R: Read + Seek>(stream: &mut BitStream<R>) -> Result<Self> {
-        let mut total = 0;
-        for i in 0..BIT_SIZE {
-            if stream.get_bit()? {
-                total |= 1 << i;
-            }
-        }
-        Self::new(total)
+    pub fn read_from<R: Read>(stream: &mut BitStream<R>) -> Result<Self> {
+        Self::new(stream.read_bits(BIT_SIZE as u8)?)
     }
 
     pub fn write_to<O: crate::bit_stream::BitOggStreamT>(&self, stream: &mut O) -> Result<()> {
-        for i in 0..BIT_SIZE {
-            let bit = (self.total & (1 << i)) != 0;
-            stream.write_bits(if bit { 1 } else { 0 }, 1)?;
-        }
-        Ok(())
+        stream.write_bits(self.total, BIT_SIZE as u8)
     }
 }
 
@@ -309,106 +522,226 @@ impl BitUintV {
         if size > 32 {
             return Err(ParseError::Message("Too many bits".into()));
         }
-        if v >= (1 << size) {
+        if size < 32 && v >= (1 << size) {
             return Err(ParseError::Message("Integer too big".into()));
         }
         Ok(Self { size, total: v })
     }
 
-    pub fn read_from<R: Read + Seek>(stream: &mut BitStream<R>, size: usize) -> Result<Self> {
-        let mut total = 0;
-        for i in 0..size {
-            if stream.get_bit()? {
-                total |= 1 << i;
-            }
+    pub fn read_from<R: Read>(stream: &mut BitStream<R>, size: usize) -> Result<Self> {
+        if size > 32 {
+            return Err(ParseError::Message("Too many bits".into()));
         }
-        Self::new(size, total)
+        Self::new(size, stream.read_bits(size as u8)?)
     }
 
-    /// Writes this BitUintV bit–by–bit to the BitOggStreamT.
+    /// Writes this BitUintV to the BitOggStreamT as a single field.
     pub fn write_to<O: crate::bit_stream::BitOggStreamT>(&self, stream: &mut O) -> Result<()> {
-        for i in 0..self.size {
-            let bit = (self.total & (1 << i)) != 0;
-            stream.write_bits(if bit { 1 } else { 0 }, 1)?;
+        stream.write_bits(self.total, self.size as u8)
+    }
+}
+
+
+/// Ogg page CRC-32 (polynomial 0x04c11db7, zero initial value, no final xor).
+pub fn checksum(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+    crc.value()
+}
+
+/// Incremental Ogg CRC-32, processing eight bytes per step (slicing-by-8).
+/// Feeding the data in several `update` calls gives the same result as one
+/// `checksum` over the concatenation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Crc {
+    value: u32,
+}
+
+impl Crc {
+    pub fn new() -> Self {
+        Self { value: 0 }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let mut crc = self.value;
+        let mut chunks = data.chunks_exact(8);
+        for c in &mut chunks {
+            crc ^= u32::from_be_bytes([c[0], c[1], c[2], c[3]]);
+            crc = CRC_TABLES[7][(crc >> 24) as usize]
+                ^ CRC_TABLES[6][((crc >> 16) & 0xFF) as usize]
+                ^ CRC_TABLES[5][((crc >> 8) & 0xFF) as usize]
+                ^ CRC_TABLES[4][(crc & 0xFF) as usize]
+                ^ CRC_TABLES[3][c[4] as usize]
+                ^ CRC_TABLES[2][c[5] as usize]
+                ^ CRC_TABLES[1][c[6] as usize]
+                ^ CRC_TABLES[0][c[7] as usize];
         }
-        Ok(())
+        for &b in chunks.remainder() {
+            crc = (crc << 8) ^ CRC_TABLES[0][((crc >> 24) as u8 ^ b) as usize];
+        }
+        self.value = crc;
+    }
+
+    pub fn value(&self) -> u32 {
+        self.value
     }
 }
 
+const CRC_POLYNOMIAL: u32 = 0x04c11db7;
+
+/// `CRC_TABLES[k][b]` is the CRC of byte `b` followed by `k` zero bytes.
+static CRC_TABLES: [[u32; 256]; 8] = crc_tables();
 
-/// CRC checksum calculation using a lookup table.
-pub fn checksum(data: &[u8], bytes: i32) -> u32 {
-    let mut crc_reg: u32 = 0;
-    for &b in data.iter().take(bytes as usize) {
-        let index = ((crc_reg >> 24) as u8 ^ b) as usize;
-        crc_reg = (crc_reg << 8) ^ CRC_LOOKUP[index];
+const fn crc_tables() -> [[u32; 256]; 8] {
+    let mut tables = [[0u32; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut r = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            r = if r & 0x80000000 != 0 { (r << 1) ^ CRC_POLYNOMIAL } else { r << 1 };
+            bit += 1;
+        }
+        tables[0][i] = r;
+        i += 1;
+    }
+    let mut k = 1;
+    while k < 8 {
+        let mut i = 0;
+        while i < 256 {
+            let prev = tables[k - 1][i];
+            tables[k][i] = (prev << 8) ^ tables[0][(prev >> 24) as usize];
+            i += 1;
+        }
+        k += 1;
     }
-    crc_reg
+    tables
 }
 
-static CRC_LOOKUP: [u32; 256] = [
-    0x00000000,0x04c11db7,0x09823b6e,0x0d4326d9,
-    0x130476dc,0x17c56b6b,0x1a864db2,0x1e475005,
-    0x2608edb8,0x22c9f00f,0x2f8ad6d6,0x2b4bcb61,
-    0x350c9b64,0x31cd86d3,0x3c8ea00a,0x384fbdbd,
-    0x4c11db70,0x48d0c6c7,0x4593e01e,0x4152fda9,
-    0x5f15adac,0x5bd4b01b,0x569796c2,0x52568b75,
-    0x6a1936c8,0x6ed82b7f,0x639b0da6,0x675a1011,
-    0x791d4014,0x7ddc5da3,0x709f7b7a,0x745e66cd,
-    0x9823b6e0,0x9ce2ab57,0x91a18d8e,0x95609039,
-    0x8b27c03c,0x8fe6dd8b,0x82a5fb52,0x8664e6e5,
-    0xbe2b5b58,0xbaea46ef,0xb7a96036,0xb3687d81,
-    0xad2f2d84,0xa9ee3033,0xa4ad16ea,0xa06c0b5d,
-    0xd4326d90,0xd0f37027,0xddb056fe,0xd9714b49,
-    0xc7361b4c,0xc3f706fb,0xceb42022,0xca753d95,
-    0xf23a8028,0xf6fb9d9f,0xfbb8bb46,0xff79a6f1,
-    0xe13ef6f4,0xe5ffeb43,0xe8bccd9a,0xec7dd02d,
-    0x34867077,0x30476dc0,0x3d044b19,0x39c556ae,
-    0x278206ab,0x23431b1c,0x2e003dc5,0x2ac12072,
-    0x128e9dcf,0x164f8078,0x1b0ca6a1,0x1fcdbb16,
-    0x018aeb13,0x054bf6a4,0x0808d07d,0x0cc9cdca,
-    0x7897ab07,0x7c56b6b0,0x71159069,0x75d48dde,
-    0x6b93dddb,0x6f52c06c,0x6211e6b5,0x66d0fb02,
-    0x5e9f46bf,0x5a5e5b08,0x571d7dd1,0x53dc6066,
-    0x4d9b3063,0x495a2dd4,0x44190b0d,0x40d816ba,
-    0xaca5c697,0xa864db20,0xa527fdf9,0xa1e6e04e,
-    0xbfa1b04b,0xbb60adfc,0xb6238b25,0xb2e29692,
-    0x8aad2b2f,0x8e6c3698,0x832f1041,0x87ee0df6,
-    0x99a95df3,0x9d684044,0x902b669d,0x94ea7b2a,
-    0xe0b41de7,0xe4750050,0xe9362689,0xedf73b3e,
-    0xf3b06b3b,0xf771768c,0xfa325055,0xfef34de2,
-    0xc6bcf05f,0xc27dede8,0xcf3ecb31,0xcbffd686,
-    0xd5b88683,0xd1799b34,0xdc3abded,0xd8fba05a,
-    0x690ce0ee,0x6dcdfd59,0x608edb80,0x644fc637,
-    0x7a089632,0x7ec98b85,0x738aad5c,0x774bb0eb,
-    0x4f040d56,0x4bc510e1,0x46863638,0x42472b8f,
-    0x5c007b8a,0x58c1663d,0x558240e4,0x51435d53,
-    0x251d3b9e,0x21dc2629,0x2c9f00f0,0x285e1d47,
-    0x36194d42,0x32d850f5,0x3f9b762c,0x3b5a6b9b,
-    0x0315d626,0x07d4cb91,0x0a97ed48,0x0e56f0ff,
-    0x1011a0fa,0x14d0bd4d,0x19939b94,0x1d528623,
-    0xf12f560e,0xf5ee4bb9,0xf8ad6d60,0xfc6c70d7,
-    0xe22b20d2,0xe6ea3d65,0xeba91bbc,0xef68060b,
-    0xd727bbb6,0xd3e6a601,0xdea580d8,0xda649d6f,
-    0xc423cd6a,0xc0e2d0dd,0xcda1f604,0xc960ebb3,
-    0xbd3e8d7e,0xb9ff90c9,0xb4bcb610,0xb07daba7,
-    0xae3afba2,0xaafbe615,0xa7b8c0cc,0xa379dd7b,
-    0x9b3660c6,0x9ff77d71,0x92b45ba8,0x9675461f,
-    0x8832161a,0x8cf30bad,0x81b02d74,0x857130c3,
-    0x5d8a9099,0x594b8d2e,0x5408abf7,0x50c9b640,
-    0x4e8ee645,0x4a4ffbf2,0x470cdd2b,0x43cdc09c,
-    0x7b827d21,0x7f436096,0x7200464f,0x76c15bf8,
-    0x68860bfd,0x6c47164a,0x61043093,0x65c52d24,
-    0x119b4be9,0x155a565e,0x18197087,0x1cd86d30,
-    0x029f3d35,0x065e2082,0x0b1d065b,0x0fdc1bec,
-    0x3793a651,0x3352bbe6,0x3e119d3f,0x3ad08088,
-    0x2497d08d,0x2056cd3a,0x2d15ebe3,0x29d4f654,
-    0xc5a92679,0xc1683bce,0xcc2b1d17,0xc8ea00a0,
-    0xd6ad50a5,0xd26c4d12,0xdf2f6bcb,0xdbee767c,
-    0xe3a1cbc1,0xe760d676,0xea23f0af,0xeee2ed18,
-    0xf0a5bd1d,0xf464a0aa,0xf9278673,0xfde69bc4,
-    0x89b8fd09,0x8d79e0be,0x803ac667,0x84fbdbd0,
-    0x9abc8bd5,0x9e7d9662,0x933eb0bb,0x97ffad0c,
-    0xafb010b1,0xab710d06,0xa6322bdf,0xa2f33668,
-    0xbcb4666d,0xb8757bda,0xb5365d03,0xb1f740b4
-];
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bytes from a fixed linear congruential generator.
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    /// Hands out at most `chunk` bytes per read, so block refills end at odd places.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.chunk).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    /// The byte-at-a-time reader `BitStream` replaced: one `read_exact` per
+    /// byte, bits taken LSB-first.
+    struct BytewiseBits<'a> {
+        data: &'a [u8],
+        bit_buffer: u8,
+        bits_left: u8,
+    }
+
+    impl BytewiseBits<'_> {
+        fn get_bit(&mut self) -> Option<bool> {
+            if self.bits_left == 0 {
+                let mut buf = [0u8; 1];
+                self.data.read_exact(&mut buf).ok()?;
+                self.bit_buffer = buf[0];
+                self.bits_left = 8;
+            }
+            self.bits_left -= 1;
+            Some(self.bit_buffer & (0x80 >> self.bits_left) != 0)
+        }
+
+        fn read_bits(&mut self, bits: u8) -> Option<u32> {
+            let mut total = 0;
+            for i in 0..bits {
+                if self.get_bit()? {
+                    total |= 1 << i;
+                }
+            }
+            Some(total)
+        }
+    }
+
+    #[test]
+    fn buffered_reads_match_bytewise_reads() {
+        let data = noise(3 * READ_BLOCK_SIZE + 5, 7);
+        for chunk in [1, 3, 7, 4096, 5000] {
+            let mut bits = BitStream::new(Trickle { data: &data, chunk });
+            let mut reference = BytewiseBits { data: &data, bit_buffer: 0, bits_left: 0 };
+            let jitter = noise(data.len() * 2, chunk as u32);
+            let mut total = 0u64;
+            for (step, jitter) in (1..=32u8).cycle().zip(jitter) {
+                // Every width in turn, mixed with random ones so the byte offset drifts.
+                let width = if jitter & 1 == 0 { step } else { jitter % 32 + 1 };
+                match reference.read_bits(width) {
+                    Some(expected) => {
+                        assert_eq!(bits.read_bits(width).unwrap(), expected, "chunk {} at bit {}", chunk, total);
+                        total += width as u64;
+                        assert_eq!(bits.get_total_bits_read(), total);
+                    }
+                    None => {
+                        assert!(bits.read_bits(width).is_err(), "chunk {} read past the end", chunk);
+                        break;
+                    }
+                }
+            }
+            assert!(total > data.len() as u64 * 8 - 32);
+        }
+    }
+
+    /// The byte-at-a-time table lookup that slicing-by-8 replaced.
+    fn bytewise_checksum(data: &[u8]) -> u32 {
+        let table: Vec<u32> = (0..256u32)
+            .map(|i| (0..8).fold(i << 24, |r, _| if r & 0x80000000 != 0 { (r << 1) ^ 0x04c11db7 } else { r << 1 }))
+            .collect();
+        // Entries of the table the old code spelled out.
+        assert_eq!((table[1], table[0x80], table[255]), (0x04c11db7, 0x690ce0ee, 0xb1f740b4));
+        data.iter().fold(0, |crc, &b| (crc << 8) ^ table[((crc >> 24) as u8 ^ b) as usize])
+    }
+
+    #[test]
+    fn sliced_crc_matches_bytewise_crc() {
+        let data = noise(64, 3);
+        for len in 0..=17 {
+            assert_eq!(checksum(&data[..len]), bytewise_checksum(&data[..len]), "length {}", len);
+        }
+        let expected = bytewise_checksum(&data);
+        assert_eq!(checksum(&data), expected);
+        for split in 0..data.len() {
+            let mut crc = Crc::new();
+            crc.update(&data[..split]);
+            crc.update(&data[split..]);
+            assert_eq!(crc.value(), expected, "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn crc_of_a_libogg_page() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/sine_mono.ogg");
+        let ogg = std::fs::read(path).unwrap();
+        let len = HEADER_BYTES + ogg[26] as usize;
+        let len = len + ogg[HEADER_BYTES..len].iter().map(|&lace| lace as usize).sum::<usize>();
+        let mut page = ogg[..len].to_vec();
+        let stored = u32::from_le_bytes(page[22..26].try_into().unwrap());
+        page[22..26].fill(0);
+        assert_eq!(checksum(&page), stored);
+        assert_eq!(bytewise_checksum(&page), stored);
+    }
+}
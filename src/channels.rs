@@ -0,0 +1,209 @@
+//! The speakers a wem's channels feed, and their order.
+//!
+//! Wwise stores channels in WAVE order with the LFE channel moved last, so a
+//! 5.1 wem holds FL FR C BL BR LFE. Vorbis mandates FL C FR BL BR LFE, and
+//! the rebuilt packets keep the Wwise order: floors and residues are coded
+//! per channel, so the order can't change without decoding the audio.
+//! Decoded output is put right with `ChannelLayout::to_vorbis_order`.
+
+use std::fmt;
+
+/// A speaker position, named after its bit in a WAVE channel mask. Wwise
+/// uses the same bits in its own channel configs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Speaker {
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    LowFrequency,
+    BackLeft,
+    BackRight,
+    FrontLeftOfCenter,
+    FrontRightOfCenter,
+    BackCenter,
+    SideLeft,
+    SideRight,
+    TopCenter,
+    TopFrontLeft,
+    TopFrontCenter,
+    TopFrontRight,
+    TopBackLeft,
+    TopBackCenter,
+    TopBackRight,
+}
+
+impl Speaker {
+    /// Every speaker, in channel mask bit order.
+    pub const ALL: [Speaker; 18] = [
+        Speaker::FrontLeft,
+        Speaker::FrontRight,
+        Speaker::FrontCenter,
+        Speaker::LowFrequency,
+        Speaker::BackLeft,
+        Speaker::BackRight,
+        Speaker::FrontLeftOfCenter,
+        Speaker::FrontRightOfCenter,
+        Speaker::BackCenter,
+        Speaker::SideLeft,
+        Speaker::SideRight,
+        Speaker::TopCenter,
+        Speaker::TopFrontLeft,
+        Speaker::TopFrontCenter,
+        Speaker::TopFrontRight,
+        Speaker::TopBackLeft,
+        Speaker::TopBackCenter,
+        Speaker::TopBackRight,
+    ];
+
+    /// The speaker's bit in a channel mask.
+    pub fn mask(self) -> u32 {
+        1 << self as u32
+    }
+
+    /// Place in the Vorbis channel order, for the speakers that order covers.
+    fn vorbis_rank(self) -> Option<u8> {
+        match self {
+            Speaker::FrontLeft => Some(0),
+            Speaker::FrontCenter => Some(1),
+            Speaker::FrontRight => Some(2),
+            Speaker::SideLeft => Some(3),
+            Speaker::SideRight => Some(4),
+            Speaker::BackLeft => Some(5),
+            Speaker::BackRight => Some(6),
+            Speaker::BackCenter => Some(7),
+            Speaker::LowFrequency => Some(8),
+            _ => None,
+        }
+    }
+}
+
+/// How a Wwise channel config describes the channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelConfigType {
+    /// Channels without speaker positions.
+    Anonymous,
+    /// Speakers given by the mask.
+    Standard,
+    /// Ambisonic components rather than speakers.
+    Ambisonic,
+    Other(u8),
+}
+
+/// Channel layout from the fmt chunk's `subtype` field. Recent Wwise
+/// releases store a channel config there: the channel count in the low byte,
+/// the config type in the next 4 bits and the speaker mask above them.
+/// Older releases store a WAVE channel mask, or nothing in short fmt chunks;
+/// wems with a vorb chunk of their own always predate channel configs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelLayout {
+    pub channels: u16,
+    /// Speakers, as a WAVE channel mask; 0 if the fmt chunk names none.
+    pub mask: u32,
+    /// `None` for a plain WAVE channel mask, or no mask at all.
+    pub config_type: Option<ChannelConfigType>,
+}
+
+impl ChannelLayout {
+    /// Decodes `subtype` for a wem of `channels` channels. `has_subtype` is
+    /// false for fmt chunks too short to hold one.
+    pub fn from_fmt(channels: u16, subtype: u32, has_subtype: bool) -> Self {
+        if !has_subtype {
+            return Self { channels, mask: 0, config_type: None };
+        }
+        // A WAVE channel mask never equals the channel count in its low
+        // byte for the layouts Wwise writes.
+        if subtype & 0xFF == channels as u32 && subtype >> 8 != 0 {
+            let config_type = match (subtype >> 8) & 0xF {
+                0 => ChannelConfigType::Anonymous,
+                1 => ChannelConfigType::Standard,
+                2 => ChannelConfigType::Ambisonic,
+                other => ChannelConfigType::Other(other as u8),
+            };
+            Self { channels, mask: subtype >> 12, config_type: Some(config_type) }
+        } else {
+            Self { channels, mask: subtype, config_type: None }
+        }
+    }
+
+    /// Takes `subtype` as a WAVE channel mask, for fmt chunks from before
+    /// channel configs; `has_subtype` as in `from_fmt`.
+    pub fn from_mask(channels: u16, subtype: u32, has_subtype: bool) -> Self {
+        Self { channels, mask: if has_subtype { subtype } else { 0 }, config_type: None }
+    }
+
+    /// The speaker of every channel, in the order the wem stores them, or
+    /// `None` if the mask doesn't name one speaker per channel.
+    pub fn speakers(&self) -> Option<Vec<Speaker>> {
+        if matches!(self.config_type, Some(ChannelConfigType::Anonymous | ChannelConfigType::Ambisonic)) {
+            return None;
+        }
+        let mut speakers: Vec<Speaker> = Speaker::ALL
+            .into_iter()
+            .filter(|speaker| self.mask & speaker.mask() != 0)
+            .collect();
+        if speakers.len() != self.channels as usize {
+            return None;
+        }
+        if let Some(lfe) = speakers.iter().position(|&speaker| speaker == Speaker::LowFrequency) {
+            let lfe = speakers.remove(lfe);
+            speakers.push(lfe);
+        }
+        Some(speakers)
+    }
+
+    /// For every channel in Vorbis order, the stored channel that feeds it.
+    /// `None` when the speakers are unknown or Vorbis defines no order for
+    /// them; mono and stereo map to themselves.
+    pub fn vorbis_order(&self) -> Option<Vec<usize>> {
+        if self.channels <= 2 {
+            return Some((0..self.channels as usize).collect());
+        }
+        let speakers = self.speakers()?;
+        if speakers.len() > 8 {
+            return None;
+        }
+        let ranks = speakers
+            .iter()
+            .map(|speaker| speaker.vorbis_rank())
+            .collect::<Option<Vec<u8>>>()?;
+        let mut order: Vec<usize> = (0..speakers.len()).collect();
+        order.sort_by_key(|&channel| ranks[channel]);
+        Some(order)
+    }
+
+    /// Whether `to_vorbis_order` moves any channel.
+    pub fn reorders(&self) -> bool {
+        self.vorbis_order()
+            .is_some_and(|order| order.iter().enumerate().any(|(to, &from)| to != from))
+    }
+
+    /// Reorders interleaved samples decoded from the converted stream, in
+    /// the wem's channel order, to the Vorbis order. Leaves them alone if
+    /// there is no such order. A trailing partial frame is left untouched.
+    pub fn to_vorbis_order<T: Copy>(&self, samples: &mut [T]) {
+        let Some(order) = self.vorbis_order().filter(|_| self.reorders()) else {
+            return;
+        };
+        let mut frame = Vec::with_capacity(order.len());
+        for samples in samples.chunks_exact_mut(order.len()) {
+            frame.clear();
+            frame.extend(order.iter().map(|&from| samples[from]));
+            samples.copy_from_slice(&frame);
+        }
+    }
+}
+
+impl fmt::Display for ChannelLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.speakers() {
+            Some(speakers) => {
+                let names: Vec<String> = speakers.iter().map(|speaker| format!("{:?}", speaker)).collect();
+                write!(f, "{}", names.join(" "))
+            }
+            None => match self.config_type {
+                Some(ChannelConfigType::Ambisonic) => write!(f, "{} ambisonic channels", self.channels),
+                _ => write!(f, "{} channels, mask 0x{:X}", self.channels, self.mask),
+            },
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use std::fs::File;
-use std::io::{self, Read, Seek, BufReader, SeekFrom};
+use std::io::{Read, Seek, SeekFrom};
 use crate::errors::{ParseError, Result};
 use crate::bit_stream::{BitStream, BitOggStreamT, BitUint, BitUintV};
 
@@ -14,7 +14,11 @@ pub fn ilog(mut v: u32) -> i32 {
 }
 
 /// Compute quantized values for lookup type 1.
+/// Empty codebooks have no values.
 pub fn book_maptype1_quantvals(entries: u32, dimensions: u32) -> u32 {
+    if entries == 0 || dimensions == 0 {
+        return 0;
+    }
     // Get a starting hint.
     let bits = ilog(entries);
     let shift = ((bits - 1) * ((dimensions - 1) as i32)) / (dimensions as i32);
@@ -23,8 +27,8 @@ pub fn book_maptype1_quantvals(entries: u32, dimensions: u32) -> u32 {
         let mut acc: u64 = 1;
         let mut acc1: u64 = 1;
         for _ in 0..dimensions {
-            acc *= vals as u64;
-            acc1 *= (vals + 1) as u64;
+            acc = acc.saturating_mul(vals as u64);
+            acc1 = acc1.saturating_mul((vals + 1) as u64);
         }
         if acc <= entries as u64 && acc1 > entries as u64 {
             return vals;
@@ -69,6 +73,9 @@ impl CodebookLibrary {
             file.read_exact(&mut buf)?;
             u32::from_le_bytes(buf) as i64
         };
+        if offset_offset > file_size - 4 {
+            return Err(ParseError::Message("codebook offset table out of range".into()));
+        }
         let codebook_count = (file_size - offset_offset) / 4;
 
         let mut codebook_data = vec![0u8; offset_offset as usize];
@@ -81,6 +88,9 @@ impl CodebookLibrary {
             file.read_exact(&mut buf)?;
             codebook_offsets.push(i32::from_le_bytes(buf) as i64);
         }
+        if codebook_offsets.windows(2).any(|w| w[0] < 0 || w[0] > w[1] || w[1] > offset_offset) {
+            return Err(ParseError::Message("codebook offsets out of order".into()));
+        }
         Ok(Self {
             codebook_data: Some(codebook_data),
             codebook_offsets: Some(codebook_offsets),
@@ -90,12 +100,12 @@ impl CodebookLibrary {
 
     pub fn get_codebook(&self, i: usize) -> Result<&[u8]> {
         if let (Some(ref data), Some(ref offsets)) = (&self.codebook_data, &self.codebook_offsets) {
-            if i >= (self.codebook_count - 1) as usize {
+            if i + 1 >= self.codebook_count as usize {
                 return Err(ParseError::Message("Invalid codebook index".into()));
             }
             let start = offsets[i] as usize;
             let end = offsets[i+1] as usize;
-            Ok(&data[start..end])
+            data.get(start..end).ok_or_else(|| ParseError::Message("Invalid codebook index".into()))
         } else {
             Err(ParseError::Message("codebook library not loaded".into()))
         }
@@ -103,7 +113,7 @@ impl CodebookLibrary {
 
     pub fn get_codebook_size(&self, i: usize) -> Result<i64> {
         if let Some(ref offsets) = self.codebook_offsets {
-            if i >= (self.codebook_count - 1) as usize {
+            if i + 1 >= self.codebook_count as usize {
                 return Err(ParseError::Message("Invalid codebook index".into()));
             }
             Ok(offsets[i+1] - offsets[i])
@@ -112,7 +122,8 @@ impl CodebookLibrary {
         }
     }
 
-    pub fn rebuild(&self, codebook_id: usize, os: &mut impl BitOggStreamT) -> Result<()> {
+    /// Codebook `codebook_id` of the library.
+    pub fn codebook(&self, codebook_id: usize) -> Result<Codebook> {
         let cb = self.get_codebook(codebook_id)?;
         let cb_size = self.get_codebook_size(codebook_id)?;
         if cb.is_empty() || cb_size == -1 {
@@ -121,81 +132,123 @@ impl CodebookLibrary {
         use std::io::Cursor;
         let mut cursor = Cursor::new(cb);
         let mut bis = BitStream::new(&mut cursor);
-        self.rebuild_from_stream(&mut bis, cb_size as u32, os)
+        Codebook::read_stripped(&mut bis, cb_size as u32)
     }
 
-    pub fn rebuild_from_stream<R: Read + Seek>(
+    pub fn rebuild(&self, codebook_id: usize, os: &mut impl BitOggStreamT) -> Result<()> {
+        self.codebook(codebook_id)?.write_to(os)
+    }
+
+    pub fn rebuild_from_stream<R: Read>(
         &self,
         bis: &mut BitStream<R>,
         cb_size: u32,
         os: &mut impl BitOggStreamT,
     ) -> Result<()> {
-        let dimensions = BitUint::<4>::read_from(bis)?;
-        let entries = BitUint::<14>::read_from(bis)?;
-        BitUint::<24>::new(0x564342)?.write_to(os)?;
-        BitUint::<16>::new(dimensions.total)?.write_to(os)?;
-        BitUint::<24>::new(entries.total)?.write_to(os)?;
-        
-        // Gather codeword lengths.
-        let ordered = BitUint::<1>::read_from(bis)?;
-        ordered.write_to(os)?;
-        if ordered.total != 0 {
-            let initial_length = BitUint::<5>::read_from(bis)?;
-            initial_length.write_to(os)?;
-            let mut current_entry: u32 = 0;
-            while current_entry < entries.total {
-                let bits = ilog(entries.total - current_entry) as usize;
-                let number = BitUintV::read_from(bis, bits)?;
-                number.write_to(os)?;
-                current_entry += number.total;
-            }
-            if current_entry > entries.total {
-                return Err(ParseError::Message("current_entry out of range".into()));
-            }
+        Codebook::read_stripped(bis, cb_size)?.write_to(os)
+    }
+
+    pub fn copy<R: Read, O: BitOggStreamT>(&self, bis: &mut BitStream<R>, os: &mut O) -> Result<()> {
+        let codebook = Codebook::read(bis)?;
+        if codebook.lookup.as_ref().is_some_and(|lookup| lookup.lookup_type == 2) {
+            return Err(ParseError::Message("didn't expect lookup type 2".into()));
+        }
+        codebook.write_to(os)
+    }
+}
+
+// -------------------- Codebook -------------------------------------------------
+/// How the codeword lengths of a codebook are stored. Lengths are in bits,
+/// 1 to 32.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodewordLengths {
+    /// Lengths that never decrease from one entry to the next, stored as the
+    /// number of entries of each length from `initial_length` up.
+    Ordered { initial_length: u8, counts: Vec<u32> },
+    /// A length per entry; `None` for the entries a sparse codebook leaves unused.
+    Listed { sparse: bool, lengths: Vec<Option<u8>> },
+}
+
+/// The vector lookup table of a codebook.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodebookLookup {
+    /// 1 for a lattice of `book_maptype1_quantvals` values shared by all
+    /// dimensions, 2 for a value per entry and dimension.
+    pub lookup_type: u8,
+    /// Packed Vorbis float32s, as stored.
+    pub minimum_value: u32,
+    pub delta_value: u32,
+    /// Bits per multiplicand, 1 to 16.
+    pub value_bits: u8,
+    pub sequence_p: bool,
+    pub multiplicands: Vec<u32>,
+}
+
+/// A codebook of a Vorbis setup header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Codebook {
+    pub dimensions: u16,
+    pub entries: u32,
+    pub lengths: CodewordLengths,
+    pub lookup: Option<CodebookLookup>,
+}
+
+impl Codebook {
+    /// Reads a codebook as stored in a standard setup header.
+    pub fn read<R: Read>(bis: &mut BitStream<R>) -> Result<Self> {
+        if bis.read_bits(24)? != 0x564342 {
+            return Err(ParseError::Message("invalid codebook identifier".into()));
+        }
+        let dimensions = bis.read_bits(16)? as u16;
+        let entries = bis.read_bits(24)?;
+        let lengths = if bis.get_bit()? {
+            read_ordered_lengths(bis, entries)?
         } else {
-            let codeword_length_length = BitUint::<3>::read_from(bis)?;
-            let sparse = BitUint::<1>::read_from(bis)?;
-            if codeword_length_length.total == 0 || codeword_length_length.total > 5 {
-                return Err(ParseError::Message("nonsense codeword length".into()));
+            let sparse = bis.get_bit()?;
+            let mut lengths = Vec::new();
+            for _ in 0..entries {
+                let present = !sparse || bis.get_bit()?;
+                lengths.push(if present { Some(bis.read_bits(5)? as u8 + 1) } else { None });
             }
-            sparse.write_to(os)?;
+            CodewordLengths::Listed { sparse, lengths }
+        };
+        let lookup = match bis.read_bits(4)? {
+            0 => None,
+            lookup_type @ (1 | 2) => Some(read_lookup(bis, lookup_type as u8, entries, dimensions)?),
+            _ => return Err(ParseError::Message("invalid lookup type".into())),
+        };
+        Ok(Self { dimensions, entries, lengths, lookup })
+    }
 
-            for i in 0..entries.total {
-                let mut present_bool = true;
-                if sparse.total != 0 {
-                    let present = BitUint::<1>::read_from(bis)?;
-                    present.write_to(os)?;
-                    present_bool = present.total != 0;
-                }
-                if present_bool{
-                    let codeword_length = BitUintV::read_from(bis, codeword_length_length.total as usize)?;
-                    BitUint::<5>::new(codeword_length.total)?.write_to(os)?;
-                }
+    /// Reads a codebook in Wwise's compact form, which drops the sync
+    /// pattern, narrows the sizes and stores codeword lengths in as few bits
+    /// as they need. `cb_size`, unless 0, is the size in bytes it has to take up.
+    pub fn read_stripped<R: Read>(bis: &mut BitStream<R>, cb_size: u32) -> Result<Self> {
+        let dimensions = bis.read_bits(4)? as u16;
+        let entries = bis.read_bits(14)?;
+        let lengths = if bis.get_bit()? {
+            read_ordered_lengths(bis, entries)?
+        } else {
+            let codeword_length_length = bis.read_bits(3)?;
+            let sparse = bis.get_bit()?;
+            if codeword_length_length == 0 || codeword_length_length > 5 {
+                return Err(ParseError::Message("nonsense codeword length".into()));
             }
-        }
-        let lookup_type = BitUint::<1>::read_from(bis)?;
-        BitUint::<4>::new(lookup_type.total)?.write_to(os)?;
-        if lookup_type.total == 0 {
-            // nothing
-        } else if lookup_type.total == 1 {
-            let min = BitUint::<32>::read_from(bis)?;
-            let max = BitUint::<32>::read_from(bis)?;
-            let value_length = BitUint::<4>::read_from(bis)?;
-            let sequence_flag = BitUint::<1>::read_from(bis)?;
-            min.write_to(os)?;
-            max.write_to(os)?;
-            value_length.write_to(os)?;
-            sequence_flag.write_to(os)?;
-            let quantvals = book_maptype1_quantvals(entries.total, dimensions.total);
-            for _ in 0..quantvals {
-                let val = BitUintV::read_from(bis, (value_length.total + 1) as usize)?;
-                val.write_to(os)?;
+            let mut lengths = Vec::new();
+            for _ in 0..entries {
+                let present = !sparse || bis.get_bit()?;
+                lengths.push(if present {
+                    Some(bis.read_bits(codeword_length_length as u8)? as u8 + 1)
+                } else {
+                    None
+                });
             }
-        } else if lookup_type.total == 2 {
-            return Err(ParseError::Message("didn't expect lookup type 2".into()));
-        } else {
-            return Err(ParseError::Message("invalid lookup type".into()));
-        }
+            CodewordLengths::Listed { sparse, lengths }
+        };
+        let lookup = match bis.read_bits(1)? {
+            0 => None,
+            _ => Some(read_lookup(bis, 1, entries, dimensions)?),
+        };
         if cb_size != 0 && (bis.get_total_bits_read() / 8 + 1) != cb_size as u64 {
             return Err(ParseError::Message(format!(
                 "Size mismatch: expected {}, got {}",
@@ -203,77 +256,85 @@ impl CodebookLibrary {
                 bis.get_total_bits_read() / 8 + 1
             )));
         }
-        
-        Ok(())
+        Ok(Self { dimensions, entries, lengths, lookup })
     }
 
-    pub fn copy<R: Read + Seek, O: BitOggStreamT>(&self, bis: &mut BitStream<R>, os: &mut O) -> Result<()> {
-        let id = BitUint::<24>::read_from(bis)?;
-        let dimensions = BitUint::<16>::read_from(bis)?;
-        let entries = BitUint::<24>::read_from(bis)?;
-        if id.total != 0x564342 {
-            return Err(ParseError::Message("invalid codebook identifier".into()));
-        }
-        id.write_to(os)?;
-        BitUint::<16>::new(dimensions.total)?.write_to(os)?;
-        BitUint::<24>::new(entries.total)?.write_to(os)?;
-
-        let ordered = BitUint::<1>::read_from(bis)?;
-        ordered.write_to(os)?;
-        if ordered.total != 0 {
-            let initial_length = BitUint::<5>::read_from(bis)?;
-            initial_length.write_to(os)?;
-            let mut current_entry: u32 = 0;
-            while current_entry < entries.total {
-                let bits = ilog(entries.total - current_entry) as usize;
-                let number = BitUintV::read_from(bis, bits)?;
-                number.write_to(os)?;
-                current_entry += number.total;
-            }
-            if current_entry > entries.total {
-                return Err(ParseError::Message("current_entry out of range".into()));
+    /// Writes the codebook as a standard setup header stores it.
+    pub fn write_to<O: BitOggStreamT>(&self, os: &mut O) -> Result<()> {
+        BitUint::<24>::new(0x564342)?.write_to(os)?;
+        BitUint::<16>::new(self.dimensions as u32)?.write_to(os)?;
+        BitUint::<24>::new(self.entries)?.write_to(os)?;
+        match &self.lengths {
+            CodewordLengths::Ordered { initial_length, counts } => {
+                BitUint::<1>::new(1)?.write_to(os)?;
+                BitUint::<5>::new((*initial_length as u32).wrapping_sub(1))?.write_to(os)?;
+                let mut current_entry = 0u32;
+                for &count in counts {
+                    let bits = ilog(self.entries.saturating_sub(current_entry)) as usize;
+                    BitUintV::new(bits, count)?.write_to(os)?;
+                    current_entry = current_entry.saturating_add(count);
+                }
             }
-        } else {
-            let sparse = BitUint::<1>::read_from(bis)?;
-            sparse.write_to(os)?;
-            for _ in 0..entries.total {
-                let present = if sparse.total != 0 {
-                    BitUint::<1>::read_from(bis)?
-                } else {
-                    BitUint::<1>::new(1)?
-                };
-                present.write_to(os)?;
-                if present.total != 0 {
-                    let codeword_length = BitUint::<5>::read_from(bis)?;
-                    codeword_length.write_to(os)?;
+            CodewordLengths::Listed { sparse, lengths } => {
+                BitUint::<1>::new(0)?.write_to(os)?;
+                BitUint::<1>::new(*sparse as u32)?.write_to(os)?;
+                for length in lengths {
+                    if *sparse {
+                        BitUint::<1>::new(length.is_some() as u32)?.write_to(os)?;
+                    }
+                    match length {
+                        Some(length) => BitUint::<5>::new((*length as u32).wrapping_sub(1))?.write_to(os)?,
+                        None if !sparse => return Err(ParseError::Message("unused entry in a dense codebook".into())),
+                        None => {}
+                    }
                 }
             }
         }
-
-        let lookup_type = BitUint::<4>::read_from(bis)?;
-        lookup_type.write_to(os)?;
-        if lookup_type.total == 0 {
-            // nothing
-        } else if lookup_type.total == 1 {
-            let min = BitUint::<32>::read_from(bis)?;
-            let max = BitUint::<32>::read_from(bis)?;
-            let value_length = BitUint::<4>::read_from(bis)?;
-            let sequence_flag = BitUint::<1>::read_from(bis)?;
-            min.write_to(os)?;
-            max.write_to(os)?;
-            value_length.write_to(os)?;
-            sequence_flag.write_to(os)?;
-            let quantvals = book_maptype1_quantvals(entries.total, dimensions.total);
-            for _ in 0..quantvals {
-                let val = BitUintV::read_from(bis, (value_length.total + 1) as usize)?;
-                val.write_to(os)?;
+        match &self.lookup {
+            None => BitUint::<4>::new(0)?.write_to(os)?,
+            Some(lookup) => {
+                BitUint::<4>::new(lookup.lookup_type as u32)?.write_to(os)?;
+                BitUint::<32>::new(lookup.minimum_value)?.write_to(os)?;
+                BitUint::<32>::new(lookup.delta_value)?.write_to(os)?;
+                BitUint::<4>::new((lookup.value_bits as u32).wrapping_sub(1))?.write_to(os)?;
+                BitUint::<1>::new(lookup.sequence_p as u32)?.write_to(os)?;
+                for &value in &lookup.multiplicands {
+                    BitUintV::new(lookup.value_bits as usize, value)?.write_to(os)?;
+                }
             }
-        } else if lookup_type.total == 2 {
-            return Err(ParseError::Message("didn't expect lookup type 2".into()));
-        } else {
-            return Err(ParseError::Message("invalid lookup type".into()));
         }
-        
         Ok(())
     }
 }
+
+fn read_ordered_lengths<R: Read>(bis: &mut BitStream<R>, entries: u32) -> Result<CodewordLengths> {
+    let initial_length = bis.read_bits(5)? as u8 + 1;
+    let mut counts = Vec::new();
+    let mut current_entry: u32 = 0;
+    while current_entry < entries {
+        let count = bis.read_bits(ilog(entries - current_entry) as u8)?;
+        counts.push(count);
+        current_entry += count;
+    }
+    if current_entry > entries {
+        return Err(ParseError::Message("current_entry out of range".into()));
+    }
+    Ok(CodewordLengths::Ordered { initial_length, counts })
+}
+
+fn read_lookup<R: Read>(bis: &mut BitStream<R>, lookup_type: u8, entries: u32, dimensions: u16) -> Result<CodebookLookup> {
+    let minimum_value = bis.read_bits(32)?;
+    let delta_value = bis.read_bits(32)?;
+    let value_bits = bis.read_bits(4)? as u8 + 1;
+    let sequence_p = bis.get_bit()?;
+    let values = if lookup_type == 1 {
+        book_maptype1_quantvals(entries, dimensions as u32) as u64
+    } else {
+        entries as u64 * dimensions as u64
+    };
+    let mut multiplicands = Vec::new();
+    for _ in 0..values {
+        multiplicands.push(bis.read_bits(value_bits)?);
+    }
+    Ok(CodebookLookup { lookup_type, minimum_value, delta_value, value_bits, sequence_p, multiplicands })
+}
@@ -5,6 +5,61 @@ pub enum ParseError {
     Io(io::Error),
     Message(String),
     File(String),
+    Verify(VerifyError),
+    /// Stopped through a `CancellationToken`.
+    Cancelled,
+}
+
+/// Ways a generated Ogg stream can fail post-conversion verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The Ogg framing or Vorbis headers are broken.
+    InvalidStream(String),
+    /// The setup header in the output does not parse.
+    SetupUnparseable(String),
+    /// An audio packet refers to a mode the setup header doesn't define.
+    InvalidMode { packet: u64, mode: u32, mode_count: usize },
+    /// The final granule doesn't match the wem's sample count.
+    GranuleMismatch { expected: u64, actual: Option<u64> },
+    /// Without packet granules: `sample_count` doesn't end within the last
+    /// audio packet, whose samples run from `start` to `end`.
+    SampleCountMismatch { sample_count: u64, start: u64, end: u64 },
+    /// The audio packets written, with their Wwise headers, don't fill the
+    /// data chunk after the first audio packet offset.
+    DataSizeMismatch { expected: u64, actual: u64 },
+    /// A seek table entry doesn't point at the start of an audio packet.
+    SeekTableMismatch { offset: u64 },
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::InvalidStream(s) => write!(f, "invalid Ogg stream: {}", s),
+            VerifyError::SetupUnparseable(s) => write!(f, "setup header does not parse: {}", s),
+            VerifyError::InvalidMode { packet, mode, mode_count } => write!(
+                f,
+                "audio packet {} uses mode {} but only {} modes are defined",
+                packet, mode, mode_count
+            ),
+            VerifyError::GranuleMismatch { expected, actual: Some(actual) } => {
+                write!(f, "final granule {} doesn't match sample count {}", actual, expected)
+            }
+            VerifyError::GranuleMismatch { expected, actual: None } => {
+                write!(f, "no final granule, expected sample count {}", expected)
+            }
+            VerifyError::SampleCountMismatch { sample_count, start, end } => write!(
+                f,
+                "sample count {} isn't within the last audio packet, which covers samples {} to {}",
+                sample_count, start, end
+            ),
+            VerifyError::DataSizeMismatch { expected, actual } => {
+                write!(f, "audio packets written account for {} bytes but the data chunk holds {}", actual, expected)
+            }
+            VerifyError::SeekTableMismatch { offset } => {
+                write!(f, "seek table entry at offset {} isn't the start of an audio packet", offset)
+            }
+        }
+    }
 }
 
 impl std::fmt::Display for ParseError {
@@ -13,6 +68,8 @@ impl std::fmt::Display for ParseError {
             ParseError::File(s) => write!(f, "File open error: {}", s),
             ParseError::Io(e) => write!(f, "IO error: {}", e),
             ParseError::Message(s) => write!(f, "Parse error: {}", s),
+            ParseError::Verify(e) => write!(f, "Verification error: {}", e),
+            ParseError::Cancelled => write!(f, "Conversion cancelled"),
         }
     }
 }
@@ -25,4 +82,19 @@ impl From<io::Error> for ParseError {
     }
 }
 
+impl From<ParseError> for io::Error {
+    fn from(e: ParseError) -> Self {
+        match e {
+            ParseError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+impl From<VerifyError> for ParseError {
+    fn from(e: VerifyError) -> Self {
+        ParseError::Verify(e)
+    }
+}
+
 pub type Result<T> = std::result::Result<T, ParseError>;
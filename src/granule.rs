@@ -0,0 +1,54 @@
+//! Granule positions for wems whose packet headers carry none.
+//!
+//! Wems with 2 byte packet headers store no granule per packet, and ww2ogg
+//! writes 0 on every page of their output, which leaves players unable to
+//! seek or trim the end. The granules are derived here instead, the way a
+//! Vorbis decoder counts samples: each audio packet after the first
+//! completes a quarter of the previous block and a quarter of its own.
+
+#[derive(Clone)]
+pub(crate) struct GranuleTracker {
+    blocksizes: [u64; 2],
+    prev_blocksize: Option<u64>,
+    granule: u64,
+}
+
+impl GranuleTracker {
+    pub(crate) fn new(blocksize_0_pow: u8, blocksize_1_pow: u8) -> Self {
+        Self {
+            blocksizes: [1 << blocksize_0_pow, 1 << blocksize_1_pow],
+            prev_blocksize: None,
+            granule: 0,
+        }
+    }
+
+    /// Granule after the packets pushed so far.
+    pub(crate) fn granule(&self) -> u64 {
+        self.granule
+    }
+
+    /// Accounts for the next audio packet and returns the granule after it.
+    pub(crate) fn push(&mut self, blockflag: bool) -> u64 {
+        let blocksize = self.blocksizes[blockflag as usize];
+        if let Some(prev) = self.prev_blocksize {
+            self.granule += prev / 4 + blocksize / 4;
+        }
+        self.prev_blocksize = Some(blocksize);
+        self.granule
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GranuleTracker;
+
+    #[test]
+    fn granules_follow_the_block_sizes() {
+        let mut granules = GranuleTracker::new(8, 11);
+        let flags = [true, true, false, false, true];
+        let pushed: Vec<u64> = flags.iter().map(|&long| granules.push(long)).collect();
+        // The first packet only primes the window; long-long adds 512 + 512,
+        // long-short 512 + 64, short-short 64 + 64, short-long 64 + 512.
+        assert_eq!(pushed, [0, 1024, 1600, 1728, 2304]);
+    }
+}
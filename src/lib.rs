@@ -1,4 +1,24 @@
 pub mod wwriff;
 pub mod bit_stream;
+pub mod channels;
 pub mod codebook;
-pub mod errors;
\ No newline at end of file
+pub mod errors;
+mod granule;
+pub mod limits;
+pub mod ogg;
+pub mod options;
+pub mod overrides;
+pub mod progress;
+pub mod range;
+pub mod recovery;
+pub mod seek;
+pub mod setup;
+pub mod stats;
+pub mod stream;
+pub mod sub_reader;
+pub mod version;
+pub mod vorb;
+#[cfg(feature = "async")]
+pub mod async_io;
+#[cfg(feature = "mmap")]
+pub mod mmap;
@@ -0,0 +1,35 @@
+/// Caps on what a wem may ask the converter to do, so that malformed or
+/// hostile input fails with an error instead of exhausting memory.
+/// Anything over a cap is rejected with `ParseError::Message`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Largest buffer whose size comes from the input, such as the chunks a
+    /// `WemStream` keeps in memory before the first audio packet.
+    pub max_allocation: usize,
+    /// Largest audio packet payload, in bytes.
+    pub max_packet_size: u32,
+    /// Most channels accepted in the fmt chunk. A Vorbis stream holds at
+    /// most 255, so no setting lets more through.
+    pub max_channels: u8,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_allocation: 16 << 20,
+            max_packet_size: 1 << 16,
+            max_channels: 255,
+        }
+    }
+}
+
+impl Limits {
+    /// No caps beyond what the formats themselves allow.
+    pub fn unlimited() -> Self {
+        Self {
+            max_allocation: usize::MAX,
+            max_packet_size: u32::MAX,
+            max_channels: u8::MAX,
+        }
+    }
+}
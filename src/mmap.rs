@@ -0,0 +1,53 @@
+//! Memory-mapped file input (`mmap` feature).
+//!
+//! A mapped wem still parses its header through a `Cursor` over the mapping,
+//! but packet headers are decoded in place and audio payloads are handed to
+//! the converter as ranges of the mapping, without being copied out.
+
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::errors::{ParseError, Result};
+use crate::options::WemOptions;
+use crate::wwriff::WwiseRiffVorbis;
+
+/// A memory-mapped file. Clones share the mapping.
+#[derive(Debug, Clone)]
+pub struct MappedFile(Arc<memmap2::Mmap>);
+
+impl MappedFile {
+    /// Maps `file`, which must not be modified or truncated while the mapping is alive.
+    pub fn map(file: &File) -> Result<Self> {
+        // SAFETY: the mapping is only read; see the note above about changes to the file.
+        let map = unsafe { memmap2::Mmap::map(file)? };
+        Ok(Self(Arc::new(map)))
+    }
+
+    /// Bytes `range` of the file, if it has them.
+    pub(crate) fn get(&self, range: Range<usize>) -> Option<&[u8]> {
+        self.0.get(range)
+    }
+}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl WwiseRiffVorbis<Cursor<MappedFile>> {
+    /// Same as `new` for a file, but memory-maps it so packets are read
+    /// straight from the mapping.
+    /// The file must not be modified or truncated while the wem is alive.
+    pub fn new_mmap(name: &str, options: &WemOptions) -> Result<Self> {
+        let file = File::open(name).map_err(|_| ParseError::File(name.to_string()))?;
+        let map = MappedFile::map(&file)?;
+        // Nothing to buffer: the cursor reads from memory already.
+        let infile = BufReader::with_capacity(0, Cursor::new(map.clone()));
+        let mut wem = Self::from_buf_reader(infile, name, options)?;
+        wem.mapped = Some(map);
+        Ok(wem)
+    }
+}
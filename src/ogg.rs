@@ -0,0 +1,541 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{ErrorKind, Read};
+
+use crate::bit_stream::{BitStream, Crc};
+use crate::codebook::ilog;
+use crate::errors::{ParseError, Result};
+use crate::setup::VorbisSetup;
+
+const HEADER_BYTES: usize = 27;
+const GRANULE_NONE: u64 = u64::MAX;
+
+// -------------------- Pages ---------------------------------------------------
+#[derive(Debug, Clone)]
+pub struct OggPage {
+    /// Byte offset of the page in the physical stream.
+    pub offset: u64,
+    pub version: u8,
+    pub header_type: u8,
+    pub granule: u64,
+    pub serial: u32,
+    pub seqno: u32,
+    pub checksum: u32,
+    pub lacing: Vec<u8>,
+    pub data: Vec<u8>,
+    /// Whether `checksum` matches the page contents.
+    pub crc_ok: bool,
+}
+
+impl OggPage {
+    pub fn continued(&self) -> bool {
+        self.header_type & 1 != 0
+    }
+    pub fn bos(&self) -> bool {
+        self.header_type & 2 != 0
+    }
+    pub fn eos(&self) -> bool {
+        self.header_type & 4 != 0
+    }
+    /// The granule position, or None for -1 (no packet finishes on this page).
+    pub fn granule(&self) -> Option<u64> {
+        if self.granule == GRANULE_NONE { None } else { Some(self.granule) }
+    }
+    /// Total size of the page including its header.
+    pub fn size(&self) -> usize {
+        HEADER_BYTES + self.lacing.len() + self.data.len()
+    }
+    /// Whether the last packet on this page continues on the next one.
+    pub fn ends_unfinished(&self) -> bool {
+        self.lacing.last() == Some(&255)
+    }
+    /// Number of packets that finish on this page.
+    pub fn completed_packets(&self) -> usize {
+        self.lacing.iter().filter(|&&lace| lace < 255).count()
+    }
+}
+
+/// Reads Ogg pages one at a time from a byte stream.
+pub struct OggPageReader<R: Read> {
+    reader: R,
+    offset: u64,
+}
+
+impl<R: Read> OggPageReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, offset: 0 }
+    }
+
+    /// Byte offset of the next page.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Reads the next page, or None at a clean end of stream.
+    pub fn next_page(&mut self) -> Result<Option<OggPage>> {
+        let mut header = [0u8; HEADER_BYTES];
+        let got = read_fully(&mut self.reader, &mut header)?;
+        if got == 0 {
+            return Ok(None);
+        }
+        if got < HEADER_BYTES {
+            return Err(ParseError::Message("page header truncated".into()));
+        }
+        if &header[0..4] != b"OggS" {
+            return Err(ParseError::Message(format!("missing OggS capture pattern at offset {}", self.offset)));
+        }
+        let mut lacing = vec![0u8; header[26] as usize];
+        if read_fully(&mut self.reader, &mut lacing)? < lacing.len() {
+            return Err(ParseError::Message("page header truncated".into()));
+        }
+        let data_size = lacing.iter().map(|&lace| lace as usize).sum();
+        let mut data = vec![0u8; data_size];
+        if read_fully(&mut self.reader, &mut data)? < data_size {
+            return Err(ParseError::Message("page truncated".into()));
+        }
+
+        let checksum = u32::from_le_bytes([header[22], header[23], header[24], header[25]]);
+        header[22..26].fill(0);
+        let mut crc = Crc::new();
+        crc.update(&header);
+        crc.update(&lacing);
+        crc.update(&data);
+
+        let page = OggPage {
+            offset: self.offset,
+            version: header[4],
+            header_type: header[5],
+            granule: u64::from_le_bytes(header[6..14].try_into().unwrap()),
+            serial: u32::from_le_bytes(header[14..18].try_into().unwrap()),
+            seqno: u32::from_le_bytes(header[18..22].try_into().unwrap()),
+            checksum,
+            lacing,
+            data,
+            crc_ok: crc.value() == checksum,
+        };
+        self.offset += page.size() as u64;
+        Ok(Some(page))
+    }
+}
+
+fn read_fully<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut got = 0;
+    while got < buf.len() {
+        match reader.read(&mut buf[got..]) {
+            Ok(0) => break,
+            Ok(n) => got += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(got)
+}
+
+// -------------------- Packets -------------------------------------------------
+#[derive(Debug, Clone)]
+pub struct OggPacket {
+    pub serial: u32,
+    pub data: Vec<u8>,
+    /// Granule of the page this packet finished on, if it is the last packet finishing there.
+    pub granule: Option<u64>,
+    /// First packet of its logical stream.
+    pub bos: bool,
+    /// Last packet of its logical stream.
+    pub eos: bool,
+}
+
+/// Reassembles the packets of one logical stream from its pages.
+#[derive(Debug, Default)]
+struct PacketAssembler {
+    partial: Vec<u8>,
+    in_packet: bool,
+    packets_out: u64,
+}
+
+impl PacketAssembler {
+    /// Splits `page` into packets. Continuation mismatches are returned as
+    /// messages; the affected data is dropped.
+    fn push_page(&mut self, page: &OggPage, out: &mut Vec<OggPacket>, problems: &mut Vec<String>) {
+        if page.continued() && !self.in_packet {
+            problems.push("continued page without a packet to continue".into());
+        } else if !page.continued() && self.in_packet {
+            problems.push("previous packet not finished but page is not marked continued".into());
+            self.partial.clear();
+            self.in_packet = false;
+        }
+        let completed = page.completed_packets();
+        let mut finished = 0;
+        let mut pos = 0;
+        let mut skipping = page.continued() && !self.in_packet;
+        for &lace in &page.lacing {
+            let end = pos + lace as usize;
+            if !skipping {
+                self.partial.extend_from_slice(&page.data[pos..end]);
+                self.in_packet = true;
+            }
+            pos = end;
+            if lace < 255 {
+                finished += 1;
+                if !skipping {
+                    out.push(OggPacket {
+                        serial: page.serial,
+                        data: std::mem::take(&mut self.partial),
+                        granule: if finished == completed { page.granule() } else { None },
+                        bos: self.packets_out == 0,
+                        eos: page.eos() && finished == completed,
+                    });
+                    self.packets_out += 1;
+                }
+                self.in_packet = false;
+                skipping = false;
+            }
+        }
+    }
+}
+
+/// Reads complete packets across all logical streams of an Ogg file.
+pub struct OggPacketReader<R: Read> {
+    pages: OggPageReader<R>,
+    streams: HashMap<u32, PacketAssembler>,
+    ready: std::collections::VecDeque<OggPacket>,
+}
+
+impl<R: Read> OggPacketReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            pages: OggPageReader::new(reader),
+            streams: HashMap::new(),
+            ready: Default::default(),
+        }
+    }
+
+    /// Returns the next complete packet, or None at end of stream.
+    /// Pages with a bad CRC are an error.
+    pub fn next_packet(&mut self) -> Result<Option<OggPacket>> {
+        while self.ready.is_empty() {
+            let page = match self.pages.next_page()? {
+                Some(p) => p,
+                None => return Ok(None),
+            };
+            if !page.crc_ok {
+                return Err(ParseError::Message(format!("CRC mismatch in page at offset {}", page.offset)));
+            }
+            let mut out = Vec::new();
+            let mut problems = Vec::new();
+            self.streams.entry(page.serial).or_default().push_page(&page, &mut out, &mut problems);
+            if let Some(problem) = problems.into_iter().next() {
+                return Err(ParseError::Message(problem));
+            }
+            self.ready.extend(out);
+        }
+        Ok(self.ready.pop_front())
+    }
+}
+
+// -------------------- Vorbis headers ------------------------------------------
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VorbisIdentification {
+    pub version: u32,
+    pub channels: u8,
+    pub sample_rate: u32,
+    pub bitrate_maximum: i32,
+    pub bitrate_nominal: i32,
+    pub bitrate_minimum: i32,
+    pub blocksize_0_pow: u8,
+    pub blocksize_1_pow: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VorbisComment {
+    pub vendor: String,
+    pub comments: Vec<String>,
+}
+
+pub(crate) fn check_vorbis_header(packet: &[u8], packet_type: u8) -> Result<()> {
+    if packet.len() < 7 || packet[0] != packet_type || &packet[1..7] != b"vorbis" {
+        return Err(ParseError::Message(format!("not a Vorbis header packet of type {}", packet_type)));
+    }
+    Ok(())
+}
+
+pub fn parse_identification(packet: &[u8]) -> Result<VorbisIdentification> {
+    check_vorbis_header(packet, 1)?;
+    if packet.len() < 30 {
+        return Err(ParseError::Message("identification header truncated".into()));
+    }
+    let u32_at = |i: usize| u32::from_le_bytes(packet[i..i + 4].try_into().unwrap());
+    let ident = VorbisIdentification {
+        version: u32_at(7),
+        channels: packet[11],
+        sample_rate: u32_at(12),
+        bitrate_maximum: u32_at(16) as i32,
+        bitrate_nominal: u32_at(20) as i32,
+        bitrate_minimum: u32_at(24) as i32,
+        blocksize_0_pow: packet[28] & 0x0F,
+        blocksize_1_pow: packet[28] >> 4,
+    };
+    if ident.version != 0 {
+        return Err(ParseError::Message("unsupported Vorbis version".into()));
+    }
+    if ident.channels == 0 || ident.sample_rate == 0 {
+        return Err(ParseError::Message("zero channels or sample rate".into()));
+    }
+    if ident.blocksize_0_pow < 6
+        || ident.blocksize_1_pow > 13
+        || ident.blocksize_0_pow > ident.blocksize_1_pow
+    {
+        return Err(ParseError::Message("invalid blocksizes".into()));
+    }
+    if packet[29] & 1 == 0 {
+        return Err(ParseError::Message("identification framing bit not set".into()));
+    }
+    Ok(ident)
+}
+
+pub fn parse_comment(packet: &[u8]) -> Result<VorbisComment> {
+    check_vorbis_header(packet, 3)?;
+    let mut pos = 7;
+    let mut take = |len: usize| -> Result<&[u8]> {
+        if packet.len() - pos < len {
+            return Err(ParseError::Message("comment header truncated".into()));
+        }
+        pos += len;
+        Ok(&packet[pos - len..pos])
+    };
+    let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap()) as usize;
+
+    let vendor_len = read_u32(take(4)?);
+    let vendor = String::from_utf8_lossy(take(vendor_len)?).into_owned();
+    let count = read_u32(take(4)?);
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        let len = read_u32(take(4)?);
+        comments.push(String::from_utf8_lossy(take(len)?).into_owned());
+    }
+    if take(1)?[0] & 1 == 0 {
+        return Err(ParseError::Message("comment framing bit not set".into()));
+    }
+    Ok(VorbisComment { vendor, comments })
+}
+
+/// Walks a standard Vorbis setup header and returns the block flag of every mode.
+pub fn parse_setup_modes(packet: &[u8], channels: u8) -> Result<Vec<bool>> {
+    Ok(VorbisSetup::parse(packet, channels)?.mode_blockflag())
+}
+
+/// Returns the mode number of a standard Vorbis audio packet.
+pub fn audio_packet_mode(packet: &[u8], mode_count: usize) -> Result<u32> {
+    let mut ss = BitStream::new(packet);
+    if ss.read_bits(1)? != 0 {
+        return Err(ParseError::Message("not an audio packet".into()));
+    }
+    ss.read_bits(ilog(mode_count.saturating_sub(1) as u32) as u8)
+}
+
+// -------------------- Validation ----------------------------------------------
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// Byte offset of the page the problem was found in.
+    pub offset: u64,
+    pub serial: Option<u32>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.serial {
+            Some(serial) => write!(f, "offset {} (stream {:#x}): {}", self.offset, serial, self.message),
+            None => write!(f, "offset {}: {}", self.offset, self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StreamReport {
+    pub serial: u32,
+    pub pages: u64,
+    /// All packets, including the three headers.
+    pub packets: u64,
+    pub audio_packets: u64,
+    /// Granule of the last page that had one.
+    pub last_granule: Option<u64>,
+    pub eos: bool,
+    pub identification: Option<VorbisIdentification>,
+    pub comment: Option<VorbisComment>,
+    /// Block flag per mode, if the setup header parsed.
+    pub mode_blockflag: Option<Vec<bool>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub bytes: u64,
+    pub pages: u64,
+    pub packets: u64,
+    /// Logical streams in the order their first page appeared.
+    pub streams: Vec<StreamReport>,
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct StreamState {
+    index: usize,
+    next_seqno: u32,
+    assembler: PacketAssembler,
+}
+
+/// Reads a whole Ogg file and checks its page structure (capture pattern,
+/// CRCs, sequence numbers, lacing, BOS/EOS flags, granule monotonicity) and
+/// the Vorbis header triad and audio packet modes of every logical stream.
+/// Problems are collected in the report; only I/O errors are returned as `Err`.
+pub fn validate_ogg<R: Read>(reader: R) -> Result<Report> {
+    let mut pages = OggPageReader::new(reader);
+    let mut report = Report::default();
+    let mut states: HashMap<u32, StreamState> = HashMap::new();
+
+    loop {
+        let offset = pages.offset();
+        let page = match pages.next_page() {
+            Ok(Some(page)) => page,
+            Ok(None) => break,
+            Err(ParseError::Message(message)) => {
+                report.issues.push(Issue { offset, serial: None, message });
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        report.pages += 1;
+        let mut problems = Vec::new();
+
+        if page.version != 0 {
+            problems.push(format!("unsupported stream structure version {}", page.version));
+        }
+        if !page.crc_ok {
+            problems.push("CRC mismatch".into());
+        }
+        if page.header_type & !7 != 0 {
+            problems.push(format!("reserved header type bits set ({:#x})", page.header_type));
+        }
+
+        let state = match states.get_mut(&page.serial) {
+            Some(state) => {
+                if page.bos() {
+                    problems.push("BOS flag on a page that is not the first of its stream".into());
+                }
+                if report.streams[state.index].eos {
+                    problems.push("page after EOS".into());
+                }
+                if page.seqno != state.next_seqno {
+                    problems.push(format!(
+                        "page sequence number {} where {} was expected",
+                        page.seqno, state.next_seqno
+                    ));
+                }
+                state
+            }
+            None => {
+                if !page.bos() {
+                    problems.push("first page of stream lacks BOS flag".into());
+                }
+                report.streams.push(StreamReport { serial: page.serial, ..Default::default() });
+                states.entry(page.serial).or_insert(StreamState {
+                    index: report.streams.len() - 1,
+                    ..Default::default()
+                })
+            }
+        };
+        state.next_seqno = page.seqno.wrapping_add(1);
+
+        let stream = &mut report.streams[state.index];
+        stream.pages += 1;
+        stream.eos |= page.eos();
+        match page.granule() {
+            Some(granule) => {
+                if page.completed_packets() == 0 {
+                    problems.push("granule set on a page where no packet finishes".into());
+                }
+                if let Some(last) = stream.last_granule {
+                    if granule < last {
+                        problems.push(format!("granule {} decreases from {}", granule, last));
+                    }
+                }
+                stream.last_granule = Some(granule);
+            }
+            None => {
+                if page.completed_packets() != 0 {
+                    problems.push("granule -1 on a page where a packet finishes".into());
+                }
+            }
+        }
+
+        let mut packets = Vec::new();
+        state.assembler.push_page(&page, &mut packets, &mut problems);
+        for packet in packets {
+            report.packets += 1;
+            stream.packets += 1;
+            match check_vorbis_packet(stream, &packet.data) {
+                Ok(()) => {}
+                Err(ParseError::Message(message)) => problems.push(message),
+                Err(err) => problems.push(err.to_string()),
+            }
+        }
+        report.issues.extend(problems.into_iter().map(|message| Issue {
+            offset,
+            serial: Some(page.serial),
+            message,
+        }));
+    }
+
+    report.bytes = pages.offset();
+    for stream in &report.streams {
+        let offset = report.bytes;
+        let serial = Some(stream.serial);
+        if !stream.eos {
+            report.issues.push(Issue { offset, serial, message: "stream ends without EOS page".into() });
+        }
+        if states[&stream.serial].assembler.in_packet {
+            report.issues.push(Issue { offset, serial, message: "last packet is unfinished".into() });
+        }
+        if stream.packets < 3 {
+            report.issues.push(Issue { offset, serial, message: "missing Vorbis header packets".into() });
+        }
+    }
+    Ok(report)
+}
+
+/// Checks one packet of a Vorbis stream; `stream.packets` already counts it.
+fn check_vorbis_packet(stream: &mut StreamReport, data: &[u8]) -> Result<()> {
+    match stream.packets {
+        1 => {
+            stream.identification = Some(parse_identification(data)?);
+        }
+        2 => {
+            stream.comment = Some(parse_comment(data)?);
+        }
+        3 => {
+            let channels = match &stream.identification {
+                Some(ident) => ident.channels,
+                None => return Err(ParseError::Message("setup header without identification".into())),
+            };
+            stream.mode_blockflag = Some(parse_setup_modes(data, channels)?);
+        }
+        _ => {
+            stream.audio_packets += 1;
+            if data.is_empty() {
+                return Ok(());
+            }
+            if let Some(modes) = &stream.mode_blockflag {
+                let mode = audio_packet_mode(data, modes.len())?;
+                if mode as usize >= modes.len() {
+                    return Err(ParseError::Message(format!("audio packet uses undefined mode {}", mode)));
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+//! Settings for opening a wem, shared by every constructor.
+
+use crate::limits::Limits;
+use crate::overrides::Overrides;
+use crate::wwriff::ForcePacketFormat;
+
+/// How to open a wem. `new` fills in the two settings every wem needs; the
+/// rest default to a strict parse of the file as it is, and can be changed
+/// with struct update syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WemOptions {
+    /// Codebook library the setup packet refers to, unless `inline_codebooks`.
+    pub codebooks_name: String,
+    /// The setup packet carries its codebooks instead of library ids.
+    pub inline_codebooks: bool,
+    /// The setup packet is a full Vorbis setup header rather than Wwise's
+    /// stripped one.
+    pub full_setup: bool,
+    /// Whether the audio packets are Wwise's modified ones; applied whatever
+    /// the vorb chunk's mod signal says.
+    pub force_packet_format: ForcePacketFormat,
+    /// Header values taken from here instead of the file wherever they are set.
+    pub overrides: Overrides,
+    /// Caps on what the wem may ask for; checked while parsing and converting.
+    pub limits: Limits,
+    /// Accept a wem cut short: the RIFF and the data chunk are clamped to the
+    /// end of the input, with the missing bytes counted in `truncated_bytes`.
+    /// Meant for `generate_ogg_recovering`; a strict conversion of such a wem
+    /// still stops at the first incomplete packet.
+    pub recovering: bool,
+}
+
+impl WemOptions {
+    pub fn new(codebooks_name: &str, force_packet_format: ForcePacketFormat) -> Self {
+        Self {
+            codebooks_name: codebooks_name.to_string(),
+            inline_codebooks: false,
+            full_setup: false,
+            force_packet_format,
+            overrides: Overrides::default(),
+            limits: Limits::default(),
+            recovering: false,
+        }
+    }
+}
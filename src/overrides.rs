@@ -0,0 +1,26 @@
+use crate::vorb::VorbLayout;
+
+/// Values to use instead of what the wem's header says or implies, for files
+/// whose header is wrong, as written by some game-specific Wwise forks.
+/// `None` keeps the value read from the file. The packet format is forced
+/// separately, with `ForcePacketFormat`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Overrides {
+    /// Where to read the vorb fields, instead of the layout known for the
+    /// chunk size or detected from the data chunk.
+    pub vorb_layout: Option<VorbLayout>,
+    /// 8 byte packet headers, with a 32-bit size and a granule.
+    pub old_packet_headers: Option<bool>,
+    /// 2 byte packet headers, without a granule. Ignored if
+    /// `old_packet_headers` ends up set.
+    pub no_granule: Option<bool>,
+    /// Identification, comment and setup headers stored whole in the data chunk.
+    pub header_triad_present: Option<bool>,
+    /// Offset of the setup packet, or of the header triad, in the data chunk.
+    pub setup_packet_offset: Option<u32>,
+    /// Offset of the first audio packet in the data chunk.
+    pub first_audio_packet_offset: Option<u32>,
+    /// `(blocksize_0_pow, blocksize_1_pow)`; unused with the header triad,
+    /// which carries its own.
+    pub blocksizes: Option<(u8, u8)>,
+}
@@ -0,0 +1,54 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// How far a conversion has got, reported after every audio packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Audio packets converted so far.
+    pub packets: u64,
+    /// Bytes of the data chunk consumed so far, headers and setup included.
+    pub bytes: u64,
+    /// Size of the data chunk.
+    pub data_size: u64,
+}
+
+impl Progress {
+    /// Progress as a fraction between 0 and 1.
+    pub fn fraction(&self) -> f64 {
+        if self.data_size == 0 {
+            1.0
+        } else {
+            self.bytes as f64 / self.data_size as f64
+        }
+    }
+}
+
+/// Receives progress while a conversion runs. Closures taking a `Progress` implement it.
+pub trait ProgressObserver {
+    fn on_progress(&mut self, progress: Progress);
+}
+
+impl<F: FnMut(Progress)> ProgressObserver for F {
+    fn on_progress(&mut self, progress: Progress) {
+        self(progress)
+    }
+}
+
+/// Cancels a running conversion from another thread. Clones share the same flag.
+/// The conversion checks it between packets and stops with `ParseError::Cancelled`.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,123 @@
+//! Conversion of a stretch of samples to a stream of its own.
+//!
+//! Only the audio packets covering the range are converted, starting one
+//! packet early because each packet overlaps the one before it. That first
+//! packet shares a page with the next, so the first granule can tell the
+//! decoder how many leading samples to drop; the last granule likewise cuts
+//! the end. Granules are rebased so the stream starts at sample 0.
+
+use std::io::{Read, Seek, Write};
+use std::ops::Range;
+
+use crate::bit_stream::{BitOggStream, BitOggStreamT};
+use crate::errors::{ParseError, Result};
+use crate::wwriff::{AudioRebuilder, WwiseRiffVorbis};
+
+/// Passes a rebuilt stream through to a `BitOggStream`, keeping the first
+/// audio packet on the page of the second and shifting every granule.
+struct RangeStream<'a, W: Write> {
+    inner: &'a mut BitOggStream<W>,
+    /// Subtracted from every granule.
+    shift: u64,
+    /// Granule of the last page, the length of the range.
+    length: u64,
+    /// The next `flush_page` only ends the packet.
+    join_next: bool,
+}
+
+impl<W: Write> BitOggStreamT for RangeStream<'_, W> {
+    fn write_bits(&mut self, value: u32, bits: u8) -> Result<()> {
+        self.inner.write_bits(value, bits)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        BitOggStreamT::write_all(self.inner, buf)
+    }
+
+    fn set_granule(&mut self, granule: u32) {
+        let granule = (granule as u64).saturating_sub(self.shift);
+        self.inner.set_granule(granule as u32);
+    }
+
+    fn flush_page(&mut self, next_continued: bool, last: bool) -> Result<()> {
+        if std::mem::take(&mut self.join_next) {
+            return self.inner.end_packet();
+        }
+        if last {
+            self.inner.set_granule(self.length as u32);
+        }
+        self.inner.flush_page(next_continued, last)
+    }
+}
+
+impl<R: Read + Seek> WwiseRiffVorbis<R> {
+    /// Converts samples `start_sample` up to, not including, `end_sample`
+    /// into an Ogg Vorbis stream that decodes to exactly those samples, and
+    /// returns the range written. That is the range asked for unless it
+    /// starts and ends inside one packet: decoders can't cut both ends of a
+    /// packet, so the range is widened to the end of the packet, or to its
+    /// start for the last packet. `verify` is not applied, and granules are
+    /// always derived, as if `derive_granules` were set.
+    pub fn generate_ogg_range<W: Write>(
+        &mut self,
+        start_sample: u64,
+        end_sample: u64,
+        writer: W,
+    ) -> Result<Range<u64>> {
+        if start_sample >= end_sample || end_sample > self.sample_count as u64 {
+            return Err(ParseError::Message(format!(
+                "bad sample range {}..{} ({} samples)",
+                start_sample, end_sample, self.sample_count
+            )));
+        }
+        let first = self.seek_to_sample(start_sample)?;
+        let mut last = self.seek_to_sample(end_sample - 1)?.packet;
+        let (mut start_sample, mut end_sample) = (start_sample, end_sample);
+        // Decoders cut the end, not the start, of a stream whose first
+        // granule is also its last, so a range starting inside a packet
+        // needs another page after it; granules can't go back on that page,
+        // so it has to end at the packet's end or later.
+        let data_end = (self.data_offset + self.data_size) as u64;
+        if last.header_offset == first.packet.header_offset && start_sample > first.start {
+            if last.next_offset() < data_end {
+                last = self.packet_at(last.next_offset() as i64, last.index + 1)?;
+                end_sample = end_sample.max(first.end);
+            } else {
+                start_sample = first.start;
+            }
+        }
+
+        let mut ogg_stream = BitOggStream::new(writer);
+        let (mode_blockflag, mode_bits) = self.write_headers(&mut ogg_stream)?;
+        let mut rebuilder = AudioRebuilder::new(self, mode_blockflag, mode_bits);
+        rebuilder.derive_granules = true;
+        // Header granules are absolute; derived ones count from the first
+        // packet written, which ends at `first.start`.
+        let shift = if self.no_granule {
+            start_sample - first.start
+        } else {
+            start_sample
+        };
+        let mut os = RangeStream {
+            inner: &mut ogg_stream,
+            shift,
+            length: end_sample - start_sample,
+            join_next: true,
+        };
+
+        let mut offset = first.previous.header_offset as i64;
+        let mut index = first.previous.index;
+        loop {
+            let packet = self.packet_at(offset, index)?;
+            let payload = self.payload(&packet)?;
+            rebuilder.push(Some((packet, payload)), &mut os)?;
+            if packet.header_offset == last.header_offset {
+                break;
+            }
+            offset = packet.next_offset() as i64;
+            index += 1;
+        }
+        rebuilder.push(None, &mut os)?;
+        Ok(start_sample..end_sample)
+    }
+}
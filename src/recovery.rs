@@ -0,0 +1,227 @@
+//! Best-effort conversion of truncated or corrupted wems.
+//!
+//! The headers must be intact; past them, every complete audio packet is
+//! converted and damaged ones are either skipped or end the stream, which is
+//! always closed with an EOS page. What could not be converted is listed in
+//! the returned `RecoveryReport`.
+
+use std::fmt;
+use std::io::{Read, Seek, Write};
+
+use crate::bit_stream::BitOggStream;
+use crate::errors::{ParseError, Result};
+use crate::wwriff::{AudioRebuilder, Payload, WwisePacket, WwiseRiffVorbis};
+
+/// Packets that must follow a candidate offset for `RecoveryMode::Skip` to
+/// resume there.
+const RESYNC_PACKETS: usize = 4;
+
+/// What to do at the first damaged packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryMode {
+    /// End the stream with the last good packet.
+    Stop,
+    /// Leave the damaged packet out and resume at the next packet that
+    /// looks intact.
+    Skip,
+}
+
+/// Part of the wem that did not make it into the Ogg stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LostRange {
+    /// Offset in the wem.
+    pub offset: u64,
+    pub len: u64,
+    pub reason: String,
+    /// Samples the lost packets held, if their headers chain across the
+    /// range and their modes can be read. Granules derived after the range
+    /// account for them; when this is `None` they fall short by the gap.
+    pub samples: Option<u64>,
+}
+
+impl fmt::Display for LostRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes at offset {}: {}", self.len, self.offset, self.reason)
+    }
+}
+
+/// Outcome of `WwiseRiffVorbis::generate_ogg_recovering`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Audio packets written to the Ogg stream.
+    pub packets: u64,
+    /// Damaged or missing parts of the data chunk, in order.
+    pub lost: Vec<LostRange>,
+    /// Final granule of the Ogg stream, i.e. the samples it holds.
+    pub samples: u64,
+    /// Samples the wem claims to hold.
+    pub expected_samples: u64,
+}
+
+impl RecoveryReport {
+    /// True if nothing was lost.
+    pub fn is_complete(&self) -> bool {
+        self.lost.is_empty()
+    }
+}
+
+impl<R: Read + Seek> WwiseRiffVorbis<R> {
+    /// Same as `generate_ogg_to`, but converts what it can of a damaged wem
+    /// instead of failing at the first bad packet. Fails only if the headers
+    /// can't be rebuilt or no audio packet survives. `verify` is not applied,
+    /// and granules are always derived, as if `derive_granules` were set.
+    pub fn generate_ogg_recovering<W: Write>(&mut self, writer: W, mode: RecoveryMode) -> Result<RecoveryReport> {
+        let mut ogg_stream = BitOggStream::new(writer);
+        let (mode_blockflag, mode_bits) = self.write_headers(&mut ogg_stream)?;
+        let mut rebuilder = AudioRebuilder::new(self, mode_blockflag, mode_bits);
+        rebuilder.derive_granules = true;
+        rebuilder.clamp_final_granule = true;
+
+        let end = self.data_offset + self.data_size;
+        let mut offset = self.data_offset + self.first_audio_packet_offset as i64;
+        let mut index = 0;
+        let mut packets = 0;
+        let mut lost = Vec::new();
+        while offset < end {
+            let damage = match self.intact_packet(&rebuilder, offset, index) {
+                Ok((packet, payload)) => {
+                    rebuilder.push(Some((packet, payload)), &mut ogg_stream)?;
+                    offset = packet.next_offset() as i64;
+                    index += 1;
+                    packets += 1;
+                    continue;
+                }
+                Err(damage) => damage,
+            };
+            let resume = match mode {
+                RecoveryMode::Stop => None,
+                RecoveryMode::Skip => {
+                    // Where the damaged packet's header says the next one starts, if it does.
+                    let after = self.packet_at(offset, index).ok().map(|packet| packet.next_offset() as i64);
+                    match after.filter(|&after| after <= end && self.resyncs_at(&rebuilder, after, end)) {
+                        Some(after) => Some(after),
+                        None => self.resync(&rebuilder, offset + 1, end),
+                    }
+                }
+            };
+            let next = resume.unwrap_or(end);
+            let samples = self.lost_blockflags(&rebuilder, offset, next, index).map(|flags| rebuilder.skip(&flags));
+            lost.push(LostRange {
+                offset: offset as u64,
+                len: (next - offset) as u64,
+                reason: damage.to_string(),
+                samples,
+            });
+            offset = next;
+        }
+        if self.truncated_bytes != 0 {
+            lost.push(LostRange {
+                offset: self.file_size as u64,
+                len: self.truncated_bytes,
+                reason: "file truncated".into(),
+                samples: None,
+            });
+        }
+
+        rebuilder.push(None, &mut ogg_stream)?;
+        if packets == 0 {
+            return Err(ParseError::Message("no audio packet could be recovered".into()));
+        }
+        Ok(RecoveryReport {
+            packets,
+            lost,
+            samples: rebuilder.last_granule() as u64,
+            expected_samples: self.sample_count as u64,
+        })
+    }
+
+    /// Reads the packet at `offset`, failing if it can't be converted.
+    fn intact_packet(
+        &mut self,
+        rebuilder: &AudioRebuilder,
+        offset: i64,
+        index: u64,
+    ) -> Result<(WwisePacket, Payload)> {
+        let packet = self.packet_at(offset, index)?;
+        let payload = self.payload(&packet)?;
+        if !rebuilder.accepts(&payload) {
+            return Err(ParseError::Message(format!(
+                "packet {} uses a mode the setup header doesn't define",
+                index
+            )));
+        }
+        Ok((packet, payload))
+    }
+
+    /// Block flags of the packets from `from` to `to`, if their headers lead
+    /// from one to the next and land on `to`.
+    fn lost_blockflags(&mut self, rebuilder: &AudioRebuilder, from: i64, to: i64, index: u64) -> Option<Vec<bool>> {
+        let mut blockflags = Vec::new();
+        let mut offset = from;
+        while offset < to {
+            let packet = self.packet_at(offset, index).ok()?;
+            if packet.next_offset() as i64 > to {
+                return None;
+            }
+            if packet.size != 0 {
+                let mut first_byte = [0u8];
+                self.read_at(packet.payload_offset, &mut first_byte).ok()?;
+                blockflags.push(rebuilder.blockflag(first_byte[0], index).ok()?);
+            }
+            offset = packet.next_offset() as i64;
+        }
+        (offset == to).then_some(blockflags)
+    }
+
+    /// First offset from `from` at which the stream resyncs, see `resyncs_at`.
+    fn resync(&mut self, rebuilder: &AudioRebuilder, from: i64, end: i64) -> Option<i64> {
+        (from..end).find(|&candidate| self.resyncs_at(rebuilder, candidate, end))
+    }
+
+    /// Whether `RESYNC_PACKETS` intact packets, or intact packets up to the
+    /// end of the data chunk, follow each other from `candidate`.
+    fn resyncs_at(&mut self, rebuilder: &AudioRebuilder, candidate: i64, end: i64) -> bool {
+        let mut offset = candidate;
+        for _ in 0..RESYNC_PACKETS {
+            if offset == end {
+                return true;
+            }
+            match self.plausible_packet(rebuilder, offset, end) {
+                Some(next) => offset = next,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// End of the packet at `offset`, if it looks intact: it ends by `end`,
+    /// isn't empty (two zero bytes anywhere would pass for an empty packet)
+    /// and uses a defined mode. Only the header and the first payload byte
+    /// are read, since most offsets tried while resyncing aren't packets.
+    fn plausible_packet(&mut self, rebuilder: &AudioRebuilder, offset: i64, end: i64) -> Option<i64> {
+        let header_size = self.packet_header_size();
+        if offset + header_size > end {
+            return None;
+        }
+        let mut header = [0u8; 8];
+        let header = &mut header[..header_size as usize];
+        self.read_at(offset as u64, header).ok()?;
+        let packet = self.decode_packet_header(header, offset, 0).ok()?;
+        let next = packet.next_offset() as i64;
+        if packet.size == 0 || next > end {
+            return None;
+        }
+        let mut first_byte = [0u8];
+        self.read_at(packet.payload_offset, &mut first_byte).ok()?;
+        rebuilder.accepts(&first_byte).then_some(next)
+    }
+
+    /// Fills `buf` from `offset`, keeping the read buffer when `offset` falls
+    /// in it, as the nearby offsets tried while resyncing do.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let position = self.infile.stream_position()?;
+        self.infile.seek_relative(offset as i64 - position as i64)?;
+        self.infile.read_exact(buf)?;
+        Ok(())
+    }
+}
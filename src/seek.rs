@@ -0,0 +1,218 @@
+//! Wwise's seek table and sample-accurate packet lookup.
+//!
+//! When the setup packet doesn't start the data chunk, the bytes in front of
+//! it are a seek table: pairs of granule and byte deltas, 16-bit each, or
+//! 32-bit with 8 byte packet headers. Each pair moves from the previous
+//! entry, starting at the first audio packet and granule 0, to an audio
+//! packet and the granule position after it.
+
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::bit_stream::BitPacketStream;
+use crate::errors::{ParseError, Result};
+use crate::wwriff::{AudioRebuilder, WwisePacket, WwiseRiffVorbis};
+
+/// An entry of the seek table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeekPoint {
+    /// Granule position after the packet at `offset`.
+    pub granule: u64,
+    /// Offset of the packet header in the wem.
+    pub offset: u64,
+}
+
+/// Where decoding has to begin to reach a sample, as found by
+/// `WwiseRiffVorbis::seek_to_sample`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeekPosition {
+    /// The audio packet whose decoded output holds the sample. Reached
+    /// through the seek table, its `index` counts from the seek point used,
+    /// since the table doesn't record packet numbers.
+    pub packet: WwisePacket,
+    /// The packet before it, which a decoder needs first: every packet
+    /// overlaps the one before it.
+    pub previous: WwisePacket,
+    /// Granule position after `previous`, the first sample `packet` outputs.
+    pub start: u64,
+    /// Granule position after `packet`.
+    pub end: u64,
+}
+
+/// What `seek_to_sample` needs from the headers, kept between calls.
+pub(crate) struct SeekIndex {
+    /// The seek table, empty if the wem has none.
+    table: Vec<SeekPoint>,
+    /// Block flags of the setup modes, for wems without packet granules.
+    modes: Option<AudioRebuilder>,
+    /// The header fields the index was built from.
+    headers: IndexedHeaders,
+}
+
+/// The header fields behind a `SeekIndex`; the index is rebuilt when any of
+/// them changes, as the public fields of `WwiseRiffVorbis` can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IndexedHeaders {
+    data_offset: i64,
+    data_size: i64,
+    setup_packet_offset: u32,
+    first_audio_packet_offset: u32,
+    blocksizes: (u8, u8),
+    inline_codebooks: bool,
+    full_setup: bool,
+    header_triad_present: bool,
+    old_packet_headers: bool,
+    no_granule: bool,
+}
+
+impl IndexedHeaders {
+    fn of<R: Read + Seek>(wem: &WwiseRiffVorbis<R>) -> Self {
+        Self {
+            data_offset: wem.data_offset,
+            data_size: wem.data_size,
+            setup_packet_offset: wem.setup_packet_offset,
+            first_audio_packet_offset: wem.first_audio_packet_offset,
+            blocksizes: (wem.blocksize_0_pow, wem.blocksize_1_pow),
+            inline_codebooks: wem.inline_codebooks,
+            full_setup: wem.full_setup,
+            header_triad_present: wem.header_triad_present,
+            old_packet_headers: wem.old_packet_headers,
+            no_granule: wem.no_granule,
+        }
+    }
+}
+
+impl fmt::Debug for SeekIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SeekIndex").field("table", &self.table).finish_non_exhaustive()
+    }
+}
+
+impl SeekIndex {
+    fn build<R: Read + Seek>(wem: &mut WwiseRiffVorbis<R>) -> Result<Self> {
+        let modes = if wem.no_granule {
+            let mut headers = BitPacketStream::new();
+            let (mode_blockflag, mode_bits) = wem.write_headers(&mut headers)?;
+            Some(AudioRebuilder::new(wem, mode_blockflag, mode_bits))
+        } else {
+            None
+        };
+        Ok(Self { table: wem.seek_table()?, modes, headers: IndexedHeaders::of(wem) })
+    }
+}
+
+impl<R: Read + Seek> WwiseRiffVorbis<R> {
+    /// Parses the seek table; empty if the wem has none.
+    pub fn seek_table(&mut self) -> Result<Vec<SeekPoint>> {
+        let size = self.setup_packet_offset as i64;
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+        let entry_size = if self.old_packet_headers { 8 } else { 4 };
+        if size % entry_size != 0 || size > self.data_size {
+            return Err(ParseError::Message(format!("seek table of {} bytes, not a whole number of entries", size)));
+        }
+        let read_16 = self.read_16;
+        let read_32 = self.read_32;
+        self.infile.seek(SeekFrom::Start(self.data_offset as u64))?;
+        let mut granule = 0u64;
+        let mut offset = (self.data_offset + self.first_audio_packet_offset as i64) as u64;
+        let mut table = Vec::with_capacity((size / entry_size) as usize);
+        for _ in 0..size / entry_size {
+            let (granule_delta, offset_delta) = if self.old_packet_headers {
+                (read_32(&mut self.infile)? as u64, read_32(&mut self.infile)? as u64)
+            } else {
+                (read_16(&mut self.infile)? as u64, read_16(&mut self.infile)? as u64)
+            };
+            granule += granule_delta;
+            offset += offset_delta;
+            table.push(SeekPoint { granule, offset });
+        }
+        Ok(table)
+    }
+
+    /// Finds the audio packet that outputs sample `sample`, jumping to the
+    /// closest seek table entry before it and scanning packets from there.
+    /// Seek table entries that disagree with their packet headers are passed
+    /// over, down to a scan from the first audio packet; a seek table that
+    /// can't be parsed is an error. The seek table and setup modes are read on
+    /// the first call, and again only after the header fields they come from
+    /// change.
+    pub fn seek_to_sample(&mut self, sample: u64) -> Result<SeekPosition> {
+        if sample >= self.sample_count as u64 {
+            return Err(ParseError::Message(format!("sample {} past the end ({} samples)", sample, self.sample_count)));
+        }
+        let index = match self.seek_index.take() {
+            Some(index) if index.headers == IndexedHeaders::of(self) => index,
+            _ => SeekIndex::build(self)?,
+        };
+        let found = self.find_sample(&index, sample);
+        self.seek_index = Some(index);
+        found
+    }
+
+    fn find_sample(&mut self, index: &SeekIndex, sample: u64) -> Result<SeekPosition> {
+        let modes = &index.modes;
+        let start = self.seek_point_before(&index.table, sample);
+        let mut offset = match start {
+            Some(point) => point.offset as i64,
+            None => self.data_offset + self.first_audio_packet_offset as i64,
+        };
+        let data_end = self.data_offset + self.data_size;
+        // Previous packet, the granule after it and its block flag.
+        let mut previous: Option<(WwisePacket, u64, Option<bool>)> = None;
+        let mut index = 0;
+        while offset < data_end {
+            let packet = self.packet_at(offset, index)?;
+            let blockflag = match modes {
+                Some(modes) if packet.size != 0 => {
+                    self.infile.seek(SeekFrom::Start(packet.payload_offset))?;
+                    let mut first_byte = [0u8];
+                    self.infile.read_exact(&mut first_byte)?;
+                    Some(modes.blockflag(first_byte[0], index)?)
+                }
+                _ => None,
+            };
+            let granule = match (packet.granule, &previous) {
+                (Some(granule), _) => granule as u64,
+                (None, None) => start.map_or(0, |point| point.granule),
+                (None, Some((_, granule, prev_blockflag))) => {
+                    let blocksize = |flag: bool| {
+                        1u64 << if flag { self.blocksize_1_pow } else { self.blocksize_0_pow }
+                    };
+                    match (prev_blockflag, blockflag) {
+                        (Some(prev), Some(cur)) => granule + blocksize(*prev) / 4 + blocksize(cur) / 4,
+                        _ => *granule,
+                    }
+                }
+            };
+            if let Some((previous, start, _)) = previous {
+                if granule > sample {
+                    // Derived granules are cut to the sample count at the end, as in `generate_ogg`.
+                    let end = if packet.granule.is_none() && packet.next_offset() as i64 == data_end {
+                        granule.min(self.sample_count as u64)
+                    } else {
+                        granule
+                    };
+                    return Ok(SeekPosition { packet, previous, start, end });
+                }
+            }
+            let blockflag = blockflag.or(previous.and_then(|(_, _, flag)| flag));
+            previous = Some((packet, granule, blockflag));
+            offset = packet.next_offset() as i64;
+            index += 1;
+        }
+        Err(ParseError::Message(format!("no audio packet holds sample {}", sample)))
+    }
+
+    /// The last seek table entry at or before `sample` whose packet header
+    /// agrees with it; `None` if there is no such entry or no table.
+    fn seek_point_before(&mut self, table: &[SeekPoint], sample: u64) -> Option<SeekPoint> {
+        let point = *table.iter().rev().find(|point| point.granule <= sample)?;
+        let packet = self.packet_at(point.offset as i64, 0).ok()?;
+        match packet.granule {
+            Some(granule) if granule as u64 != point.granule => None,
+            _ => Some(point),
+        }
+    }
+}
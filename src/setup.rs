@@ -0,0 +1,596 @@
+//! The Vorbis setup header as data: codebooks, floors, residues, mappings
+//! and modes, read from a standard setup packet or the stripped form Wwise
+//! stores, and written back as a standard setup packet.
+
+use std::io::{Read, Seek};
+
+use crate::bit_stream::{BitOggStreamT, BitPacketStream, BitStream, BitUint, BitUintV};
+use crate::codebook::{ilog, Codebook};
+use crate::errors::{ParseError, Result};
+use crate::ogg::check_vorbis_header;
+use crate::wwriff::{VorbisPacketHeader, WwiseRiffVorbis};
+
+/// A floor of type 0, which Wwise never writes but standard setups may hold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Floor0 {
+    pub order: u8,
+    pub rate: u16,
+    pub bark_map_size: u16,
+    pub amplitude_bits: u8,
+    pub amplitude_offset: u8,
+    pub books: Vec<u8>,
+}
+
+/// A class of a floor 1 partition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Floor1Class {
+    /// Values per partition of this class, 1 to 8.
+    pub dimensions: u8,
+    /// Book choosing among the subclasses; there is one only with more than one subclass.
+    pub masterbook: Option<u8>,
+    /// Book of every subclass, 1, 2, 4 or 8 of them; `None` for no book.
+    pub subclass_books: Vec<Option<u8>>,
+}
+
+impl Floor1Class {
+    fn subclass_bits(&self) -> u32 {
+        self.subclass_books.len().trailing_zeros()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Floor1 {
+    /// Class of every partition.
+    pub partition_classes: Vec<u8>,
+    pub classes: Vec<Floor1Class>,
+    /// 1 to 4.
+    pub multiplier: u8,
+    pub range_bits: u8,
+    /// X positions past the two implicit ones, `dimensions` per partition.
+    pub x_list: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Floor {
+    Zero(Floor0),
+    One(Floor1),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Residue {
+    /// 0, 1 or 2.
+    pub residue_type: u16,
+    pub begin: u32,
+    pub end: u32,
+    pub partition_size: u32,
+    pub classbook: u8,
+    /// For every classification, the book of each of the 8 passes; `None`
+    /// for a pass it skips.
+    pub books: Vec<[Option<u8>; 8]>,
+}
+
+/// Channels coded as magnitude and angle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CouplingStep {
+    pub magnitude: u8,
+    pub angle: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Submap {
+    /// Unused by Vorbis I.
+    pub time_config: u8,
+    pub floor: u8,
+    pub residue: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    pub coupling: Vec<CouplingStep>,
+    /// Submap of every channel; empty with a single submap, which all use.
+    pub mux: Vec<u8>,
+    pub submaps: Vec<Submap>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mode {
+    /// Long block rather than short.
+    pub blockflag: bool,
+    pub mapping: u8,
+}
+
+/// The contents of a setup header. `VorbisSetup::parse` and the wem
+/// conversion check that every index refers to something that exists, and
+/// `validate` does the same after changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VorbisSetup {
+    /// Channels of the stream, from the identification header; channel
+    /// numbers in the setup take as many bits as they need.
+    pub channels: u8,
+    pub codebooks: Vec<Codebook>,
+    pub floors: Vec<Floor>,
+    pub residues: Vec<Residue>,
+    pub mappings: Vec<Mapping>,
+    pub modes: Vec<Mode>,
+}
+
+impl VorbisSetup {
+    /// Parses a standard setup packet of a stream with `channels` channels.
+    pub fn parse(packet: &[u8], channels: u8) -> Result<Self> {
+        check_vorbis_header(packet, 5)?;
+        let mut ss = BitStream::new(&packet[7..]);
+        let bad = |what: &str| ParseError::Message(format!("invalid setup header: {}", what));
+
+        let codebook_count = ss.read_bits(8)? + 1;
+        let mut codebooks = Vec::with_capacity(codebook_count as usize);
+        for _ in 0..codebook_count {
+            codebooks.push(Codebook::read(&mut ss)?);
+        }
+
+        let time_count = ss.read_bits(6)? + 1;
+        for _ in 0..time_count {
+            if ss.read_bits(16)? != 0 {
+                return Err(bad("time domain transform"));
+            }
+        }
+
+        let floor_count = ss.read_bits(6)? + 1;
+        let mut floors = Vec::with_capacity(floor_count as usize);
+        for _ in 0..floor_count {
+            floors.push(match ss.read_bits(16)? {
+                0 => {
+                    let order = ss.read_bits(8)? as u8;
+                    let rate = ss.read_bits(16)? as u16;
+                    let bark_map_size = ss.read_bits(16)? as u16;
+                    let amplitude_bits = ss.read_bits(6)? as u8;
+                    let amplitude_offset = ss.read_bits(8)? as u8;
+                    let book_count = ss.read_bits(4)? + 1;
+                    let mut books = Vec::with_capacity(book_count as usize);
+                    for _ in 0..book_count {
+                        books.push(ss.read_bits(8)? as u8);
+                    }
+                    Floor::Zero(Floor0 { order, rate, bark_map_size, amplitude_bits, amplitude_offset, books })
+                }
+                1 => Floor::One(read_floor1(&mut ss)?),
+                _ => return Err(bad("floor type")),
+            });
+        }
+
+        let residue_count = ss.read_bits(6)? + 1;
+        let mut residues = Vec::with_capacity(residue_count as usize);
+        for _ in 0..residue_count {
+            let residue_type = ss.read_bits(16)?;
+            if residue_type > 2 {
+                return Err(bad("residue type"));
+            }
+            residues.push(read_residue(&mut ss, residue_type as u16)?);
+        }
+
+        let mapping_count = ss.read_bits(6)? + 1;
+        let mut mappings = Vec::with_capacity(mapping_count as usize);
+        for _ in 0..mapping_count {
+            if ss.read_bits(16)? != 0 {
+                return Err(bad("mapping type"));
+            }
+            mappings.push(read_mapping(&mut ss, channels)?);
+        }
+
+        let mode_count = ss.read_bits(6)? + 1;
+        let mut modes = Vec::with_capacity(mode_count as usize);
+        for _ in 0..mode_count {
+            let blockflag = ss.get_bit()?;
+            if ss.read_bits(16)? != 0 || ss.read_bits(16)? != 0 {
+                return Err(bad("mode window or transform type"));
+            }
+            modes.push(Mode { blockflag, mapping: ss.read_bits(8)? as u8 });
+        }
+        if !ss.get_bit()? {
+            return Err(bad("framing bit"));
+        }
+
+        let setup = Self { channels, codebooks, floors, residues, mappings, modes };
+        setup.validate()?;
+        Ok(setup)
+    }
+
+    /// Reads the setup as Wwise strips it, from the codebook count on: the
+    /// time domain transforms are left out, as are the floor, mapping,
+    /// window and transform types, all of which Vorbis I fixes; residue
+    /// types take 2 bits. Codebooks come from `read_codebook`, since their
+    /// form depends on the wem.
+    pub(crate) fn read_stripped<R: Read>(
+        ss: &mut BitStream<R>,
+        channels: u8,
+        mut read_codebook: impl FnMut(&mut BitStream<R>) -> Result<Codebook>,
+    ) -> Result<Self> {
+        let codebook_count = ss.read_bits(8)? + 1;
+        let mut codebooks = Vec::with_capacity(codebook_count as usize);
+        for _ in 0..codebook_count {
+            codebooks.push(read_codebook(ss)?);
+        }
+
+        let floor_count = ss.read_bits(6)? + 1;
+        let mut floors = Vec::with_capacity(floor_count as usize);
+        for _ in 0..floor_count {
+            floors.push(Floor::One(read_floor1(ss)?));
+        }
+
+        let residue_count = ss.read_bits(6)? + 1;
+        let mut residues = Vec::with_capacity(residue_count as usize);
+        for _ in 0..residue_count {
+            let residue_type = ss.read_bits(2)?;
+            if residue_type > 2 {
+                return Err(ParseError::Message("invalid residue type".into()));
+            }
+            residues.push(read_residue(ss, residue_type as u16)?);
+        }
+
+        let mapping_count = ss.read_bits(6)? + 1;
+        let mut mappings = Vec::with_capacity(mapping_count as usize);
+        for _ in 0..mapping_count {
+            mappings.push(read_mapping(ss, channels)?);
+        }
+
+        let mode_count = ss.read_bits(6)? + 1;
+        let mut modes = Vec::with_capacity(mode_count as usize);
+        for _ in 0..mode_count {
+            let blockflag = ss.get_bit()?;
+            modes.push(Mode { blockflag, mapping: ss.read_bits(8)? as u8 });
+        }
+
+        let setup = Self { channels, codebooks, floors, residues, mappings, modes };
+        setup.validate()?;
+        Ok(setup)
+    }
+
+    /// Checks that every book, floor, residue, mapping and channel number
+    /// refers to one that exists, and that the counts fit their fields.
+    pub fn validate(&self) -> Result<()> {
+        let channels = self.channels;
+        let bad = |what: &str| Err(ParseError::Message(format!("invalid setup header: {}", what)));
+        let book = |book: u8| (book as usize) < self.codebooks.len();
+        if self.codebooks.is_empty() || self.codebooks.len() > 256 {
+            return bad("codebook count");
+        }
+        for count in [self.floors.len(), self.residues.len(), self.mappings.len(), self.modes.len()] {
+            if count == 0 || count > 64 {
+                return bad("floor, residue, mapping or mode count");
+            }
+        }
+        for codebook in &self.codebooks {
+            if codebook.lookup.is_some() && (codebook.dimensions == 0 || codebook.entries == 0) {
+                return bad("empty codebook with lookup table");
+            }
+        }
+
+        for floor in &self.floors {
+            match floor {
+                Floor::Zero(floor) => {
+                    if floor.books.is_empty() || floor.books.len() > 16 || !floor.books.iter().all(|&b| book(b)) {
+                        return bad("floor0 book");
+                    }
+                }
+                Floor::One(floor) => {
+                    if floor.partition_classes.iter().any(|&class| class as usize >= floor.classes.len()) {
+                        return bad("floor1 partition class");
+                    }
+                    for class in &floor.classes {
+                        let subclasses = class.subclass_books.len();
+                        if !matches!(subclasses, 1 | 2 | 4 | 8) || class.masterbook.is_some() != (subclasses > 1) {
+                            return bad("floor1 subclasses");
+                        }
+                        if class.masterbook.is_some_and(|b| !book(b)) {
+                            return bad("floor1 masterbook");
+                        }
+                        if class.subclass_books.iter().flatten().any(|&b| !book(b)) {
+                            return bad("floor1 subclass book");
+                        }
+                    }
+                    let points: usize = floor
+                        .partition_classes
+                        .iter()
+                        .map(|&class| floor.classes[class as usize].dimensions as usize)
+                        .sum();
+                    if points != floor.x_list.len() {
+                        return bad("floor1 x list");
+                    }
+                }
+            }
+        }
+
+        for residue in &self.residues {
+            if residue.residue_type > 2 {
+                return bad("residue type");
+            }
+            if !book(residue.classbook) {
+                return bad("residue classbook");
+            }
+            if residue.books.is_empty() || residue.books.len() > 64 {
+                return bad("residue classifications");
+            }
+            if residue.books.iter().flatten().flatten().any(|&b| !book(b)) {
+                return bad("residue book");
+            }
+        }
+
+        for mapping in &self.mappings {
+            if mapping.submaps.is_empty() || mapping.submaps.len() > 16 || mapping.coupling.len() > 256 {
+                return bad("mapping submaps or coupling steps");
+            }
+            for step in &mapping.coupling {
+                if step.magnitude == step.angle || step.magnitude >= channels || step.angle >= channels {
+                    return bad("coupling");
+                }
+            }
+            let mux_len = if mapping.submaps.len() > 1 { channels as usize } else { 0 };
+            if mapping.mux.len() != mux_len || mapping.mux.iter().any(|&m| m as usize >= mapping.submaps.len()) {
+                return bad("mapping mux");
+            }
+            for submap in &mapping.submaps {
+                if submap.floor as usize >= self.floors.len() {
+                    return bad("mapping floor");
+                }
+                if submap.residue as usize >= self.residues.len() {
+                    return bad("mapping residue");
+                }
+            }
+        }
+
+        if self.modes.iter().any(|mode| mode.mapping as usize >= self.mappings.len()) {
+            return bad("mode mapping");
+        }
+        Ok(())
+    }
+
+    /// Block flag of every mode.
+    pub fn mode_blockflag(&self) -> Vec<bool> {
+        self.modes.iter().map(|mode| mode.blockflag).collect()
+    }
+
+    /// Bits in the mode number of an audio packet.
+    pub fn mode_bits(&self) -> i32 {
+        ilog(self.modes.len().saturating_sub(1) as u32)
+    }
+
+    /// Writes the standard setup packet, without ending it. Fails on counts
+    /// or values that don't fit their fields; it doesn't `validate`.
+    pub fn write_to<O: BitOggStreamT>(&self, os: &mut O) -> Result<()> {
+        let count_less1 = |count: usize| (count as u32).wrapping_sub(1);
+        VorbisPacketHeader::new(5).write_to(os)?;
+
+        BitUint::<8>::new(count_less1(self.codebooks.len()))?.write_to(os)?;
+        for codebook in &self.codebooks {
+            codebook.write_to(os)?;
+        }
+
+        // One time domain transform, of the only type there is.
+        BitUint::<6>::new(0)?.write_to(os)?;
+        BitUint::<16>::new(0)?.write_to(os)?;
+
+        BitUint::<6>::new(count_less1(self.floors.len()))?.write_to(os)?;
+        for floor in &self.floors {
+            match floor {
+                Floor::Zero(floor) => {
+                    BitUint::<16>::new(0)?.write_to(os)?;
+                    BitUint::<8>::new(floor.order as u32)?.write_to(os)?;
+                    BitUint::<16>::new(floor.rate as u32)?.write_to(os)?;
+                    BitUint::<16>::new(floor.bark_map_size as u32)?.write_to(os)?;
+                    BitUint::<6>::new(floor.amplitude_bits as u32)?.write_to(os)?;
+                    BitUint::<8>::new(floor.amplitude_offset as u32)?.write_to(os)?;
+                    BitUint::<4>::new(count_less1(floor.books.len()))?.write_to(os)?;
+                    for &book in &floor.books {
+                        BitUint::<8>::new(book as u32)?.write_to(os)?;
+                    }
+                }
+                Floor::One(floor) => write_floor1(floor, os)?,
+            }
+        }
+
+        BitUint::<6>::new(count_less1(self.residues.len()))?.write_to(os)?;
+        for residue in &self.residues {
+            BitUint::<16>::new(residue.residue_type as u32)?.write_to(os)?;
+            BitUint::<24>::new(residue.begin)?.write_to(os)?;
+            BitUint::<24>::new(residue.end)?.write_to(os)?;
+            BitUint::<24>::new(residue.partition_size.wrapping_sub(1))?.write_to(os)?;
+            BitUint::<6>::new(count_less1(residue.books.len()))?.write_to(os)?;
+            BitUint::<8>::new(residue.classbook as u32)?.write_to(os)?;
+            for books in &residue.books {
+                let cascade = cascade(books);
+                BitUint::<3>::new(cascade & 7)?.write_to(os)?;
+                BitUint::<1>::new((cascade > 7) as u32)?.write_to(os)?;
+                if cascade > 7 {
+                    BitUint::<5>::new(cascade >> 3)?.write_to(os)?;
+                }
+            }
+            for &book in residue.books.iter().flatten().flatten() {
+                BitUint::<8>::new(book as u32)?.write_to(os)?;
+            }
+        }
+
+        BitUint::<6>::new(count_less1(self.mappings.len()))?.write_to(os)?;
+        for mapping in &self.mappings {
+            BitUint::<16>::new(0)?.write_to(os)?;
+            let submaps = mapping.submaps.len();
+            BitUint::<1>::new((submaps > 1) as u32)?.write_to(os)?;
+            if submaps > 1 {
+                BitUint::<4>::new(count_less1(submaps))?.write_to(os)?;
+            }
+            BitUint::<1>::new(!mapping.coupling.is_empty() as u32)?.write_to(os)?;
+            if !mapping.coupling.is_empty() {
+                BitUint::<8>::new(count_less1(mapping.coupling.len()))?.write_to(os)?;
+                let channel_bits = channel_bits(self.channels as usize);
+                for step in &mapping.coupling {
+                    BitUintV::new(channel_bits, step.magnitude as u32)?.write_to(os)?;
+                    BitUintV::new(channel_bits, step.angle as u32)?.write_to(os)?;
+                }
+            }
+            BitUint::<2>::new(0)?.write_to(os)?;
+            if submaps > 1 {
+                for &mux in &mapping.mux {
+                    BitUint::<4>::new(mux as u32)?.write_to(os)?;
+                }
+            }
+            for submap in &mapping.submaps {
+                BitUint::<8>::new(submap.time_config as u32)?.write_to(os)?;
+                BitUint::<8>::new(submap.floor as u32)?.write_to(os)?;
+                BitUint::<8>::new(submap.residue as u32)?.write_to(os)?;
+            }
+        }
+
+        BitUint::<6>::new(count_less1(self.modes.len()))?.write_to(os)?;
+        for mode in &self.modes {
+            BitUint::<1>::new(mode.blockflag as u32)?.write_to(os)?;
+            BitUint::<16>::new(0)?.write_to(os)?;
+            BitUint::<16>::new(0)?.write_to(os)?;
+            BitUint::<8>::new(mode.mapping as u32)?.write_to(os)?;
+        }
+        BitUint::<1>::new(1)?.write_to(os)
+    }
+
+    /// The standard setup packet.
+    pub fn to_packet(&self) -> Result<Vec<u8>> {
+        let mut stream = BitPacketStream::new();
+        self.write_to(&mut stream)?;
+        stream.flush_page(false, false)?;
+        let (packet, _) = stream.pop_packet().ok_or_else(|| ParseError::Message("empty setup packet".into()))?;
+        Ok(packet)
+    }
+}
+
+impl<R: Read + Seek> WwiseRiffVorbis<R> {
+    /// The setup header of the converted stream, whichever form the wem
+    /// stores it in.
+    pub fn vorbis_setup(&mut self) -> Result<VorbisSetup> {
+        let mut headers = BitPacketStream::new();
+        self.write_headers(&mut headers)?;
+        let mut setup = None;
+        while let Some((packet, _)) = headers.pop_packet() {
+            setup = Some(packet);
+        }
+        let setup = setup.ok_or_else(|| ParseError::Message("empty setup packet".into()))?;
+        VorbisSetup::parse(&setup, self.channels as u8)
+    }
+}
+
+/// Bits in a channel number of a stream with `channels` channels.
+fn channel_bits(channels: usize) -> usize {
+    ilog((channels as u32).saturating_sub(1)) as usize
+}
+
+/// Bit k set for every pass k with a book.
+fn cascade(books: &[Option<u8>; 8]) -> u32 {
+    books
+        .iter()
+        .enumerate()
+        .filter(|(_, book)| book.is_some())
+        .fold(0, |cascade, (k, _)| cascade | 1 << k)
+}
+
+fn read_floor1<R: Read>(ss: &mut BitStream<R>) -> Result<Floor1> {
+    let partitions = ss.read_bits(5)?;
+    let mut partition_classes = Vec::with_capacity(partitions as usize);
+    for _ in 0..partitions {
+        partition_classes.push(ss.read_bits(4)? as u8);
+    }
+    let class_count = partition_classes.iter().max().map_or(0, |&class| class as usize + 1);
+    let mut classes = Vec::with_capacity(class_count);
+    for _ in 0..class_count {
+        let dimensions = ss.read_bits(3)? as u8 + 1;
+        let subclass_bits = ss.read_bits(2)?;
+        let masterbook = if subclass_bits != 0 { Some(ss.read_bits(8)? as u8) } else { None };
+        let mut subclass_books = Vec::with_capacity(1 << subclass_bits);
+        for _ in 0..(1 << subclass_bits) {
+            subclass_books.push((ss.read_bits(8)? as u8).checked_sub(1));
+        }
+        classes.push(Floor1Class { dimensions, masterbook, subclass_books });
+    }
+    let multiplier = ss.read_bits(2)? as u8 + 1;
+    let range_bits = ss.read_bits(4)? as u8;
+    let mut x_list = Vec::new();
+    for &class in &partition_classes {
+        for _ in 0..classes[class as usize].dimensions {
+            x_list.push(ss.read_bits(range_bits)?);
+        }
+    }
+    Ok(Floor1 { partition_classes, classes, multiplier, range_bits, x_list })
+}
+
+fn write_floor1<O: BitOggStreamT>(floor: &Floor1, os: &mut O) -> Result<()> {
+    BitUint::<16>::new(1)?.write_to(os)?;
+    BitUint::<5>::new(floor.partition_classes.len() as u32)?.write_to(os)?;
+    for &class in &floor.partition_classes {
+        BitUint::<4>::new(class as u32)?.write_to(os)?;
+    }
+    for class in &floor.classes {
+        BitUint::<3>::new((class.dimensions as u32).wrapping_sub(1))?.write_to(os)?;
+        BitUint::<2>::new(class.subclass_bits())?.write_to(os)?;
+        if let Some(masterbook) = class.masterbook {
+            BitUint::<8>::new(masterbook as u32)?.write_to(os)?;
+        }
+        for book in &class.subclass_books {
+            BitUint::<8>::new(book.map_or(0, |book| book as u32 + 1))?.write_to(os)?;
+        }
+    }
+    BitUint::<2>::new((floor.multiplier as u32).wrapping_sub(1))?.write_to(os)?;
+    BitUint::<4>::new(floor.range_bits as u32)?.write_to(os)?;
+    for &x in &floor.x_list {
+        BitUintV::new(floor.range_bits as usize, x)?.write_to(os)?;
+    }
+    Ok(())
+}
+
+fn read_residue<R: Read>(ss: &mut BitStream<R>, residue_type: u16) -> Result<Residue> {
+    let begin = ss.read_bits(24)?;
+    let end = ss.read_bits(24)?;
+    let partition_size = ss.read_bits(24)? + 1;
+    let classifications = ss.read_bits(6)? + 1;
+    let classbook = ss.read_bits(8)? as u8;
+    let mut cascades = Vec::with_capacity(classifications as usize);
+    for _ in 0..classifications {
+        let low_bits = ss.read_bits(3)?;
+        let high_bits = if ss.get_bit()? { ss.read_bits(5)? } else { 0 };
+        cascades.push(high_bits * 8 + low_bits);
+    }
+    let mut books = Vec::with_capacity(classifications as usize);
+    for cascade in cascades {
+        let mut passes = [None; 8];
+        for (k, pass) in passes.iter_mut().enumerate() {
+            if cascade & (1 << k) != 0 {
+                *pass = Some(ss.read_bits(8)? as u8);
+            }
+        }
+        books.push(passes);
+    }
+    Ok(Residue { residue_type, begin, end, partition_size, classbook, books })
+}
+
+fn read_mapping<R: Read>(ss: &mut BitStream<R>, channels: u8) -> Result<Mapping> {
+    let submap_count = if ss.get_bit()? { ss.read_bits(4)? + 1 } else { 1 };
+    let mut coupling = Vec::new();
+    if ss.get_bit()? {
+        let coupling_steps = ss.read_bits(8)? + 1;
+        let channel_bits = channel_bits(channels as usize) as u8;
+        for _ in 0..coupling_steps {
+            let magnitude = ss.read_bits(channel_bits)? as u8;
+            let angle = ss.read_bits(channel_bits)? as u8;
+            coupling.push(CouplingStep { magnitude, angle });
+        }
+    }
+    if ss.read_bits(2)? != 0 {
+        return Err(ParseError::Message("invalid setup header: mapping reserved field".into()));
+    }
+    let mut mux = Vec::new();
+    if submap_count > 1 {
+        for _ in 0..channels {
+            mux.push(ss.read_bits(4)? as u8);
+        }
+    }
+    let mut submaps = Vec::with_capacity(submap_count as usize);
+    for _ in 0..submap_count {
+        let time_config = ss.read_bits(8)? as u8;
+        let floor = ss.read_bits(8)? as u8;
+        let residue = ss.read_bits(8)? as u8;
+        submaps.push(Submap { time_config, floor, residue });
+    }
+    Ok(Mapping { coupling, mux, submaps })
+}
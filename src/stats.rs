@@ -0,0 +1,102 @@
+//! Packet size, bitrate and block size statistics of a wem, for auditing the
+//! quality settings it was encoded with.
+
+use std::io::{Read, Seek};
+
+use crate::bit_stream::BitPacketStream;
+use crate::errors::{ParseError, Result};
+use crate::granule::GranuleTracker;
+use crate::wwriff::{AudioRebuilder, WwiseRiffVorbis};
+
+/// Outcome of `WwiseRiffVorbis::bitrate_stats`. Sizes and bitrates count
+/// packet payloads, not the Wwise packet headers around them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitrateStats {
+    pub packet_count: u64,
+    pub min_packet_size: u32,
+    pub max_packet_size: u32,
+    pub average_packet_size: f64,
+    /// Bits per second over the whole stream.
+    pub average_bitrate: f64,
+    /// Bits per second claimed by the fmt chunk's `avg_bytes_per_second`.
+    pub header_bitrate: u64,
+    /// Audio packets using a short block, and a long one; empty packets have neither.
+    pub short_blocks: u64,
+    pub long_blocks: u64,
+    /// Bits of the packets starting in each second of audio. The last
+    /// second may be partial.
+    pub bitrate_per_second: Vec<u64>,
+}
+
+impl<R: Read + Seek> WwiseRiffVorbis<R> {
+    /// Walks every audio packet and gathers its size, block size and
+    /// position in time. Block sizes come from the modes in the setup
+    /// header, so the codebooks have to be available.
+    pub fn bitrate_stats(&mut self) -> Result<BitrateStats> {
+        if self.sample_rate == 0 {
+            return Err(ParseError::Message("sample rate of 0".into()));
+        }
+        let mut headers = BitPacketStream::new();
+        let (mode_blockflag, mode_bits) = self.write_headers(&mut headers)?;
+        let modes = AudioRebuilder::new(self, mode_blockflag, mode_bits);
+        let mut granules = GranuleTracker::new(self.blocksize_0_pow, self.blocksize_1_pow);
+
+        let sample_count = self.sample_count as u64;
+        let sample_rate = self.sample_rate as u64;
+        let seconds = sample_count.div_ceil(sample_rate).max(1) as usize;
+        let mut stats = BitrateStats {
+            packet_count: 0,
+            min_packet_size: 0,
+            max_packet_size: 0,
+            average_packet_size: 0.0,
+            average_bitrate: 0.0,
+            header_bitrate: self.avg_bytes_per_second as u64 * 8,
+            short_blocks: 0,
+            long_blocks: 0,
+            bitrate_per_second: vec![0; seconds],
+        };
+        let mut total_bytes = 0u64;
+        // Granule position after the previous packet, where this one's output starts.
+        let mut start = 0u64;
+
+        let mut packets = self.packets();
+        while let Some(packet) = packets.next() {
+            let packet = packet?;
+            let payload = packets.read_payload(&packet)?;
+            let blockflag = match payload.first() {
+                Some(&first_byte) => Some(modes.blockflag(first_byte, packet.index)?),
+                None => None,
+            };
+            match blockflag {
+                Some(true) => stats.long_blocks += 1,
+                Some(false) => stats.short_blocks += 1,
+                None => {}
+            }
+
+            if stats.packet_count == 0 {
+                stats.min_packet_size = packet.size;
+            }
+            stats.packet_count += 1;
+            stats.min_packet_size = stats.min_packet_size.min(packet.size);
+            stats.max_packet_size = stats.max_packet_size.max(packet.size);
+            total_bytes += packet.size as u64;
+            let second = ((start / sample_rate) as usize).min(seconds - 1);
+            stats.bitrate_per_second[second] += packet.size as u64 * 8;
+
+            let granule = match (packet.granule, blockflag) {
+                (Some(granule), _) => granule as u64,
+                (None, Some(blockflag)) => granules.push(blockflag),
+                (None, None) => start,
+            };
+            start = granule.clamp(start, sample_count);
+        }
+
+        if stats.packet_count != 0 {
+            stats.average_packet_size = total_bytes as f64 / stats.packet_count as f64;
+        }
+        if sample_count != 0 {
+            stats.average_bitrate = total_bytes as f64 * 8.0 * sample_rate as f64 / sample_count as f64;
+        }
+        Ok(stats)
+    }
+}
@@ -0,0 +1,156 @@
+//! Conversion from a forward-only reader such as a pipe, socket or
+//! decompressor.
+//!
+//! Everything in front of the first audio packet (the chunks before `data`
+//! and the setup packet) is kept in memory and parsed as usual; the audio
+//! packets are then read in order with one packet of lookahead. This only
+//! works when the fmt and vorb chunks come before the data chunk and the vorb
+//! layout is a known one. Chunks after the data chunk are never read.
+
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+
+use crate::bit_stream::BitOggStream;
+use crate::errors::{ParseError, Result};
+use crate::options::WemOptions;
+use crate::vorb::VorbLayout;
+use crate::wwriff::{AudioRebuilder, Payload, WwisePacket, WwiseRiffVorbis};
+
+/// A wem being read from a plain `Read`. Returned by `WemStream::new`, which
+/// parses the header; `generate_ogg_to` then converts the audio packets as
+/// they arrive.
+pub struct WemStream<R: Read> {
+    wem: WwiseRiffVorbis<Cursor<Vec<u8>>>,
+    input: R,
+    /// Offset in the wem of the next byte of `input`.
+    position: i64,
+    index: u64,
+}
+
+impl<R: Read> WemStream<R> {
+    /// Parses the header. The chunks in front of the first audio packet must
+    /// fit in `options.limits.max_allocation`; `options.recovering` isn't
+    /// supported, since the input can't be measured up front.
+    pub fn new(input: R, options: &WemOptions) -> Result<Self> {
+        if options.recovering {
+            return Err(ParseError::Message("can't recover a wem from a forward-only reader".to_string()));
+        }
+        let image = BufReader::new(Cursor::new(Vec::new()));
+        let wem = WwiseRiffVorbis::unparsed(image, "", options);
+        let mut stream = WemStream { wem, input, position: 0, index: 0 };
+
+        stream.buffer(12)?;
+        stream.wem.infile.seek(SeekFrom::Start(0))?;
+        stream.wem.read_riff_header()?;
+
+        let mut chunk_offset = 12;
+        while stream.wem.data_offset == -1 {
+            if chunk_offset + 8 > stream.wem.riff_size {
+                return Err(ParseError::Message("expected fmt, data chunks".to_string()));
+            }
+            stream.buffer(8)?;
+            stream.wem.infile.seek(SeekFrom::Start(chunk_offset as u64))?;
+            let mut chunk_type = [0u8; 4];
+            stream.wem.infile.read_exact(&mut chunk_type)?;
+            let read_32_fn = stream.wem.read_32;
+            let chunk_size = read_32_fn(&mut stream.wem.infile)? as i64;
+            stream.wem.record_chunk(&chunk_type, chunk_offset, chunk_size);
+            if &chunk_type != b"data" {
+                stream.buffer(chunk_size)?;
+            }
+            chunk_offset += 8 + chunk_size;
+        }
+        if chunk_offset > stream.wem.riff_size {
+            return Err(ParseError::Message("chunk truncated".to_string()));
+        }
+        if stream.wem.fmt_offset == -1 {
+            return Err(ParseError::Message("fmt chunk after data chunk, can't convert without seeking".to_string()));
+        }
+        if stream.wem.vorb_offset == -1 && stream.wem.fmt_size != 0x42 {
+            return Err(ParseError::Message("vorb chunk after data chunk, can't convert without seeking".to_string()));
+        }
+        if stream.wem.vorb_offset != -1 && VorbLayout::known(stream.wem.vorb_size).is_none() {
+            return Err(ParseError::Message("unknown vorb layout, can't detect it without seeking".to_string()));
+        }
+        stream.wem.file_size = stream.wem.riff_size;
+        stream.wem.parse_chunks(options.force_packet_format)?;
+
+        let first_audio_packet_offset = stream.wem.first_audio_packet_offset as i64;
+        if first_audio_packet_offset > stream.wem.data_size {
+            return Err(ParseError::Message("first audio packet past the data chunk".to_string()));
+        }
+        stream.buffer(first_audio_packet_offset)?;
+        Ok(stream)
+    }
+
+    /// The parsed header. Only the part in front of the first audio packet is
+    /// available through its reader.
+    pub fn wem(&self) -> &WwiseRiffVorbis<Cursor<Vec<u8>>> {
+        &self.wem
+    }
+
+    /// Sets `derive_granules` on the wem, see `WwiseRiffVorbis::derive_granules`.
+    pub fn set_derive_granules(&mut self, derive_granules: bool) {
+        self.wem.derive_granules = derive_granules;
+    }
+
+    /// Converts the rest of the input to Ogg, writing pages as they are built.
+    /// `verify` is not applied, since it needs to re-read the data chunk.
+    pub fn generate_ogg_to<W: Write>(mut self, writer: W) -> Result<()> {
+        let mut ogg_stream = BitOggStream::new(writer);
+        let (mode_blockflag, mode_bits) = self.wem.write_headers(&mut ogg_stream)?;
+        let mut rebuilder = AudioRebuilder::new(&self.wem, mode_blockflag, mode_bits);
+        loop {
+            let next = self.next_packet()?;
+            let end = next.is_none();
+            rebuilder.push(next, &mut ogg_stream)?;
+            if end {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Reads the next audio packet and its payload straight from the input.
+    fn next_packet(&mut self) -> Result<Option<(WwisePacket, Payload)>> {
+        let end = self.wem.data_offset + self.wem.data_size;
+        if self.position >= end {
+            return Ok(None);
+        }
+        let header_size = self.wem.packet_header_size();
+        if self.position + header_size > end {
+            return Err(ParseError::Message("page header truncated".into()));
+        }
+        let mut header = [0u8; 8];
+        let header = &mut header[..header_size as usize];
+        self.input.read_exact(header)?;
+        let packet = self.wem.decode_packet_header(header, self.position, self.index)?;
+        let mut payload = Vec::new();
+        self.read_into(&mut payload, packet.size as i64)?;
+        self.position = packet.next_offset() as i64;
+        self.index += 1;
+        Ok(Some((packet, payload.into())))
+    }
+
+    /// Appends the next `len` bytes of the input to the in-memory header.
+    fn buffer(&mut self, len: i64) -> Result<()> {
+        let room = self.wem.limits.max_allocation.saturating_sub(self.wem.infile.get_ref().get_ref().len());
+        if len > i64::try_from(room).unwrap_or(i64::MAX) {
+            return Err(ParseError::Message("wem header larger than max_allocation".into()));
+        }
+        let mut image = std::mem::take(self.wem.infile.get_mut().get_mut());
+        let result = self.read_into(&mut image, len);
+        *self.wem.infile.get_mut().get_mut() = image;
+        result?;
+        self.position += len;
+        Ok(())
+    }
+
+    /// Appends exactly `len` bytes of the input to `buf`, growing it only as data arrives.
+    fn read_into(&mut self, buf: &mut Vec<u8>, len: i64) -> Result<()> {
+        let len = u64::try_from(len).map_err(|_| ParseError::Message("negative length".into()))?;
+        let read = (&mut self.input).take(len).read_to_end(buf)?;
+        if read as u64 != len {
+            return Err(ParseError::Message("unexpected end of stream".into()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// A window of `len` bytes starting at `start` in another reader, seen as a
+/// stream of its own. Lets a wem embedded in an archive or sound bank be
+/// converted in place:
+/// `WwiseRiffVorbis::from_reader(SubReader::new(bank, start, len)?, ...)`.
+#[derive(Debug)]
+pub struct SubReader<R: Read + Seek> {
+    inner: R,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl<R: Read + Seek> SubReader<R> {
+    /// Fails if the window reaches past the end of `inner`.
+    pub fn new(mut inner: R, start: u64, len: u64) -> io::Result<Self> {
+        let inner_len = inner.seek(SeekFrom::End(0))?;
+        if start.checked_add(len).is_none_or(|end| end > inner_len) {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "window reaches past the end of the reader",
+            ));
+        }
+        inner.seek(SeekFrom::Start(start))?;
+        Ok(Self { inner, start, len, pos: 0 })
+    }
+
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read + Seek> Read for SubReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.len.saturating_sub(self.pos);
+        let max = (buf.len() as u64).min(left) as usize;
+        if max == 0 {
+            return Ok(0);
+        }
+        let n = self.inner.read(&mut buf[..max])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for SubReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => (0, offset as i128),
+            SeekFrom::End(offset) => (self.len, offset as i128),
+            SeekFrom::Current(offset) => (self.pos, offset as i128),
+        };
+        let target = base as i128 + offset;
+        if target < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek before the start of the window"));
+        }
+        let target = u64::try_from(target)
+            .ok()
+            .and_then(|target| self.start.checked_add(target).map(|_| target))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek position overflows"))?;
+        self.inner.seek(SeekFrom::Start(self.start + target))?;
+        self.pos = target;
+        Ok(target)
+    }
+}
@@ -0,0 +1,179 @@
+//! Inference of the Wwise SDK release that wrote a wem from the header
+//! details that changed between releases.
+//!
+//! Wwise publishes no history of its wem layout, so the ranges rest on two
+//! reverse-engineered converters:
+//!
+//! - vgmstream (`src/meta/wwise.c`) dates each layout by the games it was
+//!   found in: vorb chunks of 0x2C and 0x28 bytes are "earliest (~2009)" and
+//!   "early (~2009)", 0x34 "common (2010~2011)", 0x32 "rare (mid 2011)" and
+//!   0x2A "uncommon (mid 2011)"; all of them are "older Wwise (~<2012)",
+//!   while "newer Wwise (>2012)" moves the vorb fields into a 0x42 byte fmt
+//!   chunk. It also notes that the latest games store a channel config
+//!   (channel count, config type, speaker mask) where older ones have a WAVE
+//!   channel mask.
+//! - ww2ogg (`wwriff.cpp`) ties the packet headers to those layouts: 8 bytes
+//!   with a 0x28 or 0x2C vorb chunk, 2 bytes with a 0x2A one or the 0x42 fmt
+//!   chunk, 6 bytes otherwise. It reads a mod signal from the 0x2A layout
+//!   and lists the values seen with standard and with modified packets.
+//!
+//! Game release dates only bound the SDK from above, so the ranges are
+//! approximate; each clue is reported along with the range it allows, so a
+//! surprising estimate can be traced back to the field behind it.
+
+use std::fmt;
+use std::io::{Read, Seek};
+
+use crate::wwriff::{is_standard_mod_signal, WwiseRiffVorbis};
+
+/// A Wwise SDK release, e.g. 2011.2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WwiseVersion {
+    pub year: u16,
+    pub release: u8,
+}
+
+impl WwiseVersion {
+    pub const fn new(year: u16, release: u8) -> Self {
+        Self { year, release }
+    }
+}
+
+impl fmt::Display for WwiseVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.year, self.release)
+    }
+}
+
+/// Releases from `earliest` to `latest`, both included; `None` leaves that
+/// end open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionRange {
+    pub earliest: Option<WwiseVersion>,
+    pub latest: Option<WwiseVersion>,
+}
+
+impl VersionRange {
+    /// Every release.
+    pub const ANY: VersionRange = VersionRange { earliest: None, latest: None };
+
+    const fn from(year: u16, release: u8) -> Self {
+        Self { earliest: Some(WwiseVersion::new(year, release)), latest: None }
+    }
+
+    const fn up_to(year: u16, release: u8) -> Self {
+        Self { earliest: None, latest: Some(WwiseVersion::new(year, release)) }
+    }
+
+    const fn between(earliest: (u16, u8), latest: (u16, u8)) -> Self {
+        Self {
+            earliest: Some(WwiseVersion::new(earliest.0, earliest.1)),
+            latest: Some(WwiseVersion::new(latest.0, latest.1)),
+        }
+    }
+
+    pub fn contains(&self, version: WwiseVersion) -> bool {
+        self.earliest.is_none_or(|earliest| earliest <= version)
+            && self.latest.is_none_or(|latest| version <= latest)
+    }
+
+    /// Releases in both ranges, or `None` if they don't overlap.
+    pub fn intersect(&self, other: &VersionRange) -> Option<VersionRange> {
+        let earliest = self.earliest.max(other.earliest);
+        let latest = match (self.latest, other.latest) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        match (earliest, latest) {
+            (Some(earliest), Some(latest)) if earliest > latest => None,
+            _ => Some(VersionRange { earliest, latest }),
+        }
+    }
+}
+
+impl fmt::Display for VersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.earliest, self.latest) {
+            (Some(earliest), Some(latest)) if earliest == latest => write!(f, "{}", earliest),
+            (Some(earliest), Some(latest)) => write!(f, "{} to {}", earliest, latest),
+            (Some(earliest), None) => write!(f, "{} or later", earliest),
+            (None, Some(latest)) => write!(f, "{} or earlier", latest),
+            (None, None) => write!(f, "any version"),
+        }
+    }
+}
+
+/// A header detail and the releases that write it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evidence {
+    pub clue: String,
+    pub range: VersionRange,
+}
+
+/// Outcome of `WwiseRiffVorbis::detect_wwise_version`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionEstimate {
+    /// Releases allowed by every clue; `None` if the clues contradict each
+    /// other, as they may for a hand-edited header or forced overrides.
+    pub range: Option<VersionRange>,
+    pub evidence: Vec<Evidence>,
+}
+
+impl<R: Read + Seek> WwiseRiffVorbis<R> {
+    /// Estimates which Wwise SDK releases could have written this wem from
+    /// its fmt chunk, vorb layout, packet headers and mod signal.
+    pub fn detect_wwise_version(&self) -> VersionEstimate {
+        let mut evidence = Vec::new();
+        let mut note = |clue: String, range: VersionRange| evidence.push(Evidence { clue, range });
+
+        if self.fmt_size == 0x42 {
+            note("vorb fields in a 0x42 byte fmt chunk".into(), VersionRange::from(2012, 1));
+        } else {
+            note(format!("vorb chunk after a 0x{:X} byte fmt chunk", self.fmt_size), VersionRange::up_to(2012, 1));
+        }
+
+        // Only read as a channel config from a 0x42 byte fmt chunk (see
+        // `ChannelLayout`), so this agrees with the clue above.
+        if self.fmt_size >= 0x18 {
+            if self.channel_layout.config_type.is_some() {
+                note(format!("channel config 0x{:X} in the fmt chunk", self.subtype), VersionRange::from(2012, 1));
+            } else {
+                note(format!("channel mask 0x{:X} in the fmt chunk", self.subtype), VersionRange::ANY);
+            }
+        }
+
+        let vorb = match self.vorb_size {
+            0x28 | 0x2C => Some(VersionRange::up_to(2009, 3)),
+            0x34 => Some(VersionRange::between((2010, 1), (2011, 3))),
+            0x32 | 0x2A => Some(VersionRange::between((2011, 1), (2012, 1))),
+            _ => None,
+        };
+        if let Some(range) = vorb {
+            note(format!("vorb chunk of 0x{:X} bytes", self.vorb_size), range);
+        }
+
+        // The layouts each packet header comes with, from ww2ogg.
+        if self.old_packet_headers {
+            note("8 byte packet headers".into(), VersionRange::up_to(2009, 3));
+        } else if self.no_granule {
+            note("2 byte packet headers".into(), VersionRange::from(2011, 1));
+        } else {
+            note("6 byte packet headers".into(), VersionRange::between((2010, 1), (2012, 1)));
+        }
+
+        // Modified packets first appear with the 0x2A layout; standard ones
+        // are written by every release.
+        if self.vorb_layout.is_some_and(|layout| layout.mod_signal.is_some()) {
+            if is_standard_mod_signal(self.mod_signal) {
+                note(format!("standard packets (mod signal 0x{:X})", self.mod_signal), VersionRange::ANY);
+            } else {
+                note(format!("modified packets (mod signal 0x{:X})", self.mod_signal), VersionRange::from(2011, 1));
+            }
+        }
+
+        let range = evidence
+            .iter()
+            .try_fold(VersionRange::ANY, |range, evidence| range.intersect(&evidence.range));
+        VersionEstimate { range, evidence }
+    }
+}
@@ -0,0 +1,222 @@
+//! Layouts of the vorb chunk, which holds the Vorbis parameters Wwise keeps
+//! outside the stream. Known layouts are looked up by chunk size; new ones are
+//! found by `WwiseRiffVorbis::detect_vorb_layout`.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::errors::{ParseError, Result};
+use crate::wwriff::WwiseRiffVorbis;
+
+/// Where a vorb chunk layout keeps its fields, as offsets from the start of
+/// the chunk, and which packet format goes with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VorbLayout {
+    /// Chunk size the layout goes with; -1 for the fields stored at the end
+    /// of a 0x42 byte fmt chunk instead of a vorb chunk.
+    pub size: i64,
+    pub sample_count: i64,
+    /// Encoder flags telling modified packets apart; only layouts with 2
+    /// byte packet headers have them.
+    pub mod_signal: Option<i64>,
+    pub setup_packet_offset: i64,
+    pub first_audio_packet_offset: i64,
+    /// Absent when the data chunk holds the full header triad.
+    pub uid: Option<i64>,
+    /// `blocksize_0_pow` followed by `blocksize_1_pow`; absent with the triad.
+    pub blocksizes: Option<i64>,
+    pub header_triad: bool,
+    pub old_packet_headers: bool,
+    pub no_granule: bool,
+}
+
+const NO_GRANULE: VorbLayout = VorbLayout {
+    size: 0x2A,
+    sample_count: 0,
+    mod_signal: Some(0x4),
+    setup_packet_offset: 0x10,
+    first_audio_packet_offset: 0x14,
+    uid: Some(0x24),
+    blocksizes: Some(0x28),
+    header_triad: false,
+    old_packet_headers: false,
+    no_granule: true,
+};
+
+const TRIAD: VorbLayout = VorbLayout {
+    size: 0x28,
+    sample_count: 0,
+    mod_signal: None,
+    setup_packet_offset: 0x18,
+    first_audio_packet_offset: 0x1C,
+    uid: None,
+    blocksizes: None,
+    header_triad: true,
+    old_packet_headers: true,
+    no_granule: false,
+};
+
+const GRANULE: VorbLayout = VorbLayout {
+    size: 0x32,
+    sample_count: 0,
+    mod_signal: None,
+    setup_packet_offset: 0x18,
+    first_audio_packet_offset: 0x1C,
+    uid: Some(0x2C),
+    blocksizes: Some(0x30),
+    header_triad: false,
+    old_packet_headers: false,
+    no_granule: false,
+};
+
+/// The layouts written by the Wwise versions we have seen.
+pub const KNOWN_VORB_LAYOUTS: [VorbLayout; 6] = [
+    VorbLayout { size: -1, ..NO_GRANULE },
+    TRIAD,
+    NO_GRANULE,
+    VorbLayout { size: 0x2C, ..TRIAD },
+    GRANULE,
+    VorbLayout { size: 0x34, ..GRANULE },
+];
+
+impl VorbLayout {
+    /// The known layout for a vorb chunk of `size` bytes.
+    pub fn known(size: i64) -> Option<VorbLayout> {
+        KNOWN_VORB_LAYOUTS.iter().copied().find(|layout| layout.size == size)
+    }
+
+    /// True if this is one of `KNOWN_VORB_LAYOUTS` rather than a detected one.
+    pub fn is_known(&self) -> bool {
+        KNOWN_VORB_LAYOUTS.contains(self)
+    }
+}
+
+/// Packets that must chain from the first audio packet for a detected layout
+/// to be accepted, unless the data chunk ends first.
+const CHAINED_PACKETS: usize = 4;
+
+/// Candidate packet header formats, as (old_packet_headers, no_granule).
+const PACKET_FORMATS: [(bool, bool); 3] = [(true, false), (false, false), (false, true)];
+
+impl<R: Read + Seek> WwiseRiffVorbis<R> {
+    /// Finds the fields of a vorb chunk of unknown size by trying every
+    /// plausible setup/first audio packet offset pair and packet header
+    /// format until the data chunk agrees with them. The sample count is
+    /// taken from the start of the chunk, and the mod signal, for 2 byte
+    /// packet headers, from offset 4, as in every known layout.
+    pub fn detect_vorb_layout(&mut self) -> Result<VorbLayout> {
+        let size = self.vorb_size;
+        if size < 0x10 || size as u64 > self.limits.max_allocation as u64 {
+            return Err(ParseError::Message("bad vorb size".to_string()));
+        }
+        let mut vorb = vec![0u8; size as usize];
+        self.infile.seek(SeekFrom::Start(self.vorb_offset as u64))?;
+        self.infile.read_exact(&mut vorb)?;
+        let read_32 = self.read_32;
+        let field = |at: i64| read_32(&mut &vorb[at as usize..]).unwrap_or(0) as i64;
+
+        let (old_packet_headers, no_granule) = (self.old_packet_headers, self.no_granule);
+        let mut found = None;
+        'search: for at in (4..=size - 8).step_by(4) {
+            let (setup, audio) = (field(at), field(at + 4));
+            if setup >= audio || audio > self.data_size {
+                continue;
+            }
+            for (old, no_gran) in PACKET_FORMATS {
+                self.old_packet_headers = old;
+                self.no_granule = no_gran;
+                let Some(triad) = self.setup_reaches(setup, audio) else { continue };
+                let Some(granules) = self.chained_granules(audio) else { continue };
+                found = Some((at, old, no_gran, triad, granules));
+                break 'search;
+            }
+        }
+        self.old_packet_headers = old_packet_headers;
+        self.no_granule = no_granule;
+        let (at, old_packet_headers, no_granule, header_triad, granules) =
+            found.ok_or_else(|| ParseError::Message("unrecognized vorb layout".to_string()))?;
+
+        let (uid, blocksizes) = if header_triad {
+            (None, None)
+        } else {
+            let blocksizes = find_blocksizes(&vorb, at + 8, &granules)
+                .ok_or_else(|| ParseError::Message("no blocksizes in vorb chunk".to_string()))?;
+            (Some(blocksizes - 4), Some(blocksizes))
+        };
+        Ok(VorbLayout {
+            size,
+            sample_count: 0,
+            mod_signal: if no_granule { Some(0x4) } else { None },
+            setup_packet_offset: at,
+            first_audio_packet_offset: at + 4,
+            uid,
+            blocksizes,
+            header_triad,
+            old_packet_headers,
+            no_granule,
+        })
+    }
+
+    /// Whether the setup packet, or the header triad for 8 byte headers,
+    /// ends exactly at `audio`; `Some(true)` if it was the triad.
+    fn setup_reaches(&mut self, setup: i64, audio: i64) -> Option<bool> {
+        let mut offset = self.data_offset + setup;
+        let packet = self.packet_at(offset, 0).ok()?;
+        let mut ident = [0u8; 7];
+        let triad = self.old_packet_headers
+            && packet.size >= 7
+            && self.infile.seek(SeekFrom::Start(packet.payload_offset)).is_ok()
+            && self.infile.read_exact(&mut ident).is_ok()
+            && &ident == b"\x01vorbis";
+        let packets = if triad { 3 } else { 1 };
+        for _ in 0..packets {
+            offset = self.packet_at(offset, 0).ok()?.next_offset() as i64;
+        }
+        (offset == self.data_offset + audio).then_some(triad)
+    }
+
+    /// Granules of the first `CHAINED_PACKETS` audio packets, or `None` if
+    /// they don't follow each other within the data chunk.
+    fn chained_granules(&mut self, audio: i64) -> Option<Vec<Option<u32>>> {
+        let end = self.data_offset + self.data_size;
+        let mut offset = self.data_offset + audio;
+        let mut granules = Vec::new();
+        while granules.len() < CHAINED_PACKETS && offset != end {
+            let packet = self.packet_at(offset, granules.len() as u64).ok()?;
+            if packet.size == 0 {
+                return None;
+            }
+            granules.push(packet.granule);
+            offset = packet.next_offset() as i64;
+        }
+        (!granules.is_empty()).then_some(granules)
+    }
+}
+
+/// Offset of the first plausible blocksize pair from `from`, preferring the
+/// one 0x10 bytes further, where every known layout has it, and then pairs
+/// that explain the granule steps between the first packets.
+fn find_blocksizes(vorb: &[u8], from: i64, granules: &[Option<u32>]) -> Option<i64> {
+    let valid = |at: usize| {
+        let (b0, b1) = (vorb[at], vorb[at + 1]);
+        (6..=13).contains(&b0) && (b0..=13).contains(&b1)
+    };
+    let mut candidates: Vec<usize> = (from as usize + 4..vorb.len() - 1).filter(|&at| valid(at)).collect();
+    if let Some(i) = candidates.iter().position(|&at| at as i64 == from + 0x10) {
+        let preferred = candidates.remove(i);
+        candidates.insert(0, preferred);
+    }
+    let steps: Vec<u32> = granules
+        .windows(2)
+        .filter_map(|w| w[1]?.checked_sub(w[0]?))
+        .collect();
+    let explains = |at: usize| {
+        let (short, long) = (1u32 << vorb[at], 1u32 << vorb[at + 1]);
+        steps.iter().all(|&step| step == short / 2 || step == (short + long) / 4 || step == long / 2)
+    };
+    candidates
+        .iter()
+        .copied()
+        .find(|&at| explains(at))
+        .or(candidates.first().copied())
+        .map(|at| at as i64)
+}
@@ -1,12 +1,25 @@
 use std::fs::File;
 use std::path::Path;
-use std::io::{self, Read, Seek, BufReader, BufWriter, SeekFrom, Cursor};
+use std::io::{Read, Write, Seek, BufReader, BufWriter, SeekFrom, Cursor};
+use std::ops::Deref;
 use byteorder::{LittleEndian, BigEndian, ReadBytesExt};
 use tracing;
 
-use crate::bit_stream::{BitOggStream, BitOggStreamT, BitUint, BitUintV, BitStream};
-use crate::codebook::{ilog};
-use crate::errors::{ParseError, Result};
+use crate::bit_stream::{BitOggStream, BitOggStreamT, BitPacketStream, BitUint, BitUintV, BitStream};
+use crate::channels::ChannelLayout;
+use crate::codebook::{ilog, Codebook, CodebookLibrary};
+use crate::errors::{ParseError, Result, VerifyError};
+use crate::granule::GranuleTracker;
+use crate::limits::Limits;
+#[cfg(feature = "mmap")]
+use crate::mmap::MappedFile;
+use crate::options::WemOptions;
+use crate::overrides::Overrides;
+use crate::seek::SeekIndex;
+use crate::vorb::VorbLayout;
+use crate::setup::VorbisSetup;
+use crate::progress::{CancellationToken, Progress, ProgressObserver};
+use crate::ogg::{audio_packet_mode, parse_comment, parse_identification, parse_setup_modes, validate_ogg, OggPacketReader};
 
 
 
@@ -37,7 +50,13 @@ pub fn read_32_be_dyn(reader: &mut dyn Read) -> Result<u32> {
     read_32_be(reader)
 }
 
-#[derive(Debug)]
+/// Whether a vorb chunk's mod signal stands for standard Vorbis packets;
+/// every other value means modified ones.
+pub fn is_standard_mod_signal(mod_signal: u32) -> bool {
+    matches!(mod_signal, 0x4A | 0x4B | 0x69 | 0x70)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ForcePacketFormat {
     NoModPackets,
     ModPackets,
@@ -125,6 +144,457 @@ impl Packet8 {
     pub fn header_size(&self) -> i64 {8}
 }
 
+// -------------------- WwisePacket ----------------------------------------------
+/// An audio packet in the data chunk, as located by `WwiseRiffVorbis::packets`.
+/// Offsets are absolute positions in the wem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WwisePacket {
+    /// Position among the audio packets, starting at 0.
+    pub index: u64,
+    pub header_offset: u64,
+    pub payload_offset: u64,
+    /// Payload size in bytes, without the header.
+    pub size: u32,
+    /// Absolute granule from the header; `None` for 2 byte headers, which carry none.
+    pub granule: Option<u32>,
+}
+
+impl WwisePacket {
+    pub fn header_size(&self) -> u64 {
+        self.payload_offset - self.header_offset
+    }
+    pub fn next_offset(&self) -> u64 {
+        self.payload_offset + self.size as u64
+    }
+}
+
+/// Position of the next audio packet in the data chunk. Kept apart from the
+/// wem so owners of a `WwiseRiffVorbis` can walk its packets too.
+struct PacketCursor {
+    offset: i64,
+    index: u64,
+    failed: bool,
+}
+
+impl PacketCursor {
+    fn new<R: Read + Seek>(wem: &WwiseRiffVorbis<R>) -> Self {
+        Self {
+            offset: wem.data_offset + wem.first_audio_packet_offset as i64,
+            index: 0,
+            failed: false,
+        }
+    }
+
+    fn next<R: Read + Seek>(&mut self, wem: &mut WwiseRiffVorbis<R>) -> Option<Result<WwisePacket>> {
+        if self.failed || self.offset >= wem.data_offset + wem.data_size {
+            return None;
+        }
+        let packet = wem.packet_at(self.offset, self.index);
+        match &packet {
+            Ok(packet) => {
+                self.offset = packet.next_offset() as i64;
+                self.index += 1;
+            }
+            Err(_) => self.failed = true,
+        }
+        Some(packet)
+    }
+
+    fn next_with_payload<R: Read + Seek>(
+        &mut self,
+        wem: &mut WwiseRiffVorbis<R>,
+    ) -> Result<Option<(WwisePacket, Payload)>> {
+        match self.next(wem) {
+            Some(packet) => {
+                let packet = packet?;
+                let payload = wem.payload(&packet)?;
+                Ok(Some((packet, payload)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Iterator over the audio packets of a wem, returned by `WwiseRiffVorbis::packets`.
+/// Stops after the first error.
+pub struct WwisePackets<'a, R: Read + Seek> {
+    wem: &'a mut WwiseRiffVorbis<R>,
+    cursor: PacketCursor,
+}
+
+impl<R: Read + Seek> WwisePackets<'_, R> {
+    /// Reads the payload of a packet yielded by this iterator.
+    pub fn read_payload(&mut self, packet: &WwisePacket) -> Result<Vec<u8>> {
+        self.wem.read_payload(packet)
+    }
+}
+
+impl<R: Read + Seek> Iterator for WwisePackets<'_, R> {
+    type Item = Result<WwisePacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.cursor.next(self.wem)
+    }
+}
+
+// -------------------- AudioPackets ---------------------------------------------
+/// Payload of an audio packet, read into memory or borrowed from a mapped wem.
+pub(crate) enum Payload {
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(MappedFile, std::ops::Range<usize>),
+}
+
+impl Deref for Payload {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Payload::Owned(payload) => payload,
+            #[cfg(feature = "mmap")]
+            Payload::Mapped(map, range) => &map.as_ref()[range.clone()],
+        }
+    }
+}
+
+impl From<Vec<u8>> for Payload {
+    fn from(payload: Vec<u8>) -> Self {
+        Payload::Owned(payload)
+    }
+}
+
+/// Rebuilds Wwise audio packets as standard Vorbis packets. It does no I/O:
+/// packets are pushed in data chunk order, and each is written once the one
+/// after it is known, since mod packets need the next packet's window size.
+pub(crate) struct AudioRebuilder {
+    pending: Option<(WwisePacket, Payload)>,
+    last_written: Option<WwisePacket>,
+    mode_blockflag: Vec<bool>,
+    mode_bits: i32,
+    prev_blockflag: bool,
+    granules: GranuleTracker,
+    mod_packets: bool,
+    no_granule: bool,
+    sample_count: u32,
+    /// Write granules derived from the block sizes when the packet headers
+    /// carry none, instead of 0.
+    pub(crate) derive_granules: bool,
+    /// Keep the last granule within `sample_count`, whatever the packet header says.
+    pub(crate) clamp_final_granule: bool,
+    last_granule: u32,
+    /// Block flags of packets left out after the queued one, see `skip`.
+    skipped: Vec<bool>,
+}
+
+impl AudioRebuilder {
+    pub(crate) fn new<R: Read + Seek>(wem: &WwiseRiffVorbis<R>, mode_blockflag: Vec<bool>, mode_bits: i32) -> Self {
+        Self {
+            pending: None,
+            last_written: None,
+            mode_blockflag,
+            mode_bits,
+            prev_blockflag: false,
+            granules: GranuleTracker::new(wem.blocksize_0_pow, wem.blocksize_1_pow),
+            mod_packets: wem.mod_packets,
+            no_granule: wem.no_granule,
+            sample_count: wem.sample_count,
+            derive_granules: wem.derive_granules,
+            clamp_final_granule: false,
+            last_granule: 0,
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Whether `payload` uses a mode the setup header defines; `push` can
+    /// fail halfway through a packet that doesn't.
+    pub(crate) fn accepts(&self, payload: &[u8]) -> bool {
+        let Some(&first_byte) = payload.first() else {
+            return true;
+        };
+        let mode_mask = (1u32 << self.mode_bits) - 1;
+        if self.mod_packets {
+            ((first_byte as u32 & mode_mask) as usize) < self.mode_blockflag.len()
+        } else {
+            first_byte & 1 == 0
+                && (self.mode_blockflag.is_empty()
+                    || ((((first_byte as u32) >> 1) & mode_mask) as usize) < self.mode_blockflag.len())
+        }
+    }
+
+    /// Block flag of the audio packet whose payload starts with `first_byte`.
+    pub(crate) fn blockflag(&self, first_byte: u8, packet: u64) -> Result<bool> {
+        let mode_mask = (1u32 << self.mode_bits) - 1;
+        let mode = if self.mod_packets {
+            first_byte as u32 & mode_mask
+        } else {
+            (first_byte as u32 >> 1) & mode_mask
+        };
+        mode_flag(&self.mode_blockflag, mode, packet)
+    }
+
+    /// Accounts for packets with block flags `blockflags`, left out of the
+    /// stream after the queued packet, so derived granules don't fall short
+    /// of the source from there on. Returns the samples they held.
+    pub(crate) fn skip(&mut self, blockflags: &[bool]) -> u64 {
+        let mut granules = self.granules.clone();
+        let mut start = granules.granule();
+        if let Some((packet, payload)) = &self.pending {
+            if let Some(Ok(blockflag)) = payload.first().map(|&first_byte| self.blockflag(first_byte, packet.index)) {
+                start = granules.push(blockflag);
+            }
+        }
+        let end = blockflags.iter().fold(start, |_, &blockflag| granules.push(blockflag));
+        self.skipped.extend_from_slice(blockflags);
+        end - start
+    }
+
+    /// Feeds the packets passed to `skip` to the granule tracker.
+    fn push_skipped(&mut self) {
+        for blockflag in self.skipped.drain(..) {
+            self.granules.push(blockflag);
+            self.prev_blockflag = blockflag;
+        }
+    }
+
+    /// Granule position of the packet last written by `push`.
+    pub(crate) fn last_granule(&self) -> u32 {
+        self.last_granule
+    }
+
+    /// Queues the next packet of the data chunk (`None` at its end) and writes
+    /// the previously queued packet to `os`, ending it with `flush_page`.
+    /// Returns false if nothing was queued before.
+    pub(crate) fn push<O: BitOggStreamT>(
+        &mut self,
+        next: Option<(WwisePacket, Payload)>,
+        os: &mut O,
+    ) -> Result<bool> {
+        let Some((packet, payload)) = std::mem::replace(&mut self.pending, next) else {
+            self.push_skipped();
+            return Ok(false);
+        };
+        let last = self.pending.is_none();
+        self.last_written = Some(packet);
+
+        let mode_blockflag = &self.mode_blockflag;
+        let mode_bits = self.mode_bits;
+        let mode_mask = (1u32 << mode_bits) - 1;
+        let mut granule = match packet.granule {
+            Some(0xFFFFFFFF) => 1,
+            Some(granule) => granule,
+            None => 0,
+        };
+        if let Some(&first_byte) = payload.first() {
+            let first_byte = first_byte as u32;
+            if self.mod_packets {
+                if mode_blockflag.is_empty() {
+                    return Err(ParseError::Message("didn't load mode_blockflag".into()));
+                }
+                // Output one bit for packet type (0 == audio)
+                BitUint::<1>::new(0)?.write_to(os)?;
+
+                // The mode number and the rest of the first byte are split by hand.
+                let mode_number = BitUintV::new(mode_bits as usize, first_byte & mode_mask)?;
+                mode_number.write_to(os)?;
+                let remainder = BitUintV::new(8 - mode_bits as usize, first_byte >> mode_bits)?;
+
+                let blockflag = mode_flag(mode_blockflag, mode_number.total, packet.index)?;
+                if blockflag {
+                    // Long windows need the previous and next window sizes.
+                    let next_blockflag = match &self.pending {
+                        Some((next, next_payload)) if !next_payload.is_empty() => {
+                            mode_flag(mode_blockflag, next_payload[0] as u32 & mode_mask, next.index)?
+                        }
+                        _ => false,
+                    };
+                    BitUint::<1>::new(self.prev_blockflag as u32)?.write_to(os)?;
+                    BitUint::<1>::new(next_blockflag as u32)?.write_to(os)?;
+                }
+
+                self.prev_blockflag = blockflag;
+                remainder.write_to(os)?;
+            } else {
+                BitUint::<8>::new(first_byte)?.write_to(os)?;
+                let mode_number = (first_byte >> 1) & mode_mask;
+                self.prev_blockflag = mode_blockflag.get(mode_number as usize).copied().unwrap_or(false);
+            }
+
+            // Without granules in the packet headers they can be derived from
+            // the block sizes; the tracker runs either way for `skip`.
+            if self.no_granule && !mode_blockflag.is_empty() {
+                let mut derived = self.granules.push(self.prev_blockflag);
+                if last {
+                    derived = derived.min(self.sample_count as u64);
+                }
+                if self.derive_granules {
+                    granule = derived as u32;
+                }
+            }
+
+            // Write remaining bytes of the packet.
+            os.write_all(&payload[1..])?;
+        }
+        if last && self.clamp_final_granule {
+            granule = granule.min(self.sample_count);
+        }
+        os.set_granule(granule);
+        self.last_granule = granule;
+        os.flush_page(false, last)?;
+        self.push_skipped();
+        Ok(true)
+    }
+
+    /// Progress after the packet last written by `push`.
+    pub(crate) fn progress<R: Read + Seek>(&self, wem: &WwiseRiffVorbis<R>) -> Progress {
+        let (packets, bytes) = match &self.last_written {
+            Some(packet) => (packet.index + 1, packet.next_offset() - wem.data_offset as u64),
+            None => (0, wem.first_audio_packet_offset as u64),
+        };
+        Progress { packets, bytes, data_size: wem.data_size as u64 }
+    }
+}
+
+/// Block flag of `mode` in a mod packet, which the setup header must define.
+fn mode_flag(mode_blockflag: &[bool], mode: u32, packet: u64) -> Result<bool> {
+    mode_blockflag.get(mode as usize).copied().ok_or_else(|| {
+        ParseError::Message(format!("packet {}: mode {} not defined by the setup header", packet, mode))
+    })
+}
+
+/// Feeds an `AudioRebuilder` from the data chunk of a seekable wem.
+pub(crate) struct AudioPackets {
+    cursor: PacketCursor,
+    rebuilder: AudioRebuilder,
+    done: bool,
+}
+
+impl AudioPackets {
+    pub(crate) fn new<R: Read + Seek>(wem: &WwiseRiffVorbis<R>, mode_blockflag: Vec<bool>, mode_bits: i32) -> Self {
+        Self {
+            cursor: PacketCursor::new(wem),
+            rebuilder: AudioRebuilder::new(wem, mode_blockflag, mode_bits),
+            done: false,
+        }
+    }
+
+    /// Writes the next audio packet to `os` and ends it with `flush_page`.
+    /// Returns false once every packet has been written.
+    pub(crate) fn write_next<R: Read + Seek, O: BitOggStreamT>(
+        &mut self,
+        wem: &mut WwiseRiffVorbis<R>,
+        os: &mut O,
+    ) -> Result<bool> {
+        while !self.done {
+            let next = self.cursor.next_with_payload(wem)?;
+            self.done = next.is_none();
+            if self.rebuilder.push(next, os)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub(crate) fn progress<R: Read + Seek>(&self, wem: &WwiseRiffVorbis<R>) -> Progress {
+        self.rebuilder.progress(wem)
+    }
+}
+
+// -------------------- VorbisPackets ---------------------------------------------
+/// A rebuilt Vorbis packet, ready for a decoder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VorbisPacket {
+    pub data: Vec<u8>,
+    /// Granule position after this packet; 0 for the three headers.
+    pub granule: u64,
+}
+
+/// Iterator over the identification, comment and setup headers followed by
+/// the audio packets, returned by `WwiseRiffVorbis::vorbis_packets`.
+/// Stops after the first error.
+pub struct VorbisPackets<'a, R: Read + Seek> {
+    wem: &'a mut WwiseRiffVorbis<R>,
+    stream: BitPacketStream,
+    audio: AudioPackets,
+    done: bool,
+}
+
+impl<R: Read + Seek> Iterator for VorbisPackets<'_, R> {
+    type Item = Result<VorbisPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((data, granule)) = self.stream.pop_packet() {
+                return Some(Ok(VorbisPacket { data, granule: granule as u64 }));
+            }
+            if self.done {
+                return None;
+            }
+            match self.audio.write_next(self.wem, &mut self.stream) {
+                Ok(true) => {}
+                Ok(false) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+// -------------------- OggReader ------------------------------------------------
+/// Converts a wem lazily: Ogg pages are generated as the reader is drained,
+/// so only about one page is held in memory. Returned by
+/// `WwiseRiffVorbis::into_ogg_reader`.
+pub struct OggReader<R: Read + Seek> {
+    wem: WwiseRiffVorbis<R>,
+    ogg_stream: BitOggStream<Vec<u8>>,
+    audio: Option<AudioPackets>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: Read + Seek> OggReader<R> {
+    /// Hands back the wem, e.g. to convert it again.
+    pub fn into_inner(self) -> WwiseRiffVorbis<R> {
+        self.wem
+    }
+
+    /// Writes the headers on the first call and one audio packet on every call.
+    fn generate_page(&mut self) -> Result<()> {
+        if self.audio.is_none() {
+            let (mode_blockflag, mode_bits) = self.wem.write_headers(&mut self.ogg_stream)?;
+            self.audio = Some(AudioPackets::new(&self.wem, mode_blockflag, mode_bits));
+        }
+        if let Some(audio) = &mut self.audio {
+            if !audio.write_next(&mut self.wem, &mut self.ogg_stream)? {
+                self.done = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for OggReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.ogg_stream.get_ref().len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.ogg_stream.get_mut().clear();
+            self.pos = 0;
+            if let Err(e) = self.generate_page() {
+                self.done = true;
+                return Err(e.into());
+            }
+        }
+        let pending = &self.ogg_stream.get_ref()[self.pos..];
+        let n = pending.len().min(buf.len());
+        buf[..n].copy_from_slice(&pending[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
 // -------------------- VorbisPacketHeader -------------------------------------
 pub struct VorbisPacketHeader {
     type_: u8,
@@ -164,6 +634,8 @@ pub struct WwiseRiffVorbis<R: Read + Seek> {
     pub smpl_size: i64,
     pub vorb_size: i64,
     pub data_size: i64,
+    /// Where the vorb fields were read from; `None` until the chunks are parsed.
+    pub vorb_layout: Option<VorbLayout>,
 
     // RIFF fmt
     pub channels: u16,
@@ -173,6 +645,8 @@ pub struct WwiseRiffVorbis<R: Read + Seek> {
     // RIFF extended fmt
     pub ext_unk: u16,
     pub subtype: u32,
+    /// Speakers and channel order, decoded from `subtype`.
+    pub channel_layout: ChannelLayout,
 
     // cue info
     pub cue_count: u32,
@@ -189,6 +663,8 @@ pub struct WwiseRiffVorbis<R: Read + Seek> {
     pub uid: u32,
     pub blocksize_0_pow: u8,
     pub blocksize_1_pow: u8,
+    /// Encoder flags from the vorb chunk; 0 for layouts without them.
+    pub mod_signal: u32,
 
     pub inline_codebooks: bool,
     pub full_setup: bool,
@@ -197,24 +673,107 @@ pub struct WwiseRiffVorbis<R: Read + Seek> {
     pub no_granule: bool,
     pub mod_packets: bool,
 
+    /// Check the generated Ogg stream before handing it out (see `verify_ogg`).
+    pub verify: bool,
+    /// For wems without packet granules (`no_granule`), give each page the
+    /// granule its block sizes add up to rather than 0. Off by default, which
+    /// keeps the output of earlier versions.
+    pub derive_granules: bool,
+    /// Caps on what the wem may ask for; checked while parsing and converting.
+    pub limits: Limits,
+    /// Header values forced by the caller; applied while parsing.
+    pub overrides: Overrides,
+    /// Bytes of the RIFF missing from the input; only wems opened with
+    /// `WemOptions::recovering` can be missing any.
+    pub truncated_bytes: u64,
+    /// The mapping behind a wem opened with `new_mmap`, which packets are read from directly.
+    #[cfg(feature = "mmap")]
+    pub(crate) mapped: Option<MappedFile>,
+    /// Built by `seek_to_sample`, and rebuilt once the header fields behind it change.
+    pub(crate) seek_index: Option<SeekIndex>,
+
     pub read_16: fn(&mut dyn Read) -> Result<u16>,
     pub read_32: fn(&mut dyn Read) -> Result<u32>,
 }
 
 impl WwiseRiffVorbis<File>{
-    pub fn new(
-        name: &str,
-        codebooks_name: &str,
-        inline_codebooks: bool,
-        full_setup: bool,
-        force_packet_format: ForcePacketFormat,
-    ) -> Result<Self> {
+    pub fn new(name: &str, options: &WemOptions) -> Result<Self> {
         let file = File::open(name).map_err(|_| ParseError::File(name.to_string()))?;
-        let infile = BufReader::new(file);
+        Self::from_reader(file, name, options)
+    }
+}
+
+impl WwiseRiffVorbis<Cursor<Vec<u8>>>{
+    pub fn new(buf: Cursor<Vec<u8>>, file_name: &str, options: &WemOptions) -> Result<Self> {
+        Self::from_reader(buf, file_name, options)
+    }
+}
+
+impl<R: Read + Seek> WwiseRiffVorbis<R> {
+    /// Parses a wem from any seekable reader. `file_name` is only used by
+    /// `generate_ogg` to name the output.
+    pub fn from_reader(reader: R, file_name: &str, options: &WemOptions) -> Result<Self> {
+        Self::from_buf_reader(BufReader::new(reader), file_name, options)
+    }
+
+    /// `from_reader` with the caller's choice of buffering; in-memory input
+    /// can skip the copy through the buffer by using a capacity of 0.
+    pub(crate) fn from_buf_reader(infile: BufReader<R>, file_name: &str, options: &WemOptions) -> Result<Self> {
+        let mut instance = Self::unparsed(infile, file_name, options);
+        instance.read_chunks(options.recovering)?;
+        instance.parse_chunks(options.force_packet_format)?;
+        Ok(instance)
+    }
+
+    /// Reads the RIFF header and locates the chunks. With `clamp`, a RIFF
+    /// that reaches past the end of the input is cut back instead of rejected.
+    fn read_chunks(&mut self, clamp: bool) -> Result<()> {
+        self.file_size = self.infile.seek(SeekFrom::End(0))? as i64;
+        if self.file_size < 12 {
+            return Err(ParseError::Message("File too small".to_string()));
+        }
+        self.infile.seek(SeekFrom::Start(0))?;
+        self.read_riff_header()?;
+        if self.riff_size > self.file_size {
+            if !clamp {
+                return Err(ParseError::Message("RIFF truncated".to_string()));
+            }
+            self.truncated_bytes = (self.riff_size - self.file_size) as u64;
+            self.riff_size = self.file_size;
+        }
+
+        let read_32_fn = self.read_32;
+        let mut chunk_offset = 12;
+        while chunk_offset < self.riff_size {
+            self.infile.seek(SeekFrom::Start(chunk_offset as u64))?;
+            if chunk_offset + 8 > self.riff_size {
+                if clamp {
+                    break;
+                }
+                return Err(ParseError::Message("chunk header truncated".to_string()));
+            }
+            let mut chunk_type = [0u8; 4];
+            self.infile.read_exact(&mut chunk_type)?;
+            let chunk_size = read_32_fn(&mut self.infile)? as i64;
+            self.record_chunk(&chunk_type, chunk_offset, chunk_size);
+            chunk_offset += 8 + chunk_size;
+        }
+        if chunk_offset > self.riff_size {
+            if !clamp {
+                return Err(ParseError::Message("chunk truncated".to_string()));
+            }
+            if self.data_offset != -1 && self.data_offset + self.data_size > self.riff_size {
+                self.data_size = (self.riff_size - self.data_offset).max(0);
+            }
+        }
+        Ok(())
+    }
 
-        let mut instance = WwiseRiffVorbis {
-            file_name: name.to_string(),
-            codebooks_name: codebooks_name.to_string(),
+    /// A wem over `infile` with no chunks located yet.
+    pub(crate) fn unparsed(infile: BufReader<R>, file_name: &str, options: &WemOptions) -> Self {
+        WwiseRiffVorbis {
+            file_name: file_name.to_string(),
+            codebooks_name: options.codebooks_name.clone(),
             infile,
             file_size: -1,
             little_endian: true,
@@ -231,11 +790,13 @@ impl WwiseRiffVorbis<File>{
             smpl_size: -1,
             vorb_size: -1,
             data_size: -1,
+            vorb_layout: None,
             channels: 0,
             sample_rate: 0,
             avg_bytes_per_second: 0,
             ext_unk: 0,
             subtype: 0,
+            channel_layout: ChannelLayout::from_fmt(0, 0, false),
             cue_count: 0,
             loop_count: 0,
             loop_start: 0,
@@ -246,503 +807,265 @@ impl WwiseRiffVorbis<File>{
             uid: 0,
             blocksize_0_pow: 0,
             blocksize_1_pow: 0,
-            inline_codebooks,
-            full_setup,
+            mod_signal: 0,
+            inline_codebooks: options.inline_codebooks,
+            full_setup: options.full_setup,
             header_triad_present: false,
             old_packet_headers: false,
             no_granule: false,
             mod_packets: false,
+            verify: false,
+            derive_granules: false,
+            limits: options.limits,
+            overrides: options.overrides,
+            truncated_bytes: 0,
+            #[cfg(feature = "mmap")]
+            mapped: None,
+            seek_index: None,
             read_16: read_16_le_dyn,
             read_32: read_32_le_dyn,
-        };
-
-        instance.file_size = instance.infile.get_ref().metadata()?.len() as i64;
-        if instance.file_size < 12 {
-            return Err(ParseError::Message("File too small".to_string()));
         }
+    }
 
+    /// Reads the RIFF/RIFX and WAVE ids and the RIFF size, picking the byte order.
+    pub(crate) fn read_riff_header(&mut self) -> Result<()> {
         let mut riff_head = [0u8; 4];
-        instance.infile.seek(SeekFrom::Start(0))?;
-        instance.infile.read_exact(&mut riff_head)?;
+        self.infile.read_exact(&mut riff_head)?;
 
         if &riff_head == b"RIFX" {
-            instance.little_endian = false;
+            self.little_endian = false;
         } else if &riff_head == b"RIFF" {
-            instance.little_endian = true;
+            self.little_endian = true;
         } else {
             return Err(ParseError::Message("missing RIFF".to_string()));
         }
-        instance.read_16 = if instance.little_endian { read_16_le_dyn } else { read_16_be_dyn };
-        instance.read_32 = if instance.little_endian { read_32_le_dyn } else { read_32_be_dyn };
+        self.read_16 = if self.little_endian { read_16_le_dyn } else { read_16_be_dyn };
+        self.read_32 = if self.little_endian { read_32_le_dyn } else { read_32_be_dyn };
 
-        let read_32_fn = instance.read_32;
-        instance.riff_size = read_32_fn(&mut instance.infile)? as i64 + 8;
-        if instance.riff_size > instance.file_size {
-            return Err(ParseError::Message("RIFF truncated".to_string()));
-        }
+        let read_32_fn = self.read_32;
+        self.riff_size = read_32_fn(&mut self.infile)? as i64 + 8;
 
         let mut wave_head = [0u8; 4];
-        instance.infile.read_exact(&mut wave_head)?;
+        self.infile.read_exact(&mut wave_head)?;
         if &wave_head != b"WAVE" {
             return Err(ParseError::Message("missing WAVE".to_string()));
         }
+        Ok(())
+    }
 
-        let mut chunk_offset = 12;
-        while chunk_offset < instance.riff_size {
-            instance.infile.seek(SeekFrom::Start(chunk_offset as u64))?;
-            if chunk_offset + 8 > instance.riff_size {
-                return Err(ParseError::Message("chunk header truncated".to_string()));
-            }
-            let mut chunk_type = [0u8; 4];
-            instance.infile.read_exact(&mut chunk_type)?;
-            let chunk_size = read_32_fn(&mut instance.infile)? as i64;
-
-            match &chunk_type {
-                b"fmt " => {
-                    instance.fmt_offset = chunk_offset + 8;
-                    instance.fmt_size = chunk_size;
-                },
-                b"cue " => {
-                    instance.cue_offset = chunk_offset + 8;
-                    instance.cue_size = chunk_size;
-                },
-                b"LIST" => {
-                    instance.list_offset = chunk_offset + 8;
-                    instance.list_size = chunk_size;
-                },
-                b"smpl" => {
-                    instance.smpl_offset = chunk_offset + 8;
-                    instance.smpl_size = chunk_size;
-                },
-                b"vorb" => {
-                    instance.vorb_offset = chunk_offset + 8;
-                    instance.vorb_size = chunk_size;
-                },
-                b"data" => {
-                    instance.data_offset = chunk_offset + 8;
-                    instance.data_size = chunk_size;
-                },
-                _ => { }
-            }
-            chunk_offset += 8 + chunk_size;
-        }
-        if chunk_offset > instance.riff_size {
-            return Err(ParseError::Message("chunk truncated".to_string()));
+    /// Notes where a chunk we care about lives; others are ignored.
+    pub(crate) fn record_chunk(&mut self, chunk_type: &[u8; 4], chunk_offset: i64, chunk_size: i64) {
+        match chunk_type {
+            b"fmt " => {
+                self.fmt_offset = chunk_offset + 8;
+                self.fmt_size = chunk_size;
+            },
+            b"cue " => {
+                self.cue_offset = chunk_offset + 8;
+                self.cue_size = chunk_size;
+            },
+            b"LIST" => {
+                self.list_offset = chunk_offset + 8;
+                self.list_size = chunk_size;
+            },
+            b"smpl" => {
+                self.smpl_offset = chunk_offset + 8;
+                self.smpl_size = chunk_size;
+            },
+            b"vorb" => {
+                self.vorb_offset = chunk_offset + 8;
+                self.vorb_size = chunk_size;
+            },
+            b"data" => {
+                self.data_offset = chunk_offset + 8;
+                self.data_size = chunk_size;
+            },
+            _ => { }
         }
-        if instance.fmt_offset == -1 || instance.data_offset == -1 {
+    }
+
+    /// Parses the fmt, cue, smpl and vorb chunks once they have been located.
+    pub(crate) fn parse_chunks(&mut self, force_packet_format: ForcePacketFormat) -> Result<()> {
+        if self.fmt_offset == -1 || self.data_offset == -1 {
             return Err(ParseError::Message("expected fmt, data chunks".to_string()));
         }
+        if self.fmt_size < 0x12 {
+            return Err(ParseError::Message("fmt chunk too small".to_string()));
+        }
+        let read_16 = self.read_16;
+        let read_32 = self.read_32;
 
-        instance.infile.seek(SeekFrom::Start(instance.fmt_offset as u64))?;
-        if read_16_le(&mut instance.infile)? != 0xFFFF {
+        self.infile.seek(SeekFrom::Start(self.fmt_offset as u64))?;
+        if read_16(&mut self.infile)? != 0xFFFF {
             return Err(ParseError::Message("bad codec id".to_string()));
         }
-        instance.channels = read_16_le(&mut instance.infile)?;
-        instance.sample_rate = read_32_le(&mut instance.infile)?;
-        instance.avg_bytes_per_second = read_32_le(&mut instance.infile)?;
-        if read_16_le(&mut instance.infile)? != 0 {
+        self.channels = read_16(&mut self.infile)?;
+        if self.channels == 0 {
+            return Err(ParseError::Message("no channels".to_string()));
+        }
+        if self.channels > self.limits.max_channels as u16 {
+            return Err(ParseError::Message(format!("{} channels, more than max_channels", self.channels)));
+        }
+        self.sample_rate = read_32(&mut self.infile)?;
+        self.avg_bytes_per_second = read_32(&mut self.infile)?;
+        if read_16(&mut self.infile)? != 0 {
             return Err(ParseError::Message("bad block align".to_string()));
         }
-        if read_16_le(&mut instance.infile)? != 0 {
+        if read_16(&mut self.infile)? != 0 {
             return Err(ParseError::Message("expected 0 bps".to_string()));
         }
-        let extra_len = read_16_le(&mut instance.infile)?;
-        if (instance.fmt_size - 0x12) as u16 != extra_len {
+        let extra_len = read_16(&mut self.infile)?;
+        if (self.fmt_size - 0x12) as u16 != extra_len {
             return Err(ParseError::Message("bad extra fmt length".to_string()));
         }
-        if instance.fmt_size - 0x12 >= 2 {
-            instance.ext_unk = read_16_le(&mut instance.infile)?;
-            if instance.fmt_size - 0x12 >= 6 {
-                instance.subtype = read_32_le(&mut instance.infile)?;
+        if self.fmt_size - 0x12 >= 2 {
+            self.ext_unk = read_16(&mut self.infile)?;
+            if self.fmt_size - 0x12 >= 6 {
+                self.subtype = read_32(&mut self.infile)?;
             }
         }
-        if instance.fmt_size == 0x28 {
+        if self.fmt_size == 0x28 {
             let mut whoknowsbuf = [0u8; 16];
             let whoknowsbuf_check: [u8; 16] =
                 [1,0,0,0,0,0,0x10,0,0x80,0,0,0xAA,0,0x38,0x9b,0x71];
-            instance.infile.read_exact(&mut whoknowsbuf)?;
+            self.infile.read_exact(&mut whoknowsbuf)?;
             if whoknowsbuf != whoknowsbuf_check {
                 return Err(ParseError::Message("expected signature in extra fmt?".to_string()));
             }
         }
 
-        if instance.cue_offset != -1 {
-            instance.infile.seek(SeekFrom::Start(instance.cue_offset as u64))?;
-            instance.cue_count = read_32_le(&mut instance.infile)?;
+        if self.cue_offset != -1 && self.cue_size >= 4 {
+            self.infile.seek(SeekFrom::Start(self.cue_offset as u64))?;
+            self.cue_count = read_32(&mut self.infile)?;
         }
 
-        if instance.smpl_offset != -1 {
-            instance.infile.seek(SeekFrom::Start((instance.smpl_offset + 0x1C) as u64))?;
-            instance.loop_count = read_32_le(&mut instance.infile)?;
-            if instance.loop_count != 1 {
+        if self.smpl_offset != -1 {
+            if self.smpl_size < 0x34 {
+                return Err(ParseError::Message("smpl chunk too small".to_string()));
+            }
+            self.infile.seek(SeekFrom::Start((self.smpl_offset + 0x1C) as u64))?;
+            self.loop_count = read_32(&mut self.infile)?;
+            if self.loop_count != 1 {
                 return Err(ParseError::Message("expected one loop".to_string()));
             }
-            instance.infile.seek(SeekFrom::Start((instance.smpl_offset + 0x2C) as u64))?;
-            instance.loop_start = read_32_le(&mut instance.infile)?;
-            instance.loop_end = read_32_le(&mut instance.infile)?;
+            self.infile.seek(SeekFrom::Start((self.smpl_offset + 0x2C) as u64))?;
+            self.loop_start = read_32(&mut self.infile)?;
+            self.loop_end = read_32(&mut self.infile)?;
         }
 
-        if instance.vorb_offset == -1 {
-            if instance.fmt_size == 0x42 {
-                instance.vorb_offset = instance.fmt_offset + 0x18;
+        if self.vorb_offset == -1 {
+            if self.fmt_size == 0x42 {
+                self.vorb_offset = self.fmt_offset + 0x18;
             } else {
                 return Err(ParseError::Message("expected vorb chunk".to_string()));
             }
         }
-        match instance.vorb_size {
-            -1 | 0x28 | 0x2A | 0x2C | 0x32 | 0x34 => {
-                instance.infile.seek(SeekFrom::Start((instance.vorb_offset + 0x00) as u64))?;
-            },
-            _ => return Err(ParseError::Message("bad vorb size".to_string())),
-        }
-        instance.sample_count = read_32_le(&mut instance.infile)?;
-
-        match instance.vorb_size {
-            -1 | 0x2A => {
-                instance.no_granule = true;
-                instance.infile.seek(SeekFrom::Start((instance.vorb_offset + 0x4) as u64))?;
-                let mod_signal = read_32_le(&mut instance.infile)?;
-                if mod_signal != 0x4A && mod_signal != 0x4B &&
-                   mod_signal != 0x69 && mod_signal != 0x70 {
-                    instance.mod_packets = true;
-                }
-                instance.infile.seek(SeekFrom::Start((instance.vorb_offset + 0x10) as u64))?;
-            },
-            _ => {
-                instance.infile.seek(SeekFrom::Start((instance.vorb_offset + 0x18) as u64))?;
-            }
-        }
-
+        let layout = match (self.overrides.vorb_layout, VorbLayout::known(self.vorb_size)) {
+            (Some(layout), _) | (None, Some(layout)) => layout,
+            (None, None) => self.detect_vorb_layout()?,
+        };
+        self.read_vorb(&layout)?;
+        self.vorb_layout = Some(layout);
         match force_packet_format {
-            ForcePacketFormat::NoModPackets => instance.mod_packets = false,
-            ForcePacketFormat::ModPackets => instance.mod_packets = true,
-        }
-
-        instance.setup_packet_offset = read_32_le(&mut instance.infile)?;
-        instance.first_audio_packet_offset = read_32_le(&mut instance.infile)?;
-
-        match instance.vorb_size {
-            -1 | 0x2A => {
-                instance.infile.seek(SeekFrom::Start((instance.vorb_offset + 0x24) as u64))?;
-            },
-            0x32 | 0x34 => {
-                instance.infile.seek(SeekFrom::Start((instance.vorb_offset + 0x2C) as u64))?;
-            },
-            _ => {}
+            ForcePacketFormat::NoModPackets => self.mod_packets = false,
+            ForcePacketFormat::ModPackets => self.mod_packets = true,
         }
-
-        match instance.vorb_size {
-            0x28 | 0x2C => {
-                instance.header_triad_present = true;
-                instance.old_packet_headers = true;
-            },
-            -1 | 0x2A | 0x32 | 0x34 => {
-                instance.uid = read_32_le(&mut instance.infile)?;
-                instance.blocksize_0_pow = instance.infile.read_u8()?;
-                instance.blocksize_1_pow = instance.infile.read_u8()?;
-            },
-            _ => {}
+        self.apply_overrides();
+        if !self.header_triad_present
+            && (self.blocksize_0_pow < 6
+                || self.blocksize_1_pow > 13
+                || self.blocksize_0_pow > self.blocksize_1_pow)
+        {
+            return Err(ParseError::Message("invalid blocksizes".to_string()));
         }
 
-        if instance.loop_count != 0 {
-            if instance.loop_end == 0 {
-                instance.loop_end = instance.sample_count;
+        if self.loop_count != 0 {
+            if self.loop_end == 0 {
+                self.loop_end = self.sample_count;
             } else {
-                instance.loop_end += 1;
+                self.loop_end = self.loop_end.checked_add(1)
+                    .ok_or_else(|| ParseError::Message("loops out of range".to_string()))?;
             }
-            if instance.loop_start >= instance.sample_count ||
-               instance.loop_end > instance.sample_count ||
-               instance.loop_start > instance.loop_end {
+            if self.loop_start >= self.sample_count ||
+               self.loop_end > self.sample_count ||
+               self.loop_start > self.loop_end {
                 return Err(ParseError::Message("loops out of range".to_string()));
             }
         }
 
-        match instance.subtype {
-            4 | 3 | 0x33 | 0x37 | 0x3b | 0x3f => { },
-            _ => { }
+        // Channel configs postdate the layouts with a vorb chunk of their own.
+        self.channel_layout = if self.fmt_size == 0x42 {
+            ChannelLayout::from_fmt(self.channels, self.subtype, true)
+        } else {
+            ChannelLayout::from_mask(self.channels, self.subtype, self.fmt_size - 0x12 >= 6)
+        };
+
+        Ok(())
+    }
+
+    /// Reads the vorb chunk fields where `layout` says they are.
+    fn read_vorb(&mut self, layout: &VorbLayout) -> Result<()> {
+        let read_32 = self.read_32;
+        let seek = |wem: &mut Self, at: i64| wem.infile.seek(SeekFrom::Start((wem.vorb_offset + at) as u64));
+
+        seek(self, layout.sample_count)?;
+        self.sample_count = read_32(&mut self.infile)?;
+
+        self.no_granule = layout.no_granule;
+        self.header_triad_present = layout.header_triad;
+        self.old_packet_headers = layout.old_packet_headers;
+        if let Some(at) = layout.mod_signal {
+            seek(self, at)?;
+            self.mod_signal = read_32(&mut self.infile)?;
+            if !is_standard_mod_signal(self.mod_signal) {
+                self.mod_packets = true;
+            }
         }
 
-        Ok(instance)
+        seek(self, layout.setup_packet_offset)?;
+        self.setup_packet_offset = read_32(&mut self.infile)?;
+        seek(self, layout.first_audio_packet_offset)?;
+        self.first_audio_packet_offset = read_32(&mut self.infile)?;
+
+        if let Some(at) = layout.uid {
+            seek(self, at)?;
+            self.uid = read_32(&mut self.infile)?;
+        }
+        if let Some(at) = layout.blocksizes {
+            seek(self, at)?;
+            self.blocksize_0_pow = self.infile.read_u8()?;
+            self.blocksize_1_pow = self.infile.read_u8()?;
+        }
+        Ok(())
+    }
+
+    /// Replaces what was read from the header with `overrides`, dropping a
+    /// seek index built from the old values.
+    fn apply_overrides(&mut self) {
+        let overrides = self.overrides;
+        self.seek_index = None;
+        if let Some(old_packet_headers) = overrides.old_packet_headers {
+            self.old_packet_headers = old_packet_headers;
+        }
+        if let Some(no_granule) = overrides.no_granule {
+            self.no_granule = no_granule;
+        }
+        if let Some(header_triad_present) = overrides.header_triad_present {
+            self.header_triad_present = header_triad_present;
+        }
+        if let Some(offset) = overrides.setup_packet_offset {
+            self.setup_packet_offset = offset;
+        }
+        if let Some(offset) = overrides.first_audio_packet_offset {
+            self.first_audio_packet_offset = offset;
+        }
+        if let Some((blocksize_0_pow, blocksize_1_pow)) = overrides.blocksizes {
+            self.blocksize_0_pow = blocksize_0_pow;
+            self.blocksize_1_pow = blocksize_1_pow;
+        }
     }
 }
 
-impl WwiseRiffVorbis<Cursor<Vec<u8>>>{
-    pub fn new(
-        buf: Cursor<Vec<u8>>,
-        file_name: &str,
-        codebooks_name: &str,
-        inline_codebooks: bool,
-        full_setup: bool,
-        force_packet_format: ForcePacketFormat,
-    ) -> Result<Self> {
-        let mut infile = BufReader::new(buf.clone());
-    
-        let mut instance = WwiseRiffVorbis {
-            // Since there is no file name, we use a placeholder.
-            file_name: file_name.to_string(),
-            codebooks_name: codebooks_name.to_string(),
-            infile,
-            file_size: -1,
-            little_endian: true,
-            riff_size: -1,
-            fmt_offset: -1,
-            cue_offset: -1,
-            list_offset: -1,
-            smpl_offset: -1,
-            vorb_offset: -1,
-            data_offset: -1,
-            fmt_size: -1,
-            cue_size: -1,
-            list_size: -1,
-            smpl_size: -1,
-            vorb_size: -1,
-            data_size: -1,
-            channels: 0,
-            sample_rate: 0,
-            avg_bytes_per_second: 0,
-            ext_unk: 0,
-            subtype: 0,
-            cue_count: 0,
-            loop_count: 0,
-            loop_start: 0,
-            loop_end: 0,
-            sample_count: 0,
-            setup_packet_offset: 0,
-            first_audio_packet_offset: 0,
-            uid: 0,
-            blocksize_0_pow: 0,
-            blocksize_1_pow: 0,
-            inline_codebooks,
-            full_setup,
-            header_triad_present: false,
-            old_packet_headers: false,
-            no_granule: false,
-            mod_packets: false,
-            // Start with the little-endian functions by default.
-            read_16: read_16_le_dyn,
-            read_32: read_32_le_dyn,
-        };
-    
-        // Set the file size from the length of the buffer.
-        instance.file_size = buf.get_ref().len() as i64;
-        if instance.file_size < 12 {
-            return Err(ParseError::Message("Buffer too small".to_string()));
-        }
-    
-        // Read RIFF header.
-        let mut riff_head = [0u8; 4];
-        instance.infile.seek(SeekFrom::Start(0))?;
-        instance.infile.read_exact(&mut riff_head)?;
-    
-        if &riff_head == b"RIFX" {
-            instance.little_endian = false;
-        } else if &riff_head == b"RIFF" {
-            instance.little_endian = true;
-        } else {
-            return Err(ParseError::Message("missing RIFF".to_string()));
-        }
-        instance.read_16 = if instance.little_endian { read_16_le_dyn } else { read_16_be_dyn };
-        instance.read_32 = if instance.little_endian { read_32_le_dyn } else { read_32_be_dyn };
-    
-        let read_32_fn = instance.read_32;
-        instance.riff_size = read_32_fn(&mut instance.infile)? as i64 + 8;
-        if instance.riff_size > instance.file_size {
-            return Err(ParseError::Message("RIFF truncated".to_string()));
-        }
-    
-        // Check the WAVE header.
-        let mut wave_head = [0u8; 4];
-        instance.infile.read_exact(&mut wave_head)?;
-        if &wave_head != b"WAVE" {
-            return Err(ParseError::Message("missing WAVE".to_string()));
-        }
-    
-        // Iterate through chunks.
-        let mut chunk_offset = 12;
-        while chunk_offset < instance.riff_size {
-            instance.infile.seek(SeekFrom::Start(chunk_offset as u64))?;
-            if chunk_offset + 8 > instance.riff_size {
-                return Err(ParseError::Message("chunk header truncated".to_string()));
-            }
-            let mut chunk_type = [0u8; 4];
-            instance.infile.read_exact(&mut chunk_type)?;
-            let chunk_size = read_32_fn(&mut instance.infile)? as i64;
-    
-            match &chunk_type {
-                b"fmt " => {
-                    instance.fmt_offset = chunk_offset + 8;
-                    instance.fmt_size = chunk_size;
-                },
-                b"cue " => {
-                    instance.cue_offset = chunk_offset + 8;
-                    instance.cue_size = chunk_size;
-                },
-                b"LIST" => {
-                    instance.list_offset = chunk_offset + 8;
-                    instance.list_size = chunk_size;
-                },
-                b"smpl" => {
-                    instance.smpl_offset = chunk_offset + 8;
-                    instance.smpl_size = chunk_size;
-                },
-                b"vorb" => {
-                    instance.vorb_offset = chunk_offset + 8;
-                    instance.vorb_size = chunk_size;
-                },
-                b"data" => {
-                    instance.data_offset = chunk_offset + 8;
-                    instance.data_size = chunk_size;
-                },
-                _ => { /* ignore unrecognized chunks */ }
-            }
-            chunk_offset += 8 + chunk_size;
-        }
-        if chunk_offset > instance.riff_size {
-            return Err(ParseError::Message("chunk truncated".to_string()));
-        }
-        if instance.fmt_offset == -1 || instance.data_offset == -1 {
-            return Err(ParseError::Message("expected fmt, data chunks".to_string()));
-        }
-    
-        // Parse the fmt chunk.
-        instance.infile.seek(SeekFrom::Start(instance.fmt_offset as u64))?;
-        if read_16_le(&mut instance.infile)? != 0xFFFF {
-            return Err(ParseError::Message("bad codec id".to_string()));
-        }
-        instance.channels = read_16_le(&mut instance.infile)?;
-        instance.sample_rate = read_32_le(&mut instance.infile)?;
-        instance.avg_bytes_per_second = read_32_le(&mut instance.infile)?;
-        if read_16_le(&mut instance.infile)? != 0 {
-            return Err(ParseError::Message("bad block align".to_string()));
-        }
-        if read_16_le(&mut instance.infile)? != 0 {
-            return Err(ParseError::Message("expected 0 bps".to_string()));
-        }
-        let extra_len = read_16_le(&mut instance.infile)?;
-        if (instance.fmt_size - 0x12) as u16 != extra_len {
-            return Err(ParseError::Message("bad extra fmt length".to_string()));
-        }
-        if instance.fmt_size - 0x12 >= 2 {
-            instance.ext_unk = read_16_le(&mut instance.infile)?;
-            if instance.fmt_size - 0x12 >= 6 {
-                instance.subtype = read_32_le(&mut instance.infile)?;
-            }
-        }
-        if instance.fmt_size == 0x28 {
-            let mut whoknowsbuf = [0u8; 16];
-            let whoknowsbuf_check: [u8; 16] =
-                [1, 0, 0, 0, 0, 0, 0x10, 0, 0x80, 0, 0, 0xAA, 0, 0x38, 0x9b, 0x71];
-            instance.infile.read_exact(&mut whoknowsbuf)?;
-            if whoknowsbuf != whoknowsbuf_check {
-                return Err(ParseError::Message("expected signature in extra fmt?".to_string()));
-            }
-        }
-    
-        // Parse cue chunk if present.
-        if instance.cue_offset != -1 {
-            instance.infile.seek(SeekFrom::Start(instance.cue_offset as u64))?;
-            instance.cue_count = read_32_le(&mut instance.infile)?;
-        }
-    
-        // Parse smpl chunk if present.
-        if instance.smpl_offset != -1 {
-            instance.infile.seek(SeekFrom::Start((instance.smpl_offset + 0x1C) as u64))?;
-            instance.loop_count = read_32_le(&mut instance.infile)?;
-            if instance.loop_count != 1 {
-                return Err(ParseError::Message("expected one loop".to_string()));
-            }
-            instance.infile.seek(SeekFrom::Start((instance.smpl_offset + 0x2C) as u64))?;
-            instance.loop_start = read_32_le(&mut instance.infile)?;
-            instance.loop_end = read_32_le(&mut instance.infile)?;
-        }
-    
-        // Handle vorb chunk.
-        if instance.vorb_offset == -1 {
-            if instance.fmt_size == 0x42 {
-                instance.vorb_offset = instance.fmt_offset + 0x18;
-            } else {
-                return Err(ParseError::Message("expected vorb chunk".to_string()));
-            }
-        }
-        match instance.vorb_size {
-            -1 | 0x28 | 0x2A | 0x2C | 0x32 | 0x34 => {
-                instance.infile.seek(SeekFrom::Start((instance.vorb_offset + 0x00) as u64))?;
-            },
-            _ => return Err(ParseError::Message("bad vorb size".to_string())),
-        }
-        instance.sample_count = read_32_le(&mut instance.infile)?;
-    
-        match instance.vorb_size {
-            -1 | 0x2A => {
-                instance.no_granule = true;
-                instance.infile.seek(SeekFrom::Start((instance.vorb_offset + 0x4) as u64))?;
-                let mod_signal = read_32_le(&mut instance.infile)?;
-                if mod_signal != 0x4A && mod_signal != 0x4B &&
-                   mod_signal != 0x69 && mod_signal != 0x70 {
-                    instance.mod_packets = true;
-                }
-                instance.infile.seek(SeekFrom::Start((instance.vorb_offset + 0x10) as u64))?;
-            },
-            _ => {
-                instance.infile.seek(SeekFrom::Start((instance.vorb_offset + 0x18) as u64))?;
-            }
-        }
-    
-        // Apply forced packet format.
-        match force_packet_format {
-            ForcePacketFormat::NoModPackets => instance.mod_packets = false,
-            ForcePacketFormat::ModPackets => instance.mod_packets = true,
-        }
-    
-        instance.setup_packet_offset = read_32_le(&mut instance.infile)?;
-        instance.first_audio_packet_offset = read_32_le(&mut instance.infile)?;
-    
-        match instance.vorb_size {
-            -1 | 0x2A => {
-                instance.infile.seek(SeekFrom::Start((instance.vorb_offset + 0x24) as u64))?;
-            },
-            0x32 | 0x34 => {
-                instance.infile.seek(SeekFrom::Start((instance.vorb_offset + 0x2C) as u64))?;
-            },
-            _ => {}
-        }
-    
-        match instance.vorb_size {
-            0x28 | 0x2C => {
-                instance.header_triad_present = true;
-                instance.old_packet_headers = true;
-            },
-            -1 | 0x2A | 0x32 | 0x34 => {
-                instance.uid = read_32_le(&mut instance.infile)?;
-                instance.blocksize_0_pow = instance.infile.read_u8()?;
-                instance.blocksize_1_pow = instance.infile.read_u8()?;
-            },
-            _ => {}
-        }
-    
-        if instance.loop_count != 0 {
-            if instance.loop_end == 0 {
-                instance.loop_end = instance.sample_count;
-            } else {
-                instance.loop_end += 1;
-            }
-            if instance.loop_start >= instance.sample_count ||
-               instance.loop_end > instance.sample_count ||
-               instance.loop_start > instance.loop_end {
-                return Err(ParseError::Message("loops out of range".to_string()));
-            }
-        }
-
-        match instance.subtype {
-            4 | 3 | 0x33 | 0x37 | 0x3b | 0x3f => { },
-            _ => { }
-        }
-    
-        Ok(instance)
-    }
-}
-
-
 impl<R: Read + Seek> WwiseRiffVorbis<R> {
 
     pub fn print_info(&self) {
@@ -752,11 +1075,18 @@ impl<R: Read + Seek> WwiseRiffVorbis<R> {
             self.channels, 
             if self.channels != 1 { "s" } else { "" },
             self.sample_rate,
-            self.avg_bytes_per_second * 8
+            self.avg_bytes_per_second.wrapping_mul(8)
         );
+        tracing::trace!("channel layout: {}", self.channel_layout);
+        if self.channel_layout.reorders() {
+            tracing::trace!("channels not in Vorbis order, see ChannelLayout::to_vorbis_order");
+        }
         if self.loop_count != 0 {
             tracing::trace!("loop from {} to {}", self.loop_start, self.loop_end);
         }
+        if let Some(layout) = self.vorb_layout.filter(|layout| !layout.is_known()) {
+            tracing::trace!("detected vorb layout: {:?}", layout);
+        }
         if self.old_packet_headers {
             tracing::trace!("8 byte (old) packet headers");
         } else if self.no_granule {
@@ -782,115 +1112,280 @@ impl<R: Read + Seek> WwiseRiffVorbis<R> {
         } else {
             tracing::trace!("standard Vorbis packets");
         }
+        match self.detect_wwise_version().range {
+            Some(range) => tracing::trace!("written by Wwise {}", range),
+            None => tracing::trace!("Wwise version unclear, header clues disagree"),
+        }
     }
 
     pub fn generate_ogg(&mut self) -> Result<()> {
         let path = Path::new(&self.file_name);
         let ogg_path = path.with_extension("ogg");
         let file = File::create(&ogg_path)?;
-        let writer = BufWriter::new(file);
-        let mut ogg_stream = BitOggStream::new(writer);
+        self.generate_ogg_to(BufWriter::new(file))
+    }
 
-        let mut mode_blockflag = Vec::new();
-        let mut prev_blockflag = false;
-        let mut mode_bits = 0;
-        if self.header_triad_present {
-            // (Call generate_ogg_header_with_triad here) 
-            //self.generate_ogg_header_with_triad(&mut ogg_stream)?;
-            unimplemented!("Have not created this case since our project wont need it yet");
-        } else {
-            self.generate_ogg_header(&mut ogg_stream, &mut mode_blockflag, &mut mode_bits)?;
-        }
-
-        // Audio pages: start at the first audio packet offset.
-        let mut offset = self.data_offset + self.first_audio_packet_offset as i64;
-        while offset < self.data_offset + self.data_size {
-            let (packet_header_size, size, packet_payload_offset, granule, next_offset) =
-                if self.old_packet_headers {
-                    let audio_packet = Packet8::new(&mut self.infile, offset, self.little_endian)?;
-                    (
-                        audio_packet.header_size(),
-                        audio_packet.size(),
-                        audio_packet.offset(),
-                        audio_packet.granule(),
-                        audio_packet.next_offset(),
-                    )
-                } else {
-                    let audio_packet = Packet::new(&mut self.infile, offset, self.little_endian, self.no_granule)?;
-                    (
-                        audio_packet.header_size(),
-                        audio_packet.size() as u32,
-                        audio_packet.offset(),
-                        audio_packet.granule(),
-                        audio_packet.next_offset(),
-                    )
+    /// Same as `generate_ogg`, but writes the Ogg stream to `writer`
+    /// instead of next to the input file.
+    /// With `verify` set the stream is kept in memory and only written once it has been checked.
+    pub fn generate_ogg_to<W: Write>(&mut self, writer: W) -> Result<()> {
+        self.generate_ogg_observed(writer, None, None)
+    }
+
+    /// Same as `generate_ogg_to`, reporting progress to `observer` after every
+    /// audio packet. If `cancel` is triggered the conversion stops before the
+    /// next packet with `ParseError::Cancelled`, leaving a partial stream in
+    /// `writer` (nothing, when `verify` is set).
+    pub fn generate_ogg_observed<W: Write>(
+        &mut self,
+        mut writer: W,
+        observer: Option<&mut dyn ProgressObserver>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<()> {
+        if !self.verify {
+            let mut ogg_stream = BitOggStream::new(writer);
+            return self.write_ogg_observed(&mut ogg_stream, observer, cancel);
+        }
+        let mut ogg = Vec::new();
+        {
+            let mut ogg_stream = BitOggStream::new(&mut ogg);
+            self.write_ogg_observed(&mut ogg_stream, observer, cancel)?;
+        }
+        self.verify_ogg(&ogg)?;
+        writer.write_all(&ogg)?;
+        Ok(())
+    }
+
+    /// Re-reads an Ogg stream generated from this wem and checks it against
+    /// what the wem header says, without trusting the packet walk that
+    /// produced it: the framing and Vorbis headers are valid, the setup header
+    /// parses, every audio packet uses a defined mode, the final granule equals
+    /// `sample_count`, the audio packets fill the data chunk exactly and every
+    /// seek table entry points at one of them.
+    ///
+    /// Granules derived for wems without packet granules are recomputed from
+    /// the output's own block sizes, and `sample_count` must end within the
+    /// last packet rather than merely match the granule it was cut down to.
+    /// Without `derive_granules` such wems have no final granule to check, but
+    /// `sample_count` must still end within the last packet.
+    /// Empty audio packets leave nothing in the output, so a wem that has
+    /// them fails the data chunk check.
+    pub fn verify_ogg(&mut self, ogg: &[u8]) -> Result<()> {
+        let invalid = |e: ParseError| VerifyError::InvalidStream(e.to_string());
+        let mut packets = OggPacketReader::new(ogg);
+        let mut next_header = |name: &str| -> Result<Vec<u8>> {
+            match packets.next_packet().map_err(invalid)? {
+                Some(packet) => Ok(packet.data),
+                None => Err(VerifyError::InvalidStream(format!("missing {} header", name)).into()),
+            }
+        };
+        let identification = parse_identification(&next_header("identification")?).map_err(invalid)?;
+        parse_comment(&next_header("comment")?).map_err(invalid)?;
+        let mode_blockflag = parse_setup_modes(&next_header("setup")?, identification.channels)
+            .map_err(|e| VerifyError::SetupUnparseable(e.to_string()))?;
+
+        let header_size = self.packet_header_size() as u64;
+        let mut granules = GranuleTracker::new(identification.blocksize_0_pow, identification.blocksize_1_pow);
+        // Granule positions after the last two packets, counted from their block sizes.
+        let mut derived = (0, 0);
+        // Offset of every packet in the data chunk, from the first audio packet.
+        let mut starts = Vec::new();
+        let mut bytes = 0u64;
+        let mut last_granule = None;
+        while let Some(packet) = packets.next_packet().map_err(invalid)? {
+            if !packet.data.is_empty() {
+                let mode = audio_packet_mode(&packet.data, mode_blockflag.len()).map_err(invalid)?;
+                let Some(&blockflag) = mode_blockflag.get(mode as usize) else {
+                    return Err(VerifyError::InvalidMode {
+                        packet: starts.len() as u64,
+                        mode,
+                        mode_count: mode_blockflag.len(),
+                    }
+                    .into());
                 };
+                derived = (derived.1, granules.push(blockflag));
+            }
+            if packet.granule.is_some() {
+                last_granule = packet.granule;
+            }
+            // Rebuilding a mod packet adds its packet type and window bits.
+            let payload = (packet.data.len() as u64).saturating_sub(self.mod_packets as u64);
+            starts.push(bytes);
+            bytes += header_size + payload;
+        }
 
-            if offset + packet_header_size > self.data_offset + self.data_size {
-                return Err(ParseError::Message("page header truncated".into()));
+        let report = validate_ogg(ogg)?;
+        if let Some(issue) = report.issues.first() {
+            return Err(VerifyError::InvalidStream(issue.to_string()).into());
+        }
+        let sample_count = self.sample_count as u64;
+        let granules_written = !self.no_granule || self.derive_granules;
+        if granules_written && last_granule != Some(sample_count) {
+            return Err(VerifyError::GranuleMismatch { expected: sample_count, actual: last_granule }.into());
+        }
+        if self.no_granule && !(derived.0 < sample_count && sample_count <= derived.1) {
+            return Err(VerifyError::SampleCountMismatch { sample_count, start: derived.0, end: derived.1 }.into());
+        }
+        let expected = (self.data_size - self.first_audio_packet_offset as i64) as u64;
+        if bytes != expected {
+            return Err(VerifyError::DataSizeMismatch { expected, actual: bytes }.into());
+        }
+        let first_audio = (self.data_offset + self.first_audio_packet_offset as i64) as u64;
+        for point in self.seek_table()? {
+            let at = point.offset.checked_sub(first_audio);
+            if at.is_none_or(|at| starts.binary_search(&at).is_err()) {
+                return Err(VerifyError::SeekTableMismatch { offset: point.offset }.into());
             }
+        }
+        Ok(())
+    }
 
-            offset = packet_payload_offset;
-            self.infile.seek(SeekFrom::Start(offset as u64))?;
+    /// Walks the audio packets of the data chunk, whichever header style the wem uses.
+    pub fn packets(&mut self) -> WwisePackets<'_, R> {
+        let cursor = PacketCursor::new(self);
+        WwisePackets { wem: self, cursor }
+    }
 
-            if granule == 0xFFFFFFFF {
-                ogg_stream.set_granule(1);
-            } else {
-                ogg_stream.set_granule(granule);
-            }
-            if self.mod_packets {
-                if mode_blockflag.is_empty() {
-                    return Err(ParseError::Message("didn't load mode_blockflag".into()));
-                }
-                // Output one bit for packet type (0 == audio)
-                BitUint::<1>::new(0)?.write_to(&mut ogg_stream)?;
-
-                let mut ss = BitStream::new(&mut self.infile);
-
-                let mode_number = BitUintV::read_from(&mut ss, mode_bits as usize)?;
-                mode_number.write_to(&mut ogg_stream)?;
-                let remainder = BitUintV::read_from(&mut ss, 8 - mode_bits as usize)?;
-                // Peek at the next frame’s mode if necessary.
-
-                if mode_blockflag[mode_number.total as usize]{
-                    self.infile.seek(SeekFrom::Start(next_offset as u64))?;
-                    let mut next_blockflag = false;
-                    if next_offset + packet_header_size <= self.data_offset + self.data_size{
-                        let audio_packet = Packet::new(&mut self.infile, next_offset, self.little_endian, self.no_granule)?;
-                        let next_packet_size = audio_packet.size();
-                        if next_packet_size > 0{
-                            self.infile.seek(SeekFrom::Start(audio_packet.offset() as u64))?;
-                            let mut ss = BitStream::new(&mut self.infile);
-                            let next_mode_number = BitUintV::read_from(&mut ss, mode_bits as usize)?;
-                            next_blockflag = mode_blockflag[next_mode_number.total as usize];
-                        }
-                    }
+    /// Turns the wem into a reader that produces the Ogg stream on demand.
+    /// `verify` is not applied, since it needs the whole stream up front.
+    pub fn into_ogg_reader(self) -> OggReader<R> {
+        OggReader {
+            wem: self,
+            ogg_stream: BitOggStream::new(Vec::new()),
+            audio: None,
+            pos: 0,
+            done: false,
+        }
+    }
 
-                    BitUint::<1>::new(prev_blockflag as u32)?.write_to(&mut ogg_stream)?;
-                    BitUint::<1>::new(if next_blockflag { 1 } else { 0 })?.write_to(&mut ogg_stream)?;
-                    self.infile.seek(SeekFrom::Start(offset as u64 + 1))?;
-                }
-                
-                prev_blockflag = mode_blockflag[mode_number.total as usize];
-                remainder.write_to(&mut ogg_stream)?;
-            } else {
-                let byte = self.infile.read_u8()?;
-                BitUint::<8>::new(byte as u32)?.write_to(&mut ogg_stream)?;
+    /// Rebuilds the stream as bare Vorbis packets, for decoders that take
+    /// packets rather than Ogg pages. The three headers are rebuilt up front.
+    pub fn vorbis_packets(&mut self) -> Result<VorbisPackets<'_, R>> {
+        let mut stream = BitPacketStream::new();
+        let (mode_blockflag, mode_bits) = self.write_headers(&mut stream)?;
+        let audio = AudioPackets::new(self, mode_blockflag, mode_bits);
+        Ok(VorbisPackets { wem: self, stream, audio, done: false })
+    }
+
+    /// Reads the payload of `packet`.
+    pub fn read_payload(&mut self, packet: &WwisePacket) -> Result<Vec<u8>> {
+        self.infile.seek(SeekFrom::Start(packet.payload_offset))?;
+        let mut payload = vec![0u8; packet.size as usize];
+        self.infile.read_exact(&mut payload)?;
+        Ok(payload)
+    }
+
+    /// Payload of `packet`, borrowed from the mapping for wems opened with
+    /// `new_mmap` and read otherwise.
+    pub(crate) fn payload(&mut self, packet: &WwisePacket) -> Result<Payload> {
+        #[cfg(feature = "mmap")]
+        if let Some(map) = &self.mapped {
+            let range = packet.payload_offset as usize..packet.next_offset() as usize;
+            if map.get(range.clone()).is_some() {
+                return Ok(Payload::Mapped(map.clone(), range));
             }
+        }
+        self.read_payload(packet).map(Payload::Owned)
+    }
 
-            // Write remaining bytes of the packet.
-            for _ in 1..size {
-                let byte = self.infile.read_u8()?;
-                BitUint::<8>::new(byte as u32)?.write_to(&mut ogg_stream)?;
+    /// Reads the packet header at `offset` in the data chunk.
+    pub(crate) fn packet_at(&mut self, offset: i64, index: u64) -> Result<WwisePacket> {
+        let header_size = self.packet_header_size();
+        if offset + header_size > self.data_offset + self.data_size {
+            return Err(ParseError::Message("page header truncated".into()));
+        }
+        #[cfg(feature = "mmap")]
+        if let Some(map) = &self.mapped {
+            if let Some(header) = map.get(offset as usize..(offset + header_size) as usize) {
+                return self.decode_packet_header(header, offset, index);
             }
-            offset = next_offset;
-            ogg_stream.flush_page(false, offset == self.data_offset + self.data_size)?;
         }
-        if offset > self.data_offset + self.data_size {
+        let mut header = [0u8; 8];
+        let header = &mut header[..header_size as usize];
+        self.infile.seek(SeekFrom::Start(offset as u64))?;
+        self.infile.read_exact(header)?;
+        self.decode_packet_header(header, offset, index)
+    }
+
+    /// Size of every packet header in the data chunk.
+    pub(crate) fn packet_header_size(&self) -> i64 {
+        if self.old_packet_headers {
+            8
+        } else if self.no_granule {
+            2
+        } else {
+            6
+        }
+    }
+
+    /// Decodes the `packet_header_size` bytes of the header found at `offset`.
+    pub(crate) fn decode_packet_header(&self, header: &[u8], offset: i64, index: u64) -> Result<WwisePacket> {
+        let mut header = Cursor::new(header);
+        let (header_size, size, granule) = if self.old_packet_headers {
+            let packet = Packet8::new(&mut header, 0, self.little_endian)?;
+            (packet.header_size(), packet.size(), Some(packet.granule()))
+        } else {
+            let packet = Packet::new(&mut header, 0, self.little_endian, self.no_granule)?;
+            let granule = if self.no_granule { None } else { Some(packet.granule()) };
+            (packet.header_size(), packet.size() as u32, granule)
+        };
+        if size > self.limits.max_packet_size {
+            return Err(ParseError::Message(format!("packet {} is {} bytes, more than max_packet_size", index, size)));
+        }
+        if offset + header_size + size as i64 > self.data_offset + self.data_size {
             return Err(ParseError::Message("page truncated".into()));
         }
+        Ok(WwisePacket {
+            index,
+            header_offset: offset as u64,
+            payload_offset: (offset + header_size) as u64,
+            size,
+            granule,
+        })
+    }
+
+    /// Writes the header triad and all audio packets as one logical bitstream
+    /// into `ogg_stream`, which may already contain earlier chain links.
+    pub fn write_ogg<W: Write>(&mut self, ogg_stream: &mut BitOggStream<W>) -> Result<()> {
+        self.write_ogg_observed(ogg_stream, None, None)
+    }
+
+    fn write_ogg_observed<W: Write>(
+        &mut self,
+        ogg_stream: &mut BitOggStream<W>,
+        mut observer: Option<&mut dyn ProgressObserver>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<()> {
+        let cancelled = || cancel.is_some_and(|c| c.is_cancelled());
+        if cancelled() {
+            return Err(ParseError::Cancelled);
+        }
+        let (mode_blockflag, mode_bits) = self.write_headers(ogg_stream)?;
+        let mut audio = AudioPackets::new(self, mode_blockflag, mode_bits);
+        loop {
+            if cancelled() {
+                return Err(ParseError::Cancelled);
+            }
+            if !audio.write_next(self, ogg_stream)? {
+                return Ok(());
+            }
+            if let Some(observer) = observer.as_mut() {
+                observer.on_progress(audio.progress(self));
+            }
+        }
+    }
 
-        Ok(())
+    /// Writes the three Vorbis headers, returning the block flag of each mode
+    /// and the number of bits in a mode number.
+    pub(crate) fn write_headers<O: BitOggStreamT>(&mut self, os: &mut O) -> Result<(Vec<bool>, i32)> {
+        let mut mode_blockflag = Vec::new();
+        let mut mode_bits = 0;
+        if self.header_triad_present {
+            self.generate_ogg_header_with_triad(os, &mut mode_blockflag, &mut mode_bits)?;
+        } else {
+            self.generate_ogg_header(os, &mut mode_blockflag, &mut mode_bits)?;
+        }
+        Ok((mode_blockflag, mode_bits))
     }
 
     pub fn generate_ogg_header<O: BitOggStreamT>(
@@ -905,10 +1400,10 @@ impl<R: Read + Seek> WwiseRiffVorbis<R> {
             let vhead = VorbisPacketHeader::new(1);
             vhead.write_to(os)?;
             os.write_bits(0, 32)?;
-            os.write_bits(self.channels as u32, 8)?;
+            BitUint::<8>::new(self.channels as u32)?.write_to(os)?;
             os.write_bits(self.sample_rate, 32)?;
             os.write_bits(0, 32)?;
-            os.write_bits(self.avg_bytes_per_second * 8, 32)?;
+            os.write_bits(self.avg_bytes_per_second.wrapping_mul(8), 32)?;
             os.write_bits(0, 32)?;
             os.write_bits(self.blocksize_0_pow as u32, 4)?;
             os.write_bits(self.blocksize_1_pow as u32, 4)?;
@@ -923,10 +1418,7 @@ impl<R: Read + Seek> WwiseRiffVorbis<R> {
             let vendor = format!("converted from Audiokinetic Wwise by wem_converter {}", env!("CARGO_PKG_VERSION"));
             let vendor_size = BitUint::<32>::new(vendor.len() as u32)?;
             os.write_bits(vendor_size.total, 32)?;
-            for &b in vendor.as_bytes() {
-                let c = BitUint::<8>::new(b as u32)?;
-                os.write_bits(c.total, 8)?;
-            }
+            os.write_all(vendor.as_bytes())?;
             
             if self.loop_count == 0 {
                 let user_comment_count = BitUint::<32>::new(0)?;
@@ -940,17 +1432,11 @@ impl<R: Read + Seek> WwiseRiffVorbis<R> {
                 
                 let loop_start_comment_length = BitUint::<32>::new(loop_start_str.len() as u32)?;
                 os.write_bits(loop_start_comment_length.total, 32)?;
-                for &b in loop_start_str.as_bytes() {
-                    let c = BitUint::<8>::new(b as u32)?;
-                    os.write_bits(c.total, 8)?;
-                }
+                os.write_all(loop_start_str.as_bytes())?;
                 
                 let loop_end_comment_length = BitUint::<32>::new(loop_end_str.len() as u32)?;
                 os.write_bits(loop_end_comment_length.total, 32)?;
-                for &b in loop_end_str.as_bytes() {
-                    let c = BitUint::<8>::new(b as u32)?;
-                    os.write_bits(c.total, 8)?;
-                }
+                os.write_all(loop_end_str.as_bytes())?;
             }
             let framing = BitUint::<1>::new(1)?;
             os.write_bits(framing.total, 1)?;
@@ -959,8 +1445,10 @@ impl<R: Read + Seek> WwiseRiffVorbis<R> {
 
         // generate setup packet
         {
-            let vhead = VorbisPacketHeader::new(5);
-            vhead.write_to(os)?;
+            // Built apart so a full setup can be read back for its modes.
+            let out = os;
+            let mut setup = BitPacketStream::new();
+            let os = &mut setup;
             let setup_packet = Packet::new(
                 &mut self.infile,
                 self.data_offset + self.setup_packet_offset as i64,
@@ -974,271 +1462,221 @@ impl<R: Read + Seek> WwiseRiffVorbis<R> {
             }
             let mut ss = BitStream::new(&mut self.infile);
             
-            let codebook_count_less1_val = BitUint::<8>::read_from(&mut ss)?.total;
-            let codebook_count_less1 = BitUint::<8>::new(codebook_count_less1_val)?;
-            let codebook_count = codebook_count_less1.total + 1;
-            os.write_bits(codebook_count_less1.total, 8)?;
-            if self.inline_codebooks {
-                let mut cbl = crate::codebook::CodebookLibrary::new_empty();
-                for _ in 0..(codebook_count as usize) {
-                    if self.full_setup {
-                        cbl.copy(&mut ss, os)?;
-                    } else {
-                        cbl.rebuild(0, os)?;
-                    }
-                }
+            let codebooks = if self.inline_codebooks {
+                CodebookSource::Inline { full_setup: self.full_setup }
             } else {
-                let cbl = crate::codebook::CodebookLibrary::new_from_file(&self.codebooks_name)?;
-                for i in 0..(codebook_count as usize) {
-                    let codebook_id = BitUint::<10>::read_from(&mut ss)?;
-                    if let Err(e) = cbl.rebuild(codebook_id.total as usize, os) {
-                        if codebook_id.total == 0x342 {
-                            let codebook_identifier = BitUint::<14>::read_from(&mut ss)?;
-                            if codebook_identifier.total == 0x1590 {
-                                return Err(ParseError::Message("invalid codebook id 0x342, try --full-setup".into()));
-                            }
-                        }
-                        return Err(e);
-                    }
-                }
-            }
-            
-            // --- Time Domain Transforms (placeholder) ---
-            let time_count_less1 = BitUint::<6>::new(0)?;
-            os.write_bits(time_count_less1.total, 6)?;
-            let dummy_time_value = BitUint::<16>::new(0)?;
-            os.write_bits(dummy_time_value.total, 16)?;
-            
+                CodebookSource::Library(CodebookLibrary::new_from_file(&self.codebooks_name)?)
+            };
             if self.full_setup {
+                VorbisPacketHeader::new(5).write_to(os)?;
+                let codebook_count_less1 = BitUint::<8>::read_from(&mut ss)?;
+                codebook_count_less1.write_to(os)?;
+                for _ in 0..=codebook_count_less1.total {
+                    codebooks.read(&mut ss)?.write_to(os)?;
+                }
+
+                // --- Time Domain Transforms (placeholder) ---
+                let time_count_less1 = BitUint::<6>::new(0)?;
+                os.write_bits(time_count_less1.total, 6)?;
+                let dummy_time_value = BitUint::<16>::new(0)?;
+                os.write_bits(dummy_time_value.total, 16)?;
+
                 // For full setup, copy the remaining bits of the setup packet.
                 while ss.get_total_bits_read() < (setup_packet.size() as u64 * 8) {
-                    let bit = ss.get_bit()?;
-                    let bit_val = BitUint::<1>::new(if bit { 1 } else { 0 })?;
-                    os.write_bits(bit_val.total, 1)?;
+                    let bits_left = setup_packet.size() as u64 * 8 - ss.get_total_bits_read();
+                    let bits = bits_left.min(32) as u8;
+                    os.write_bits(ss.read_bits(bits)?, bits)?;
                 }
             } else {
-                // --- Process floors, residues, mappings, and modes ---
-                // Floor count:
-                let floor_count_less1 = BitUint::<6>::read_from(&mut ss)?;
-                let floor_count = floor_count_less1.total + 1;
-                floor_count_less1.write_to(os)?;
-                
-                // Rebuild floors.
-                for _ in 0..(floor_count as usize) {
-                    // Floor type is always 1.
-                    let floor_type = BitUint::<16>::new(1)?;
-                    floor_type.write_to(os)?;
-                    
-                    let floor1_partitions = BitUint::<5>::read_from(&mut ss)?;
-                    floor1_partitions.write_to(os)?;
-
-                    
-                    // Allocate storage for partition class list.
-                    let mut floor1_partition_class_list = vec![0u32; floor1_partitions.total as usize];
-                    let mut maximum_class = 0;
-                    for j in 0..(floor1_partitions.total as usize) {
-                        let class_val = BitUint::<4>::read_from(&mut ss)?;
-                        class_val.write_to(os)?;
-                        floor1_partition_class_list[j] = class_val.total;
-                        if class_val.total > maximum_class {
-                            maximum_class = class_val.total;
-                        }
-                    }
-                    
-                    // Allocate dimensions for each class.
-                    let mut floor1_class_dimensions_list = vec![0u32; (maximum_class + 1) as usize];
-                    for j in 0..=maximum_class {
-                        let class_dimensions_less1 = BitUint::<3>::read_from(&mut ss)?;
-                        class_dimensions_less1.write_to(os)?;
-                        floor1_class_dimensions_list[j as usize] = class_dimensions_less1.total + 1;
-                        
-                        let class_subclasses = BitUint::<2>::read_from(&mut ss)?;
-                        class_subclasses.write_to(os)?;
-                        if class_subclasses.total != 0 {
-                            let masterbook = BitUint::<8>::read_from(&mut ss)?;
-                            masterbook.write_to(os)?;
-                            if masterbook.total >= codebook_count {
-                                return Err(ParseError::Message("invalid floor1 masterbook".into()));
-                            }
-                        }
-                        for _ in 0..(1 << class_subclasses.total) {
-                            let subclass_book_plus1 = BitUint::<8>::read_from(&mut ss)?;
-                            subclass_book_plus1.write_to(os)?;
-                            let subclass_book = (subclass_book_plus1.total as i32) - 1;
-                            if subclass_book >= 0 && (subclass_book as u32) >= codebook_count {
-                                return Err(ParseError::Message("invalid floor1 subclass book".into()));
-                            }
-                        }
-                    }
-                    
-                    let floor1_multiplier_less1 = BitUint::<2>::read_from(&mut ss)?;
-                    floor1_multiplier_less1.write_to(os)?;
-                    let rangebits = BitUint::<4>::read_from(&mut ss)?;
-                    rangebits.write_to(os)?;
-                    for i in 0..(floor1_partitions.total as usize) {
-                        let current_class_number = floor1_partition_class_list[i];
-                        for _ in 0..(floor1_class_dimensions_list[current_class_number as usize]) {
-                            let x = BitUintV::read_from(&mut ss, rangebits.total as usize)?;
-                            x.write_to(os)?;
-                        }
-                    }
-                }
-                // Residue count.
-                let residue_count_less1 = BitUint::<6>::read_from(&mut ss)?;
-                let residue_count = residue_count_less1.total + 1;
-                residue_count_less1.write_to(os)?;
-                for i in 0..(residue_count as usize) {
-                    let residue_type = BitUint::<2>::read_from(&mut ss)?;
-                    BitUint::<16>::new(residue_type.total)?.write_to(os)?;
-                    if residue_type.total > 2 {
-                        return Err(ParseError::Message("invalid residue type".into()));
-                    }
-                    let residue_begin = BitUint::<24>::read_from(&mut ss)?;
-                    let residue_end = BitUint::<24>::read_from(&mut ss)?;
-                    let residue_partition_size_less1 = BitUint::<24>::read_from(&mut ss)?;
-                    let residue_classifications_less1 = BitUint::<6>::read_from(&mut ss)?;
-                    let residue_classbook = BitUint::<8>::read_from(&mut ss)?;
-                    let residue_classifications = residue_classifications_less1.total + 1;
-                    residue_begin.write_to(os)?;
-                    residue_end.write_to(os)?;
-                    residue_partition_size_less1.write_to(os)?;
-                    residue_classifications_less1.write_to(os)?;
-                    residue_classbook.write_to(os)?;
-                    if residue_classbook.total >= codebook_count {
-                        return Err(ParseError::Message("invalid residue classbook".into()));
-                    }
-
-                    let mut residue_cascade = vec![0u32; residue_classifications as usize];
-                    for j in 0..(residue_classifications as usize) {
-                        // Read 3 bits for low_bits.
-                        let low_bits = BitUint::<3>::read_from(&mut ss)?;
-                        low_bits.write_to(os)?;
-                        let bitflag = BitUint::<1>::read_from(&mut ss)?;
-                        bitflag.write_to(os)?;
-
-                        let high_bits = if bitflag.total != 0 {
-                            BitUint::<5>::read_from(&mut ss)?
-                        } else {
-                            BitUint::<5>::new(0)?
-                        };
-                        if bitflag.total != 0 {
-                            high_bits.write_to(os)?;
-                        }
-                        
-                        residue_cascade[j] = high_bits.total * 8 + low_bits.total;
-                    }
-                    
-                    for j in 0..(residue_classifications as usize) {
-                        for k in 0..8 {
-                            if residue_cascade[j] & (1 << k) != 0 {
-                                let residue_book = BitUint::<8>::read_from(&mut ss)?;
-                                residue_book.write_to(os)?;
-                                if residue_book.total >= codebook_count {
-                                    return Err(ParseError::Message("invalid residue book".into()));
-                                }
-                            }
-                        }
-                    }
-                }
-                    
-                // Mapping count.
-                let mapping_count_less1 = BitUint::<6>::read_from(&mut ss)?;
-                let mapping_count = mapping_count_less1.total + 1;
-                mapping_count_less1.write_to(os)?;
-                for _ in 0..(mapping_count as usize) {
-                    let mapping_type = BitUint::<16>::new(0)?;
-                    mapping_type.write_to(os)?;
-                    let submaps_flag = BitUint::<1>::read_from(&mut ss)?;
-                    submaps_flag.write_to(os)?;
-                    let mut submaps = 1;
-                    if submaps_flag.total != 0 {
-                        let submaps_less1 = BitUint::<4>::read_from(&mut ss)?;
-                        submaps = submaps_less1.total + 1;
-                        submaps_less1.write_to(os)?;
-                    }
-                    let square_polar_flag = BitUint::<1>::read_from(&mut ss)?;
-                    square_polar_flag.write_to(os)?;
-                    if square_polar_flag.total != 0 {
-                        let coupling_steps_less1 = BitUint::<8>::read_from(&mut ss)?;
-                        let coupling_steps = coupling_steps_less1.total + 1;
-                        coupling_steps_less1.write_to(os)?;
-                        for _ in 0..(coupling_steps as usize) {
-                            let magnitude = BitUintV::read_from(&mut ss, ilog((self.channels - 1) as u32) as usize)?;
-                            let angle = BitUintV::read_from(&mut ss, ilog((self.channels - 1) as u32) as usize)?;
-                            magnitude.write_to(os)?;
-                            angle.write_to(os)?;
-                            if angle.total == magnitude.total
-                                || magnitude.total >= self.channels as u32
-                                || angle.total >= self.channels as u32 {
-                                return Err(ParseError::Message("invalid coupling".into()));
-                            }
-                        }
-                    }
-                    let mapping_reserved = BitUint::<2>::read_from(&mut ss)?;
-                    mapping_reserved.write_to(os)?;
-                    if mapping_reserved.total != 0 {
-                        return Err(ParseError::Message("mapping reserved field nonzero".into()));
-                    }
-                    if submaps > 1 {
-                        for _ in 0..self.channels {
-                            let mapping_mux = BitUint::<4>::read_from(&mut ss)?;
-                            mapping_mux.write_to(os)?;
-                            if mapping_mux.total >= submaps {
-                                return Err(ParseError::Message("mapping_mux >= submaps".into()));
-                            }
-                        }
-                    }
-                    for _ in 0..submaps {
-                        let time_config = BitUint::<8>::read_from(&mut ss)?;
-                        time_config.write_to(os)?;
-                        let floor_number = BitUint::<8>::read_from(&mut ss)?;
-                        floor_number.write_to(os)?;
-                        if floor_number.total >= mapping_count {
-                            return Err(ParseError::Message("invalid floor mapping".into()));
-                        }
-                        let residue_number = BitUint::<8>::read_from(&mut ss)?;
-                        residue_number.write_to(os)?;
-                        if residue_number.total >= mapping_count {
-                            return Err(ParseError::Message("invalid residue mapping".into()));
-                        }
-                    }
-                }
-                
-                let mode_count_less1 = BitUint::<6>::read_from(&mut ss)?;
-                let mode_count = mode_count_less1.total + 1;
-                mode_count_less1.write_to(os)?;
-                
-                *mode_blockflag = Vec::with_capacity(mode_count as usize);
-                *mode_bits = ilog(mode_count - 1);
-                for _ in 0..(mode_count as usize) {
-                    let block_flag = BitUint::<1>::read_from(&mut ss)?;
-                    block_flag.write_to(os)?;
-                    mode_blockflag.push(block_flag.total != 0);
-                    let windowtype = BitUint::<16>::new(0)?;
-                    windowtype.write_to(os)?;
-                    let transformtype = BitUint::<16>::new(0)?;
-                    transformtype.write_to(os)?;
-                    let mapping = BitUint::<8>::read_from(&mut ss)?;
-                    mapping.write_to(os)?;
-                    if mapping.total >= mapping_count {
-                        return Err(ParseError::Message("invalid mode mapping".into()));
-                    }
-                }
-                
-                let framing = BitUint::<1>::new(1)?;
-                framing.write_to(os)?;
+                let vorbis_setup =
+                    VorbisSetup::read_stripped(&mut ss, self.channels as u8, |ss| codebooks.read(ss))?;
+                *mode_blockflag = vorbis_setup.mode_blockflag();
+                *mode_bits = vorbis_setup.mode_bits();
+                vorbis_setup.write_to(os)?;
             }
-            
+
             os.flush_page(false, false)?;
 
-            if (ss.get_total_bits_read() + 6) / 8 != setup_packet.size() as u64 {
+            if ss.get_total_bits_read().div_ceil(8) != setup_packet.size() as u64 {
                 return Err(ParseError::Message("didn't read exactly setup packet".into()));
             }
             if setup_packet.next_offset() != self.data_offset + self.first_audio_packet_offset as i64 {
                 return Err(ParseError::Message("first audio packet doesn't follow setup packet".into()));
             }
+
+            let (packet, _) = setup.pop_packet().ok_or_else(|| ParseError::Message("empty setup packet".into()))?;
+            if self.full_setup {
+                (*mode_blockflag, *mode_bits) = setup_modes(&packet, self.channels as u8)?;
+            }
+            out.write_all(&packet)?;
+            out.flush_page(false, false)?;
         }
 
         Ok(())
     }
+
+    /// Copies the identification, comment and setup headers stored in the
+    /// data chunk by older Wwise versions (vorb sizes 0x28 and 0x2C).
+    pub fn generate_ogg_header_with_triad<O: BitOggStreamT>(
+        &mut self,
+        os: &mut O,
+        mode_blockflag: &mut Vec<bool>,
+        mode_bits: &mut i32,
+    ) -> Result<()> {
+        let mut offset = self.data_offset + self.setup_packet_offset as i64;
+
+        // copy identification packet
+        let information_packet = Packet8::new(&mut self.infile, offset, self.little_endian)?;
+        if information_packet.granule() != 0 {
+            return Err(ParseError::Message("information packet granule != 0".into()));
+        }
+        let information = self.read_header_packet(&information_packet)?;
+        if information.first() != Some(&1) {
+            return Err(ParseError::Message("wrong type for information packet".into()));
+        }
+        let identification = parse_identification(&information)?;
+        os.write_all(&information)?;
+        os.flush_page(false, false)?;
+        offset = information_packet.next_offset();
+
+        // copy comment packet
+        let comment_packet = Packet8::new(&mut self.infile, offset, self.little_endian)?;
+        if comment_packet.granule() != 0 {
+            return Err(ParseError::Message("comment packet granule != 0".into()));
+        }
+        let comment = self.read_header_packet(&comment_packet)?;
+        if comment.first() != Some(&3) {
+            return Err(ParseError::Message("wrong type for comment packet".into()));
+        }
+        os.write_all(&comment)?;
+        os.flush_page(false, false)?;
+        offset = comment_packet.next_offset();
+
+        // copy setup packet, rewriting the codebooks
+        {
+            let setup_packet = Packet8::new(&mut self.infile, offset, self.little_endian)?;
+            if setup_packet.granule() != 0 {
+                return Err(ParseError::Message("setup packet granule != 0".into()));
+            }
+            self.check_header_packet(&setup_packet)?;
+            self.infile.seek(SeekFrom::Start(setup_packet.offset() as u64))?;
+            let mut ss = BitStream::new(&mut self.infile);
+            let mut setup = BitPacketStream::new();
+
+            let packet_type = BitUint::<8>::read_from(&mut ss)?;
+            if packet_type.total != 5 {
+                return Err(ParseError::Message("wrong type for setup packet".into()));
+            }
+            packet_type.write_to(&mut setup)?;
+            // 'vorbis'
+            for _ in 0..6 {
+                BitUint::<8>::read_from(&mut ss)?.write_to(&mut setup)?;
+            }
+
+            let codebook_count_less1 = BitUint::<8>::read_from(&mut ss)?;
+            codebook_count_less1.write_to(&mut setup)?;
+            let cbl = crate::codebook::CodebookLibrary::new_empty();
+            for _ in 0..=codebook_count_less1.total {
+                cbl.copy(&mut ss, &mut setup)?;
+            }
+            while ss.get_total_bits_read() < setup_packet.size() as u64 * 8 {
+                let bits_left = setup_packet.size() as u64 * 8 - ss.get_total_bits_read();
+                let bits = bits_left.min(32) as u8;
+                setup.write_bits(ss.read_bits(bits)?, bits)?;
+            }
+            setup.flush_page(false, false)?;
+
+            let (packet, _) = setup.pop_packet().ok_or_else(|| ParseError::Message("empty setup packet".into()))?;
+            (*mode_blockflag, *mode_bits) = setup_modes(&packet, identification.channels)?;
+            os.write_all(&packet)?;
+            os.flush_page(false, false)?;
+            offset = setup_packet.next_offset();
+        }
+
+        if offset != self.data_offset + self.first_audio_packet_offset as i64 {
+            return Err(ParseError::Message("first audio packet doesn't follow setup packet".into()));
+        }
+        Ok(())
+    }
+
+    /// Header packets of the triad must fit in the data chunk.
+    fn check_header_packet(&self, packet: &Packet8) -> Result<()> {
+        if packet.size() > self.limits.max_packet_size {
+            return Err(ParseError::Message("header packet larger than max_packet_size".into()));
+        }
+        if packet.next_offset() > self.data_offset + self.data_size {
+            return Err(ParseError::Message("header packet truncated".into()));
+        }
+        Ok(())
+    }
+
+    fn read_header_packet(&mut self, packet: &Packet8) -> Result<Vec<u8>> {
+        self.check_header_packet(packet)?;
+        self.infile.seek(SeekFrom::Start(packet.offset() as u64))?;
+        let mut data = vec![0u8; packet.size() as usize];
+        self.infile.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
+
+/// Where the setup header in a wem takes its codebooks from.
+enum CodebookSource {
+    /// Codebook ids into an external library.
+    Library(CodebookLibrary),
+    /// Codebooks in the header itself, in the standard form with a full setup
+    /// or Wwise's compact one.
+    Inline { full_setup: bool },
+}
+
+impl CodebookSource {
+    fn read<R: Read>(&self, ss: &mut BitStream<R>) -> Result<Codebook> {
+        match self {
+            CodebookSource::Inline { full_setup: true } => {
+                let codebook = Codebook::read(ss)?;
+                if codebook.lookup.as_ref().is_some_and(|lookup| lookup.lookup_type == 2) {
+                    return Err(ParseError::Message("didn't expect lookup type 2".into()));
+                }
+                Ok(codebook)
+            }
+            CodebookSource::Inline { full_setup: false } => Codebook::read_stripped(ss, 0),
+            CodebookSource::Library(cbl) => {
+                let codebook_id = BitUint::<10>::read_from(ss)?;
+                cbl.codebook(codebook_id.total as usize).or_else(|e| {
+                    if codebook_id.total == 0x342 {
+                        let codebook_identifier = BitUint::<14>::read_from(ss)?;
+                        if codebook_identifier.total == 0x1590 {
+                            return Err(ParseError::Message("invalid codebook id 0x342, try --full-setup".into()));
+                        }
+                    }
+                    Err(e)
+                })
+            }
+        }
+    }
+}
+
+/// Block flag of every mode of a standard setup header, and the number of
+/// bits in a mode number.
+fn setup_modes(packet: &[u8], channels: u8) -> Result<(Vec<bool>, i32)> {
+    let mode_blockflag = parse_setup_modes(packet, channels)?;
+    let mode_bits = ilog(mode_blockflag.len() as u32 - 1);
+    Ok((mode_blockflag, mode_bits))
+}
+
+/// Writes `sources` one after another as a chained Ogg Vorbis file.
+/// Every wem becomes its own logical bitstream with a fresh serial number,
+/// its own header triad and granule positions starting again from 0.
+pub fn generate_chained_ogg<R: Read + Seek, W: Write>(
+    sources: &mut [WwiseRiffVorbis<R>],
+    writer: W,
+) -> Result<()> {
+    if sources.is_empty() {
+        return Err(ParseError::Message("no wems to chain".into()));
+    }
+    let mut ogg_stream = BitOggStream::new(writer);
+    for (serial, source) in (1u32..).zip(sources.iter_mut()) {
+        ogg_stream.begin_link(serial)?;
+        source.write_ogg(&mut ogg_stream)?;
+    }
+    Ok(())
 }
@@ -0,0 +1,117 @@
+//! The tokio front-end, against the blocking conversion.
+
+#![cfg(feature = "async")]
+
+mod support;
+
+use std::io::Cursor;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use support::{convert, open, wem_bytes, Layout, Vorb, VorbisSource, Wem};
+use tokio::io::AsyncWrite;
+use wem_converter::errors::ParseError;
+use wem_converter::limits::Limits;
+use wem_converter::options::WemOptions;
+
+async fn open_async(wem: &Wem, layout: &Layout) -> Wem {
+    Wem::from_async_reader(Cursor::new(wem_bytes(wem)), "fixture.wem", &layout.options(&wem.codebooks_name)).await.unwrap()
+}
+
+/// Takes `limit` bytes, then fails every write.
+struct FullDisk {
+    written: Vec<u8>,
+    limit: usize,
+}
+
+impl AsyncWrite for FullDisk {
+    fn poll_write(mut self: Pin<&mut Self>, _: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        let room = self.limit - self.written.len();
+        if room == 0 {
+            return Poll::Ready(Err(std::io::Error::other("disk full")));
+        }
+        let len = buf.len().min(room);
+        self.written.extend_from_slice(&buf[..len]);
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test]
+async fn async_conversion_matches_the_blocking_one() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layouts = [Layout::new(Vorb::Size2A), Layout::new(Vorb::Size28), Layout { big_endian: true, ..Layout::new(Vorb::Size34) }];
+    for layout in layouts {
+        let mut wem = open(&source, &layout, "async");
+        let expected = convert(&mut wem);
+
+        for verify in [false, true] {
+            let mut converted = open_async(&wem, &layout).await;
+            converted.verify = verify;
+            let mut ogg = Vec::new();
+            let converted = converted.generate_ogg_async(&mut ogg).await.unwrap();
+            assert!(ogg == expected, "{} verify {}", layout.tag(), verify);
+            // The wem comes back usable.
+            assert_eq!(converted.sample_count, source.sample_count);
+        }
+    }
+}
+
+#[tokio::test]
+async fn a_failing_writer_stops_the_conversion() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layout = Layout::new(Vorb::Size32);
+    let wem = open(&source, &layout, "async");
+    let mut disk = FullDisk { written: Vec::new(), limit: 5000 };
+    let err = open_async(&wem, &layout).await.generate_ogg_async(&mut disk).await.unwrap_err();
+    assert!(matches!(&err, ParseError::Io(e) if e.to_string() == "disk full"), "{}", err);
+    assert_eq!(disk.written.len(), 5000);
+}
+
+#[tokio::test]
+async fn conversion_errors_come_back() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    let layout = Layout::new(Vorb::Size2A);
+    let wem = open(&source, &layout, "async");
+    let mut broken = open_async(&wem, &layout).await;
+    broken.codebooks_name = "missing.bin".into();
+    let err = broken.generate_ogg_async(Vec::new()).await.unwrap_err();
+    assert_eq!(err.to_string(), "Parse error: File open error: missing.bin");
+}
+
+#[tokio::test]
+async fn input_past_max_allocation_is_not_read() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    let layout = Layout::new(Vorb::Size2A);
+    let wem = open(&source, &layout, "async");
+    let bytes = wem_bytes(&wem);
+
+    let fits = Limits { max_allocation: bytes.len(), ..Limits::default() };
+    let options = WemOptions { limits: fits, ..layout.options(&wem.codebooks_name) };
+    let opened = Wem::from_async_reader(Cursor::new(bytes.clone()), "fixture.wem", &options).await.unwrap();
+    assert_eq!(opened.sample_count, source.sample_count);
+
+    // Trailing bytes count too, and reading stops right after the cap.
+    let mut input = Cursor::new([bytes.clone(), vec![0; 1 << 20]].concat());
+    let err = Wem::from_async_reader(&mut input, "fixture.wem", &options).await.map(|_| ()).unwrap_err();
+    assert_eq!(err.to_string(), "Parse error: wem larger than max_allocation");
+    assert_eq!(input.position(), bytes.len() as u64 + 1);
+}
+
+#[tokio::test]
+async fn limits_apply_to_the_parse() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layout = Layout::new(Vorb::Size2A);
+    let wem = open(&source, &layout, "async");
+    let one = Limits { max_channels: 1, ..Limits::default() };
+    let options = WemOptions { limits: one, ..layout.options(&wem.codebooks_name) };
+    let err = Wem::from_async_reader(Cursor::new(wem_bytes(&wem)), "fixture.wem", &options).await.map(|_| ()).unwrap_err();
+    assert_eq!(err.to_string(), "Parse error: 2 channels, more than max_channels");
+}
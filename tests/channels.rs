@@ -0,0 +1,109 @@
+//! Channel layouts from the fmt chunk and their Vorbis order.
+
+mod support;
+
+use support::{open_wem, wem_bytes, Layout, Vorb, VorbisSource, Wem};
+use wem_converter::channels::{ChannelConfigType, ChannelLayout, Speaker};
+use wem_converter::overrides::Overrides;
+
+/// Opens `layout` built from `source`, with the fmt chunk's subtype replaced if given.
+fn open(source: &VorbisSource, layout: &Layout, subtype: Option<u32>) -> Wem {
+    let wem = support::open(source, layout, "channels");
+    let Some(subtype) = subtype else { return wem };
+    let mut bytes = wem_bytes(&wem);
+    let at = bytes.windows(4).position(|w| w == b"fmt ").unwrap() + 8 + 0x14;
+    let subtype = if layout.big_endian { subtype.to_be_bytes() } else { subtype.to_le_bytes() };
+    bytes[at..at + 4].copy_from_slice(&subtype);
+    open_wem(bytes, layout, &wem.codebooks_name, Overrides::default()).unwrap()
+}
+
+#[test]
+fn fixture_layouts_are_decoded() {
+    let mono = open(&VorbisSource::load("sine_mono.ogg"), &Layout::new(Vorb::Size2A), None);
+    assert_eq!(mono.channel_layout.speakers(), Some(vec![Speaker::FrontCenter]));
+    assert_eq!(mono.channel_layout.config_type, None);
+    assert_eq!(mono.channel_layout.vorbis_order(), Some(vec![0]));
+
+    let layout = Layout { big_endian: true, ..Layout::new(Vorb::InFmt) };
+    let stereo = open(&VorbisSource::load("sine_stereo.ogg"), &layout, None);
+    assert_eq!(stereo.channel_layout.speakers(), Some(vec![Speaker::FrontLeft, Speaker::FrontRight]));
+    assert!(!stereo.channel_layout.reorders());
+    assert_eq!(stereo.channel_layout.to_string(), "FrontLeft FrontRight");
+}
+
+#[test]
+fn channel_configs_are_told_from_masks() {
+    // Stereo, standard config, FL FR: what recent Wwise releases write.
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layout = Layout::new(Vorb::InFmt);
+    let wem = open(&source, &layout, Some(0x3 << 12 | 1 << 8 | 2));
+    assert_eq!(wem.channel_layout.config_type, Some(ChannelConfigType::Standard));
+    assert_eq!(wem.channel_layout.mask, 0x3);
+    assert!(wem.detect_wwise_version().evidence[1].clue.starts_with("channel config"));
+
+    let wem = open(&source, &layout, Some(2 << 8 | 2));
+    assert_eq!(wem.channel_layout.config_type, Some(ChannelConfigType::Ambisonic));
+    assert_eq!(wem.channel_layout.speakers(), None);
+    assert_eq!(wem.channel_layout.vorbis_order(), Some(vec![0, 1]));
+}
+
+#[test]
+fn channel_configs_need_the_0x42_fmt_chunk() {
+    // A config-shaped subtype next to a vorb chunk is still a mask: configs
+    // postdate that layout, and the version clues must not contradict.
+    let layout = Layout::new(Vorb::Size2A);
+    let wem = open(&VorbisSource::load("sine_stereo.ogg"), &layout, Some(0x3 << 12 | 1 << 8 | 2));
+    assert_eq!(wem.channel_layout.config_type, None);
+    assert_eq!(wem.channel_layout.mask, 0x3102);
+    let estimate = wem.detect_wwise_version();
+    assert!(estimate.evidence.iter().any(|evidence| evidence.clue == "channel mask 0x3102 in the fmt chunk"));
+    assert_eq!(estimate.range.unwrap().to_string(), "2011.1 to 2012.1");
+}
+
+#[test]
+fn surround_layouts_map_to_vorbis_order() {
+    let cases = [
+        // 3.0: FL FR C
+        (ChannelLayout::from_fmt(3, 0x7, true), vec![0, 2, 1]),
+        // quad: FL FR BL BR
+        (ChannelLayout::from_fmt(4, 0x33, true), vec![0, 1, 2, 3]),
+        // 5.0: FL FR C BL BR
+        (ChannelLayout::from_fmt(5, 0x37, true), vec![0, 2, 1, 3, 4]),
+        // 5.1: FL FR C BL BR LFE
+        (ChannelLayout::from_fmt(6, 0x3F, true), vec![0, 2, 1, 3, 4, 5]),
+        // 5.1 with side speakers, as a Wwise channel config
+        (ChannelLayout::from_fmt(6, 0x60F << 12 | 1 << 8 | 6, true), vec![0, 2, 1, 3, 4, 5]),
+        // 6.1: FL FR C BC SL SR LFE
+        (ChannelLayout::from_fmt(7, 0x70F, true), vec![0, 2, 1, 4, 5, 3, 6]),
+        // 7.1: FL FR C BL BR SL SR LFE
+        (ChannelLayout::from_fmt(8, 0x63F, true), vec![0, 2, 1, 5, 6, 3, 4, 7]),
+    ];
+    for (layout, order) in cases {
+        assert_eq!(layout.vorbis_order(), Some(order), "{}", layout);
+    }
+    let five_one = ChannelLayout::from_fmt(6, 0x3F, true);
+    assert_eq!(
+        five_one.speakers().unwrap().last(),
+        Some(&Speaker::LowFrequency),
+        "Wwise keeps the LFE channel last"
+    );
+
+    // No Vorbis order for unknown or unusual speakers.
+    assert_eq!(ChannelLayout::from_fmt(6, 0, false).vorbis_order(), None);
+    assert_eq!(ChannelLayout::from_fmt(6, 0x7, true).vorbis_order(), None);
+    assert_eq!(ChannelLayout::from_fmt(4, 0x800 | 0x7, true).vorbis_order(), None);
+}
+
+#[test]
+fn decoded_frames_are_reordered() {
+    let five_one = ChannelLayout::from_fmt(6, 0x3F, true);
+    // Two frames of FL FR C BL BR LFE, and a partial one.
+    let mut samples = [10, 11, 12, 13, 14, 15, 20, 21, 22, 23, 24, 25, 30, 31];
+    five_one.to_vorbis_order(&mut samples);
+    assert_eq!(samples, [10, 12, 11, 13, 14, 15, 20, 22, 21, 23, 24, 25, 30, 31]);
+
+    let unknown = ChannelLayout::from_fmt(6, 0, false);
+    let mut samples = [1, 2, 3, 4, 5, 6];
+    unknown.to_vorbis_order(&mut samples);
+    assert_eq!(samples, [1, 2, 3, 4, 5, 6]);
+}
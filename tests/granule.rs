@@ -0,0 +1,98 @@
+//! Granules derived for wems whose packet headers carry none.
+
+mod support;
+
+use support::{open, wem_bytes, Layout, Vorb, VorbisSource};
+use wem_converter::ogg::OggPacketReader;
+use wem_converter::stream::WemStream;
+
+/// Granules of the audio packets of `ogg`.
+fn granules(ogg: &[u8]) -> Vec<Option<u64>> {
+    let mut reader = OggPacketReader::new(ogg);
+    let mut granules = Vec::new();
+    while let Some(packet) = reader.next_packet().unwrap() {
+        granules.push(packet.granule);
+    }
+    granules.split_off(3)
+}
+
+#[test]
+fn derived_granules_match_the_source_without_verify() {
+    for name in ["sine_mono.ogg", "sine_stereo.ogg"] {
+        let source = VorbisSource::load(name);
+        let expected: Vec<Option<u64>> = source.granules().iter().map(|&granule| Some(granule as u64)).collect();
+        for vorb in [Vorb::InFmt, Vorb::Size2A] {
+            for mod_packets in [false, true] {
+                let layout = Layout { mod_packets, ..Layout::new(vorb) };
+                let mut wem = open(&source, &layout, "granule");
+                assert!(wem.no_granule && !wem.verify);
+                wem.derive_granules = true;
+                let mut ogg = Vec::new();
+                wem.generate_ogg_to(&mut ogg).unwrap();
+                assert_eq!(granules(&ogg), expected, "{} {}", name, layout.tag());
+            }
+        }
+    }
+}
+
+#[test]
+fn only_the_last_granule_is_cut_to_the_sample_count() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let mut wem = open(&source, &Layout::new(Vorb::Size2A), "granule");
+    wem.derive_granules = true;
+    let mut ogg = Vec::new();
+    wem.generate_ogg_to(&mut ogg).unwrap();
+    let granules = granules(&ogg);
+
+    // The source ends partway into its last block, so the last packet's
+    // whole block would overshoot the sample count.
+    let blocksizes = [1u64 << source.blocksize_0_pow, 1 << source.blocksize_1_pow];
+    let last = source.audio.len() - 1;
+    let full = granules[last - 1].unwrap()
+        + blocksizes[source.blockflag(&source.audio[last - 1]) as usize] / 4
+        + blocksizes[source.blockflag(&source.audio[last]) as usize] / 4;
+    assert!(full > source.sample_count as u64);
+    assert_eq!(granules[last], Some(source.sample_count as u64));
+}
+
+#[test]
+fn granules_stay_zero_unless_derivation_is_asked_for() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layout = Layout::new(Vorb::Size2A);
+    let mut wem = open(&source, &layout, "granule");
+    assert!(!wem.derive_granules);
+    let mut ogg = Vec::new();
+    wem.generate_ogg_to(&mut ogg).unwrap();
+    assert!(granules(&ogg).iter().all(|&granule| granule.is_none() || granule == Some(0)));
+
+    // Nothing else about the stream depends on the granules.
+    wem.derive_granules = true;
+    let mut derived = Vec::new();
+    wem.generate_ogg_to(&mut derived).unwrap();
+    let packets = |ogg: &[u8]| {
+        let mut reader = OggPacketReader::new(ogg);
+        std::iter::from_fn(|| reader.next_packet().unwrap().map(|packet| packet.data)).collect::<Vec<_>>()
+    };
+    assert_eq!(packets(&ogg), packets(&derived));
+
+    // The streaming converter writes the same bytes either way.
+    let bytes = wem_bytes(&wem);
+    for (derive_granules, expected) in [(false, &ogg), (true, &derived)] {
+        let mut stream = WemStream::new(&bytes[..], &layout.options(&wem.codebooks_name)).unwrap();
+        stream.set_derive_granules(derive_granules);
+        let mut streamed = Vec::new();
+        stream.generate_ogg_to(&mut streamed).unwrap();
+        assert_eq!(&streamed, expected);
+    }
+}
+
+#[test]
+fn verify_accepts_either_setting() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    for derive_granules in [false, true] {
+        let mut wem = open(&source, &Layout::new(Vorb::InFmt), "granule");
+        wem.verify = true;
+        wem.derive_granules = derive_granules;
+        wem.generate_ogg_to(std::io::sink()).unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+//! Resource limits, through every way of opening a wem.
+
+mod support;
+
+use std::io::Cursor;
+
+use support::{open, wem_bytes, Layout, Vorb, VorbisSource, Wem};
+use wem_converter::errors::Result;
+use wem_converter::limits::Limits;
+use wem_converter::options::WemOptions;
+use wem_converter::recovery::RecoveryMode;
+use wem_converter::stream::WemStream;
+use wem_converter::wwriff::WwiseRiffVorbis;
+
+/// Opens `bytes` with `limits`, strictly and for recovery.
+fn open_all(bytes: &[u8], layout: &Layout, codebooks: &str, limits: Limits) -> Vec<(&'static str, Result<Wem>)> {
+    let options = WemOptions { limits, ..layout.options(codebooks) };
+    let recovering = WemOptions { recovering: true, ..options.clone() };
+    vec![
+        ("strict", WwiseRiffVorbis::from_reader(Cursor::new(bytes.to_vec()), "", &options)),
+        ("recovering", WwiseRiffVorbis::from_reader(Cursor::new(bytes.to_vec()), "", &recovering)),
+    ]
+}
+
+/// `wem` with the channel count in its fmt chunk replaced.
+fn with_channels(wem: &Wem, channels: u16) -> Vec<u8> {
+    let mut bytes = wem_bytes(wem);
+    let fmt = bytes.windows(4).position(|w| w == b"fmt ").unwrap() + 8;
+    bytes[fmt + 2..fmt + 4].copy_from_slice(&channels.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn channel_counts_past_the_limit_are_rejected() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layout = Layout::new(Vorb::Size2A);
+    let wem = open(&source, &layout, "limits");
+
+    let one = Limits { max_channels: 1, ..Limits::default() };
+    for (name, opened) in open_all(&wem_bytes(&wem), &layout, &wem.codebooks_name, one) {
+        let err = opened.map(|_| ()).unwrap_err();
+        assert_eq!(err.to_string(), "Parse error: 2 channels, more than max_channels", "{}", name);
+    }
+    for (name, opened) in open_all(&wem_bytes(&wem), &layout, &wem.codebooks_name, Limits::default()) {
+        assert_eq!(opened.unwrap().channels, 2, "{}", name);
+    }
+}
+
+#[test]
+fn no_limit_lets_more_than_255_channels_through() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layout = Layout::new(Vorb::Size34);
+    let wem = open(&source, &layout, "limits");
+    for channels in [256, 300, u16::MAX] {
+        let bytes = with_channels(&wem, channels);
+        for (name, opened) in open_all(&bytes, &layout, &wem.codebooks_name, Limits::unlimited()) {
+            let err = opened.map(|_| ()).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("Parse error: {} channels, more than max_channels", channels),
+                "{}",
+                name
+            );
+        }
+        let options = WemOptions { limits: Limits::unlimited(), ..layout.options(&wem.codebooks_name) };
+        let streamed = WemStream::new(&bytes[..], &options);
+        let err = streamed.map(|_| ()).unwrap_err();
+        assert_eq!(err.to_string(), format!("Parse error: {} channels, more than max_channels", channels));
+    }
+}
+
+#[test]
+fn packets_past_the_size_limit_are_rejected() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layout = Layout::new(Vorb::Size32);
+    let mut wem = open(&source, &layout, "limits");
+    let packets: Vec<_> = wem.packets().map(|packet| packet.unwrap()).collect();
+    let (largest, size) = packets.iter().map(|packet| (packet.index, packet.size)).max_by_key(|&(_, size)| size).unwrap();
+    let bytes = wem_bytes(&wem);
+
+    let at_limit = Limits { max_packet_size: size, ..Limits::default() };
+    for (name, opened) in open_all(&bytes, &layout, &wem.codebooks_name, at_limit) {
+        opened.unwrap().generate_ogg_to(std::io::sink()).unwrap_or_else(|e| panic!("{}: {}", name, e));
+    }
+
+    let below = Limits { max_packet_size: size - 1, ..Limits::default() };
+    let message = format!("Parse error: packet {} is {} bytes, more than max_packet_size", largest, size);
+    for (name, opened) in open_all(&bytes, &layout, &wem.codebooks_name, below) {
+        let mut opened = opened.unwrap();
+        if name == "recovering" {
+            // Recovery skips the packet and reports it.
+            let report = opened.generate_ogg_recovering(std::io::sink(), RecoveryMode::Skip).unwrap();
+            assert_eq!(report.lost[0].offset, packets[largest as usize].header_offset);
+            assert_eq!(report.lost[0].reason, message);
+        } else {
+            let err = opened.generate_ogg_to(std::io::sink()).unwrap_err();
+            assert_eq!(err.to_string(), message, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Memory-mapped input, against conversions from memory.
+
+#![cfg(feature = "mmap")]
+
+mod support;
+
+use std::path::Path;
+
+use support::{convert, open, wem_bytes, Layout, Vorb, VorbisSource};
+use wem_converter::limits::Limits;
+use wem_converter::options::WemOptions;
+use wem_converter::wwriff::WwiseRiffVorbis;
+
+/// Writes `bytes` to a scratch file named `name` and returns its path.
+fn write_wem(name: &str, bytes: &[u8]) -> String {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::write(&path, bytes).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn mapped_wems_convert_like_in_memory_ones() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    for vorb in Vorb::ALL {
+        let layout = Layout { big_endian: vorb == Vorb::Size32, ..Layout::new(vorb) };
+        let mut wem = open(&source, &layout, "mmap");
+        let expected = convert(&mut wem);
+        let path = write_wem(&format!("mmap-{}.wem", layout.tag()), &wem_bytes(&wem));
+
+        let mut mapped = WwiseRiffVorbis::new_mmap(&path, &layout.options(&wem.codebooks_name))
+        .unwrap();
+        assert!(convert(&mut mapped) == expected, "{}", layout.tag());
+
+        let packets: Vec<_> = mapped.packets().map(|packet| packet.unwrap()).collect();
+        assert_eq!(packets.len(), source.audio.len(), "{}", layout.tag());
+        let mut ranged = Vec::new();
+        mapped.generate_ogg_range(1000, 5000, &mut ranged).unwrap();
+        let mut from_memory = Vec::new();
+        wem.generate_ogg_range(1000, 5000, &mut from_memory).unwrap();
+        assert!(ranged == from_memory, "{}", layout.tag());
+    }
+}
+
+#[test]
+fn truncated_mapped_wems_fail() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    let layout = Layout::new(Vorb::Size2A);
+    let wem = open(&source, &layout, "mmap");
+    let bytes = wem_bytes(&wem);
+    let path = write_wem("mmap-truncated.wem", &bytes[..bytes.len() - 10]);
+    let converted = WwiseRiffVorbis::new_mmap(&path, &layout.options(&wem.codebooks_name))
+        .and_then(|mut wem| wem.generate_ogg_to(std::io::sink()));
+    assert!(converted.is_err());
+
+    let missing = WwiseRiffVorbis::new_mmap("missing.wem", &layout.options(""));
+    assert_eq!(missing.unwrap_err().to_string(), "File open error: missing.wem");
+}
+
+#[test]
+fn limits_apply_to_mapped_wems() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layout = Layout::new(Vorb::Size32);
+    let mut wem = open(&source, &layout, "mmap");
+    let path = write_wem("mmap-limits.wem", &wem_bytes(&wem));
+    let codebooks = wem.codebooks_name.clone();
+    let options = |limits| WemOptions { limits, ..layout.options(&codebooks) };
+
+    let one = Limits { max_channels: 1, ..Limits::default() };
+    let err = WwiseRiffVorbis::new_mmap(&path, &options(one)).map(|_| ()).unwrap_err();
+    assert_eq!(err.to_string(), "Parse error: 2 channels, more than max_channels");
+
+    // Packets read in place are held to the size limit as well.
+    let packets: Vec<_> = wem.packets().map(|packet| packet.unwrap()).collect();
+    let (largest, size) = packets.iter().map(|packet| (packet.index, packet.size)).max_by_key(|&(_, size)| size).unwrap();
+    let below = Limits { max_packet_size: size - 1, ..Limits::default() };
+    let err = WwiseRiffVorbis::new_mmap(&path, &options(below)).unwrap().generate_ogg_to(std::io::sink()).unwrap_err();
+    assert_eq!(err.to_string(), format!("Parse error: packet {} is {} bytes, more than max_packet_size", largest, size));
+}
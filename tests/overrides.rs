@@ -0,0 +1,101 @@
+//! Conversion of wems whose header is wrong, with the right values forced.
+
+mod support;
+
+use support::{build_wem, convert, insert_into_vorb, open_wem, vorb_offset, BuiltWem, Layout, Vorb, VorbisSource};
+use wem_converter::overrides::Overrides;
+use wem_converter::vorb::VorbLayout;
+
+struct Fixture {
+    source: VorbisSource,
+    layout: Layout,
+    built: BuiltWem,
+    codebooks: String,
+}
+
+impl Fixture {
+    fn new(vorb: Vorb) -> Self {
+        let source = VorbisSource::load("sine_stereo.ogg");
+        let layout = Layout::new(vorb);
+        let built = build_wem(&source, &layout);
+        let codebooks = built.write_codebooks(&format!("overrides-{}", layout.tag()));
+        Fixture { source, layout, built, codebooks }
+    }
+
+    fn expected(&self) -> Vec<u8> {
+        convert(&mut open_wem(self.built.wem.clone(), &self.layout, &self.codebooks, Overrides::default()).unwrap())
+    }
+}
+
+#[test]
+fn default_overrides_change_nothing() {
+    let fixture = Fixture::new(Vorb::Size34);
+    let wem = open_wem(fixture.built.wem.clone(), &fixture.layout, &fixture.codebooks, Overrides::default()).unwrap();
+    assert_eq!(wem.vorb_layout, VorbLayout::known(0x34));
+    assert_eq!(wem.overrides, Overrides::default());
+}
+
+#[test]
+fn wrong_blocksizes_and_offsets_are_overridden() {
+    let fixture = Fixture::new(Vorb::Size2A);
+    let vorb = vorb_offset(&fixture.built.wem);
+    let mut wem = fixture.built.wem.clone();
+    wem[vorb + 0x10..vorb + 0x18].fill(0xFF);
+    wem[vorb + 0x28..vorb + 0x2A].copy_from_slice(&[4, 3]);
+
+    let err = open_wem(wem.clone(), &fixture.layout, &fixture.codebooks, Overrides::default()).unwrap_err();
+    assert_eq!(err.to_string(), "Parse error: invalid blocksizes");
+
+    let expected = fixture.expected();
+    let reference = open_wem(fixture.built.wem.clone(), &fixture.layout, &fixture.codebooks, Overrides::default()).unwrap();
+    let overrides = Overrides {
+        setup_packet_offset: Some(reference.setup_packet_offset),
+        first_audio_packet_offset: Some(reference.first_audio_packet_offset),
+        blocksizes: Some((fixture.source.blocksize_0_pow, fixture.source.blocksize_1_pow)),
+        ..Overrides::default()
+    };
+    assert!(convert(&mut open_wem(wem, &fixture.layout, &fixture.codebooks, overrides).unwrap()) == expected);
+}
+
+#[test]
+fn packet_format_is_overridden() {
+    // A triad wem whose vorb chunk claims the 0x34 layout, with 6 byte
+    // packet headers and no blocksizes.
+    let fixture = Fixture::new(Vorb::Size2C);
+    let wem = insert_into_vorb(&fixture.built.wem, false, 0x2C, &[0; 8]);
+    assert!(open_wem(wem.clone(), &fixture.layout, &fixture.codebooks, Overrides::default()).is_err());
+
+    let overrides = Overrides {
+        old_packet_headers: Some(true),
+        header_triad_present: Some(true),
+        ..Overrides::default()
+    };
+    assert!(convert(&mut open_wem(wem, &fixture.layout, &fixture.codebooks, overrides).unwrap()) == fixture.expected());
+}
+
+#[test]
+fn vorb_layout_is_overridden() {
+    // Setup and first audio packet offsets moved apart, which the
+    // detection doesn't look for.
+    let fixture = Fixture::new(Vorb::Size32);
+    let mut wem = insert_into_vorb(&fixture.built.wem, false, 0x2C, &[0xFF; 0x16]);
+    let vorb = vorb_offset(&wem);
+    wem.copy_within(vorb + 0x18..vorb + 0x1C, vorb + 0x30);
+    wem.copy_within(vorb + 0x1C..vorb + 0x20, vorb + 0x38);
+    wem[vorb + 0x18..vorb + 0x20].fill(0xFF);
+    let err = open_wem(wem.clone(), &fixture.layout, &fixture.codebooks, Overrides::default()).unwrap_err();
+    assert_eq!(err.to_string(), "Parse error: unrecognized vorb layout");
+
+    let layout = VorbLayout {
+        size: 0x48,
+        setup_packet_offset: 0x30,
+        first_audio_packet_offset: 0x38,
+        uid: Some(0x42),
+        blocksizes: Some(0x46),
+        ..VorbLayout::known(0x32).unwrap()
+    };
+    let overrides = Overrides { vorb_layout: Some(layout), ..Overrides::default() };
+    let opened = open_wem(wem.clone(), &fixture.layout, &fixture.codebooks, overrides).unwrap();
+    assert_eq!(opened.vorb_layout, Some(layout));
+    assert!(convert(&mut open_wem(wem, &fixture.layout, &fixture.codebooks, overrides).unwrap()) == fixture.expected());
+}
@@ -0,0 +1,105 @@
+//! Progress reports and cancellation during conversion.
+
+mod support;
+
+use support::{convert, open, Layout, Vorb, VorbisSource};
+use wem_converter::errors::ParseError;
+use wem_converter::progress::{CancellationToken, Progress, ProgressObserver};
+
+#[test]
+fn fraction_runs_from_zero_to_one() {
+    let at = |bytes| Progress { packets: 0, bytes, data_size: 400 };
+    assert_eq!(at(0).fraction(), 0.0);
+    assert_eq!(at(100).fraction(), 0.25);
+    assert_eq!(at(400).fraction(), 1.0);
+    // An empty data chunk is done before it starts.
+    assert_eq!(Progress { packets: 0, bytes: 0, data_size: 0 }.fraction(), 1.0);
+}
+
+#[test]
+fn closures_observe_progress() {
+    let mut seen = Vec::new();
+    let mut observer = |progress: Progress| seen.push(progress.packets);
+    for packets in 1..=3 {
+        observer.on_progress(Progress { packets, bytes: packets * 10, data_size: 30 });
+    }
+    assert_eq!(seen, [1, 2, 3]);
+}
+
+#[test]
+fn clones_share_the_cancel_flag() {
+    let cancel = CancellationToken::new();
+    assert!(!cancel.is_cancelled());
+    let other = cancel.clone();
+    std::thread::spawn(move || other.cancel()).join().unwrap();
+    assert!(cancel.is_cancelled());
+}
+
+#[test]
+fn progress_is_reported_after_every_packet() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    for vorb in [Vorb::Size2A, Vorb::Size28] {
+        let layout = Layout::new(vorb);
+        let mut wem = open(&source, &layout, "progress");
+        let mut reports: Vec<Progress> = Vec::new();
+        let mut observer = |progress| reports.push(progress);
+        wem.generate_ogg_observed(Vec::new(), Some(&mut observer), None).unwrap();
+
+        let packets: Vec<u64> = reports.iter().map(|progress| progress.packets).collect();
+        assert_eq!(packets, (1..=source.audio.len() as u64).collect::<Vec<_>>(), "{}", layout.tag());
+        assert!(reports.windows(2).all(|pair| pair[0].bytes < pair[1].bytes), "{}", layout.tag());
+        let last = reports.last().unwrap();
+        assert_eq!(last.bytes, wem.data_size as u64);
+        assert_eq!(last.fraction(), 1.0);
+    }
+}
+
+#[test]
+fn cancelling_mid_stream_stops_with_cancelled() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layout = Layout::new(Vorb::Size34);
+    let expected = convert(&mut open(&source, &layout, "progress"));
+
+    let mut wem = open(&source, &layout, "progress");
+    let cancel = CancellationToken::new();
+    let mut seen = 0;
+    let mut observer = |progress: Progress| {
+        seen = progress.packets;
+        if progress.packets == 10 {
+            cancel.cancel();
+        }
+    };
+    let mut ogg = Vec::new();
+    let err = wem.generate_ogg_observed(&mut ogg, Some(&mut observer), Some(&cancel)).unwrap_err();
+    assert!(matches!(err, ParseError::Cancelled), "{}", err);
+    assert_eq!(seen, 10);
+    // What was written is the start of the full stream.
+    assert!(!ogg.is_empty() && ogg.len() < expected.len());
+    assert!(expected.starts_with(&ogg));
+
+    // Verified conversions write nothing until the end.
+    let mut wem = open(&source, &layout, "progress");
+    wem.verify = true;
+    let cancel = CancellationToken::new();
+    let mut observer = |progress: Progress| {
+        if progress.packets == 10 {
+            cancel.cancel();
+        }
+    };
+    let mut ogg = Vec::new();
+    let err = wem.generate_ogg_observed(&mut ogg, Some(&mut observer), Some(&cancel)).unwrap_err();
+    assert!(matches!(err, ParseError::Cancelled), "{}", err);
+    assert!(ogg.is_empty());
+}
+
+#[test]
+fn cancelling_from_another_thread() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    let mut wem = open(&source, &Layout::new(Vorb::Size2A), "progress");
+    let cancel = CancellationToken::new();
+    let other = cancel.clone();
+    std::thread::spawn(move || other.cancel()).join().unwrap();
+    assert!(cancel.is_cancelled());
+    let err = wem.generate_ogg_observed(Vec::new(), None, Some(&cancel)).unwrap_err();
+    assert!(matches!(err, ParseError::Cancelled), "{}", err);
+}
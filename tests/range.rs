@@ -0,0 +1,119 @@
+//! Extraction of sample ranges into trimmed Ogg streams.
+
+mod support;
+
+use support::{open, Layout, Vorb, VorbisSource};
+use wem_converter::ogg::{validate_ogg, OggPacketReader, OggPageReader};
+
+/// Audio packets of `ogg` and the granules of the pages they end.
+fn audio(ogg: &[u8]) -> (Vec<Vec<u8>>, Vec<Option<u64>>) {
+    let mut reader = OggPacketReader::new(ogg);
+    let (mut packets, mut granules) = (Vec::new(), Vec::new());
+    while let Some(packet) = reader.next_packet().unwrap() {
+        packets.push(packet.data);
+        granules.push(packet.granule);
+    }
+    (packets.split_off(3), granules.split_off(3))
+}
+
+#[test]
+fn ranges_hold_the_packets_that_cover_them() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let granules = source.granules();
+    let total = source.sample_count as u64;
+    let ranges = [(0, total), (0, 1), (1000, 5000), (4095, 4096), (9000, total), (total - 1, total)];
+    for vorb in [Vorb::InFmt, Vorb::Size32, Vorb::Size2C] {
+        for seek_table in [false, true] {
+            let layout = Layout { seek_table, mod_packets: false, ..Layout::new(vorb) };
+            let mut wem = open(&source, &layout, "range");
+            for (start, end) in ranges {
+                let message = format!("{} {}..{}", layout.tag(), start, end);
+                let mut ogg = Vec::new();
+                let written = wem
+                    .generate_ogg_range(start, end, &mut ogg)
+                    .unwrap_or_else(|e| panic!("{}: {}", message, e));
+                let report = validate_ogg(&ogg[..]).unwrap();
+                assert!(report.issues.is_empty(), "{}: {:?}", message, report.issues);
+
+                // From the packet before the one holding `start` to the one
+                // holding `end - 1`. A range inside one packet gets the next
+                // packet too and ends with the first, unless that packet is
+                // the last, where the range starts with it instead.
+                let first = granules.iter().position(|&g| g as u64 > start).unwrap();
+                let mut last = granules.iter().position(|&g| g as u64 > end - 1).unwrap();
+                let (mut start, mut end) = (start, end);
+                if last == first && start > granules[first - 1] as u64 {
+                    if last + 1 < granules.len() {
+                        last += 1;
+                        end = end.max(granules[first] as u64);
+                    } else {
+                        start = granules[first - 1] as u64;
+                    }
+                }
+                assert_eq!(written, start..end, "{}", message);
+                let (packets, page_granules) = audio(&ogg);
+                assert!(packets == source.audio[first - 1..=last], "{}", message);
+
+                assert_eq!(page_granules[0], None, "{}", message);
+                assert_eq!(*page_granules.last().unwrap(), Some(end - start), "{}", message);
+                if packets.len() > 2 {
+                    assert_eq!(page_granules[1], Some(granules[first] as u64 - start), "{}", message);
+                }
+                for i in 2..packets.len() - 1 {
+                    assert_eq!(page_granules[i], Some(granules[first - 1 + i] as u64 - start), "{}", message);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn mod_packets_are_rebuilt_for_ranges() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    for vorb in [Vorb::InFmt, Vorb::Size34] {
+        let layout = Layout::new(vorb);
+        let mut wem = open(&source, &layout, "range");
+        let mut whole = Vec::new();
+        wem.generate_ogg_to(&mut whole).unwrap();
+        let mut ogg = Vec::new();
+        wem.generate_ogg_range(2000, 6000, &mut ogg).unwrap();
+        assert!(validate_ogg(&ogg[..]).unwrap().issues.is_empty());
+
+        // Same packets as a full conversion, apart from the window flags,
+        // in the first byte, of the first packet, whose previous packet
+        // isn't known, and of the last, which has no next one.
+        let (whole, _) = audio(&whole);
+        let (packets, _) = audio(&ogg);
+        let first = whole.iter().position(|packet| packet[1..] == packets[0][1..]).unwrap();
+        let expected = &whole[first..first + packets.len()];
+        let n = packets.len() - 1;
+        assert!(packets[1..n] == expected[1..n], "{}", layout.tag());
+        assert!(packets[n][1..] == expected[n][1..], "{}", layout.tag());
+    }
+}
+
+#[test]
+fn first_page_holds_two_packets() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    let mut wem = open(&source, &Layout::new(Vorb::Size2A), "range");
+    let mut ogg = Vec::new();
+    wem.generate_ogg_range(3000, 4000, &mut ogg).unwrap();
+    let mut pages = OggPageReader::new(&ogg[..]);
+    let mut audio_pages = Vec::new();
+    while let Some(page) = pages.next_page().unwrap() {
+        audio_pages.push(page);
+    }
+    let audio_pages = audio_pages.split_off(3);
+    assert_eq!(audio_pages[0].completed_packets(), 2);
+    assert!(audio_pages.last().unwrap().eos());
+}
+
+#[test]
+fn bad_ranges_are_rejected() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    let mut wem = open(&source, &Layout::new(Vorb::Size2A), "range");
+    let total = source.sample_count as u64;
+    for (start, end) in [(10, 10), (20, 10), (0, total + 1)] {
+        assert!(wem.generate_ogg_range(start, end, Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+//! Recovery of truncated and corrupted wems built by the fixture generator.
+
+mod support;
+
+use std::cell::Cell;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::rc::Rc;
+
+use support::{build_wem, Layout, Vorb, VorbisSource, Wem};
+use wem_converter::ogg::{validate_ogg, OggPacketReader};
+use wem_converter::options::WemOptions;
+use wem_converter::recovery::{RecoveryMode, RecoveryReport};
+use wem_converter::wwriff::{WwisePacket, WwiseRiffVorbis};
+
+struct Fixture {
+    source: VorbisSource,
+    layout: Layout,
+    wem: Vec<u8>,
+    codebooks: String,
+}
+
+impl Fixture {
+    fn new(vorb: Vorb, mod_packets: bool) -> Self {
+        let source = VorbisSource::load("sine_stereo.ogg");
+        let layout = Layout { mod_packets, ..Layout::new(vorb) };
+        let built = build_wem(&source, &layout);
+        let codebooks = built.write_codebooks(&format!("recovery-{}", layout.tag()));
+        Fixture { source, layout, wem: built.wem, codebooks }
+    }
+
+    fn open(&self, wem: Vec<u8>) -> Wem {
+        let options = WemOptions { recovering: true, ..self.layout.options(&self.codebooks) };
+        WwiseRiffVorbis::from_reader(Cursor::new(wem), "fixture.wem", &options).unwrap()
+    }
+
+    fn packets(&self) -> Vec<WwisePacket> {
+        self.open(self.wem.clone()).packets().map(|p| p.unwrap()).collect()
+    }
+
+    /// Converts `wem` and checks that the result is a well-formed stream.
+    fn recover(&self, wem: Vec<u8>, mode: RecoveryMode) -> (RecoveryReport, Vec<u8>) {
+        let mut ogg = Vec::new();
+        let report = self.open(wem).generate_ogg_recovering(&mut ogg, mode).unwrap();
+        let validation = validate_ogg(&ogg[..]).unwrap();
+        assert!(validation.issues.is_empty(), "{:?}", validation.issues);
+        (report, ogg)
+    }
+}
+
+/// A reader that counts the bytes read through it.
+struct Counting {
+    inner: Cursor<Vec<u8>>,
+    read: Rc<Cell<u64>>,
+}
+
+impl Read for Counting {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.read.set(self.read.get() + len as u64);
+        Ok(len)
+    }
+}
+
+impl Seek for Counting {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+fn audio_packets(ogg: &[u8]) -> Vec<Vec<u8>> {
+    let mut reader = OggPacketReader::new(ogg);
+    let mut packets = Vec::new();
+    while let Some(packet) = reader.next_packet().unwrap() {
+        packets.push(packet.data);
+    }
+    packets.split_off(3)
+}
+
+fn audio_granules(ogg: &[u8]) -> Vec<u64> {
+    let mut reader = OggPacketReader::new(ogg);
+    let mut granules = Vec::new();
+    while let Some(packet) = reader.next_packet().unwrap() {
+        granules.push(packet.granule.unwrap());
+    }
+    granules.split_off(3)
+}
+
+#[test]
+fn intact_wem_is_converted_completely() {
+    let fixture = Fixture::new(Vorb::Size34, true);
+    let (report, ogg) = fixture.recover(fixture.wem.clone(), RecoveryMode::Stop);
+    assert!(report.is_complete());
+    assert_eq!(report.packets, fixture.source.audio.len() as u64);
+    assert_eq!(report.samples, report.expected_samples);
+
+    let mut expected = Vec::new();
+    fixture.open(fixture.wem.clone()).generate_ogg_to(&mut expected).unwrap();
+    assert!(ogg == expected);
+}
+
+#[test]
+fn truncated_wem_keeps_complete_packets() {
+    for vorb in [Vorb::Size2A, Vorb::Size34, Vorb::Size2C] {
+        let fixture = Fixture::new(vorb, !vorb.header_triad());
+        let packets = fixture.packets();
+        let cut = &packets[30];
+        let len = cut.payload_offset as usize + cut.size as usize / 2;
+        let (report, _) = fixture.recover(fixture.wem[..len].to_vec(), RecoveryMode::Stop);
+
+        assert_eq!(report.packets, 30, "{}", fixture.layout.tag());
+        assert_eq!(report.samples, fixture.source.granules()[29] as u64, "{}", fixture.layout.tag());
+        assert_eq!(report.lost.len(), 2, "{:?}", report.lost);
+        assert_eq!(report.lost[0].offset, cut.header_offset);
+        assert_eq!(report.lost[1].reason, "file truncated");
+        assert_eq!(report.lost[1].offset + report.lost[1].len, fixture.wem.len() as u64);
+    }
+}
+
+#[test]
+fn corrupt_packet_header_is_skipped_or_ends_the_stream() {
+    let fixture = Fixture::new(Vorb::Size32, true);
+    let packets = fixture.packets();
+    let bad = &packets[20];
+    let mut wem = fixture.wem.clone();
+    // A size running past the end of the data chunk.
+    wem[bad.header_offset as usize..bad.header_offset as usize + 2].copy_from_slice(&[0xFF, 0xFF]);
+
+    let (stopped, _) = fixture.recover(wem.clone(), RecoveryMode::Stop);
+    assert_eq!(stopped.packets, 20);
+    assert_eq!(stopped.lost.len(), 1);
+    assert_eq!(stopped.lost[0].offset, bad.header_offset);
+
+    let (skipped, ogg) = fixture.recover(wem, RecoveryMode::Skip);
+    assert_eq!(skipped.packets, packets.len() as u64 - 1);
+    assert_eq!(skipped.lost.len(), 1);
+    assert_eq!(skipped.lost[0].offset, bad.header_offset);
+    assert_eq!(skipped.lost[0].len, bad.next_offset() - bad.header_offset);
+    assert_eq!(skipped.samples, skipped.expected_samples);
+    assert_eq!(audio_packets(&ogg).len(), packets.len() - 1);
+}
+
+#[test]
+fn packet_with_undefined_mode_is_skipped() {
+    let fixture = Fixture::new(Vorb::Size34, false);
+    let packets = fixture.packets();
+    let bad = &packets[10];
+    let mut wem = fixture.wem.clone();
+    // Standard packets start with a 0 bit for audio.
+    wem[bad.payload_offset as usize] |= 1;
+
+    let (report, ogg) = fixture.recover(wem, RecoveryMode::Skip);
+    assert_eq!(report.packets, packets.len() as u64 - 1);
+    assert_eq!(report.lost.len(), 1);
+    assert_eq!(report.lost[0].offset, bad.header_offset);
+    assert_eq!(report.lost[0].len, bad.next_offset() - bad.header_offset);
+
+    let audio = audio_packets(&ogg);
+    assert_eq!(audio[9], fixture.source.audio[9]);
+    assert_eq!(audio[10], fixture.source.audio[11]);
+}
+
+#[test]
+fn skipped_packets_still_count_towards_derived_granules() {
+    let fixture = Fixture::new(Vorb::Size2A, false);
+    let packets = fixture.packets();
+    let bad = &packets[10];
+    let mut wem = fixture.wem.clone();
+    wem[bad.payload_offset as usize] |= 1;
+
+    let (report, ogg) = fixture.recover(wem, RecoveryMode::Skip);
+    let source: Vec<u64> = fixture.source.granules().iter().map(|&granule| granule as u64).collect();
+    assert_eq!(report.lost.len(), 1);
+    assert_eq!(report.lost[0].len, bad.next_offset() - bad.header_offset);
+    assert_eq!(report.lost[0].samples, Some(source[10] - source[9]));
+
+    // Every packet after the gap ends where it does in the source.
+    let mut expected = source.clone();
+    expected.remove(10);
+    assert_eq!(audio_granules(&ogg), expected);
+    assert_eq!(report.samples, report.expected_samples);
+}
+
+#[test]
+fn unreadable_gaps_are_reported_without_samples() {
+    let fixture = Fixture::new(Vorb::Size2A, true);
+    let packets = fixture.packets();
+    let bad = &packets[20];
+    let mut wem = fixture.wem.clone();
+    wem[bad.header_offset as usize..bad.header_offset as usize + 2].copy_from_slice(&[0xFF, 0xFF]);
+
+    let (report, ogg) = fixture.recover(wem, RecoveryMode::Skip);
+    assert_eq!(report.lost.len(), 1);
+    assert_eq!(report.lost[0].samples, None);
+
+    // Without the lost packet's block size, the granules after it fall short.
+    let source = fixture.source.granules();
+    let granules = audio_granules(&ogg);
+    assert_eq!(granules[19], source[19] as u64);
+    assert!(granules[20] < source[21] as u64);
+}
+
+#[test]
+fn resyncing_over_a_long_gap_reads_little() {
+    let fixture = Fixture::new(Vorb::Size2A, false);
+    let packets = fixture.packets();
+    let (first, last) = (&packets[5], &packets[packets.len() - 10]);
+    let mut damaged = fixture.wem.clone();
+    damaged[first.header_offset as usize..last.header_offset as usize].fill(0xFF);
+
+    let options = WemOptions { recovering: true, ..fixture.layout.options(&fixture.codebooks) };
+    let recover = |wem: &[u8]| {
+        let read = Rc::new(Cell::new(0));
+        let reader = Counting { inner: Cursor::new(wem.to_vec()), read: read.clone() };
+        let mut opened = WwiseRiffVorbis::from_reader(reader, "fixture.wem", &options).unwrap();
+        let report = opened.generate_ogg_recovering(std::io::sink(), RecoveryMode::Skip).unwrap();
+        (report, read.get())
+    };
+    let (report, damaged_read) = recover(&damaged);
+    assert_eq!(report.packets, 5 + 10);
+    assert_eq!(report.lost[0].offset, first.header_offset);
+    assert_eq!(report.lost[0].len, last.header_offset - first.header_offset);
+
+    // Every offset in the gap is tried, but that costs no more reading than
+    // converting the packets that were there.
+    let (_, intact_read) = recover(&fixture.wem);
+    assert!(damaged_read <= intact_read, "{} bytes read, {} when intact", damaged_read, intact_read);
+}
@@ -0,0 +1,149 @@
+//! Converts synthetic wems built from known Ogg Vorbis streams and checks
+//! that the original Vorbis packets and granules come back out.
+
+mod support;
+
+use std::io::Read;
+
+use support::{convert, open, open_wem, wem_bytes, Layout, Vorb, VorbisSource};
+use wem_converter::ogg::{parse_identification, validate_ogg, OggPacketReader};
+use wem_converter::overrides::Overrides;
+use wem_converter::stream::WemStream;
+
+const SOURCES: [&str; 2] = ["sine_stereo.ogg", "sine_mono.ogg"];
+
+/// Checks that `ogg` carries the packets of `source`, returning what differs.
+fn compare(source: &VorbisSource, layout: &Layout, ogg: &[u8]) -> Result<(), String> {
+    let report = validate_ogg(ogg).map_err(|e| e.to_string())?;
+    if let Some(issue) = report.issues.first() {
+        return Err(issue.to_string());
+    }
+    let mut reader = OggPacketReader::new(ogg);
+    let mut packets = Vec::new();
+    while let Some(packet) = reader.next_packet().map_err(|e| e.to_string())? {
+        packets.push(packet);
+    }
+    if packets.len() != 3 + source.audio.len() {
+        return Err(format!("{} packets, expected {}", packets.len(), 3 + source.audio.len()));
+    }
+
+    let identification = parse_identification(&packets[0].data).map_err(|e| e.to_string())?;
+    if identification.channels != source.channels
+        || identification.sample_rate != source.sample_rate
+        || identification.blocksize_0_pow != source.blocksize_0_pow
+        || identification.blocksize_1_pow != source.blocksize_1_pow
+    {
+        return Err("identification header differs".into());
+    }
+    if layout.vorb.header_triad()
+        && (packets[0].data != source.identification || packets[1].data != source.comment)
+    {
+        return Err("copied header differs".into());
+    }
+    // A full setup is copied up to the end of the wem's packet, padding included.
+    let setup_same = if layout.full_setup && !layout.vorb.header_triad() {
+        trim_padding(&packets[2].data) == trim_padding(&source.setup)
+    } else {
+        packets[2].data == source.setup
+    };
+    if !setup_same {
+        return Err("setup header differs".into());
+    }
+    let granules = source.granules();
+    for (i, packet) in packets[3..].iter().enumerate() {
+        // Mod packets don't keep the zero padding at the end of a packet,
+        // nor the next window flag of the last one.
+        let same = if layout.mod_packets && i + 1 == source.audio.len() {
+            trim_padding(&source.clear_next_window_flag(&packet.data))
+                == trim_padding(&source.clear_next_window_flag(&source.audio[i]))
+        } else if layout.mod_packets {
+            trim_padding(&packet.data) == trim_padding(&source.audio[i])
+        } else {
+            packet.data == source.audio[i]
+        };
+        if !same {
+            return Err(format!("audio packet {} differs", i));
+        }
+        if packet.granule != Some(granules[i] as u64) {
+            return Err(format!("audio packet {} has granule {:?}, expected {}", i, packet.granule, granules[i]));
+        }
+    }
+    Ok(())
+}
+
+fn trim_padding(packet: &[u8]) -> &[u8] {
+    let end = packet.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    &packet[..end]
+}
+
+#[test]
+fn every_layout_round_trips() {
+    let mut failures = Vec::new();
+    for name in SOURCES {
+        let source = VorbisSource::load(name);
+        for layout in Layout::all() {
+            let mut wem = open(&source, &layout, "round_trip");
+            wem.derive_granules = true;
+            let ogg = convert(&mut wem);
+            if let Err(e) = compare(&source, &layout, &ogg) {
+                failures.push(format!("{} {}: {}", name, layout.tag(), e));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn seek_table_before_setup() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    for vorb in [Vorb::Size2A, Vorb::Size32, Vorb::Size2C] {
+        let layout = Layout { seek_table: true, ..Layout::new(vorb) };
+        let mut wem = open(&source, &layout, "seek_table");
+        wem.derive_granules = true;
+        let ogg = convert(&mut wem);
+        compare(&source, &layout, &ogg).unwrap_or_else(|e| panic!("{}: {}", layout.tag(), e));
+    }
+}
+
+#[test]
+fn every_front_end_writes_the_same_stream() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    for layout in Layout::all() {
+        let mut wem = open(&source, &layout, "front_ends");
+        let (bytes, codebooks) = (wem_bytes(&wem), wem.codebooks_name.clone());
+        let reopen = || open_wem(bytes.clone(), &layout, &codebooks, Overrides::default()).unwrap();
+        let expected = convert(&mut wem);
+
+        let mut lazy = Vec::new();
+        reopen().into_ogg_reader().read_to_end(&mut lazy).unwrap();
+        assert!(lazy == expected, "{}: OggReader differs", layout.tag());
+
+        let stream = WemStream::new(&bytes[..], &layout.options(&codebooks))
+        .unwrap();
+        let mut streamed = Vec::new();
+        stream.generate_ogg_to(&mut streamed).unwrap();
+        assert!(streamed == expected, "{}: WemStream differs", layout.tag());
+
+        let packets: Vec<_> = reopen().vorbis_packets().unwrap().map(|p| p.unwrap().data).collect();
+        let mut reader = OggPacketReader::new(&expected[..]);
+        let mut demuxed = Vec::new();
+        while let Some(packet) = reader.next_packet().unwrap() {
+            demuxed.push(packet.data);
+        }
+        assert!(packets == demuxed, "{}: vorbis_packets differs", layout.tag());
+    }
+}
+
+#[test]
+fn truncated_wems_fail_without_panicking() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    for layout in [Layout::new(Vorb::Size2A), Layout::new(Vorb::Size34), Layout::new(Vorb::Size28)] {
+        let wem = open(&source, &layout, "truncated");
+        let bytes = wem_bytes(&wem);
+        for len in (0..bytes.len()).step_by(97) {
+            let converted = open_wem(bytes[..len].to_vec(), &layout, &wem.codebooks_name, Overrides::default())
+                .and_then(|mut wem| wem.generate_ogg_to(std::io::sink()));
+            assert!(converted.is_err(), "{}: {} bytes converted", layout.tag(), len);
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Seek table parsing and sample lookup on generated wems.
+
+mod support;
+
+use std::cell::Cell;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::rc::Rc;
+
+use support::{open, open_wem, wem_bytes, Layout, Vorb, VorbisSource, SEEK_INTERVAL};
+use wem_converter::overrides::Overrides;
+use wem_converter::wwriff::{WwisePacket, WwiseRiffVorbis};
+
+/// A reader that records the lowest offset it has read from.
+struct Lowest {
+    inner: Cursor<Vec<u8>>,
+    lowest: Rc<Cell<u64>>,
+}
+
+impl Read for Lowest {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.lowest.set(self.lowest.get().min(self.inner.position()));
+        self.inner.read(buf)
+    }
+}
+
+impl Seek for Lowest {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+fn layouts() -> Vec<Layout> {
+    let mut layouts = Vec::new();
+    for vorb in [Vorb::InFmt, Vorb::Size2A, Vorb::Size32, Vorb::Size2C] {
+        for seek_table in [false, true] {
+            layouts.push(Layout { seek_table, ..Layout::new(vorb) });
+            if !vorb.header_triad() {
+                layouts.push(Layout { seek_table, mod_packets: false, ..Layout::new(vorb) });
+            }
+        }
+    }
+    layouts
+}
+
+/// Samples around every packet boundary.
+fn samples(granules: &[u32]) -> Vec<u64> {
+    let last = *granules.last().unwrap() as u64;
+    let mut samples: Vec<u64> = granules
+        .iter()
+        .flat_map(|&granule| [granule as u64, (granule as u64).saturating_sub(1), granule as u64 + 1])
+        .filter(|&sample| sample < last)
+        .collect();
+    samples.sort();
+    samples.dedup();
+    samples
+}
+
+#[test]
+fn seek_table_points_at_packets() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let granules = source.granules();
+    for layout in layouts() {
+        let mut wem = open(&source, &layout, "seek");
+        let packets: Vec<WwisePacket> = wem.packets().map(|p| p.unwrap()).collect();
+        let table = wem.seek_table().unwrap();
+        if !layout.seek_table {
+            assert!(table.is_empty());
+            continue;
+        }
+        assert_eq!(table.len(), (source.audio.len() - 2) / SEEK_INTERVAL, "{}", layout.tag());
+        for (i, point) in table.iter().enumerate() {
+            let packet = (i + 1) * SEEK_INTERVAL;
+            assert_eq!(point.granule, granules[packet] as u64, "{}", layout.tag());
+            assert_eq!(point.offset, packets[packet].header_offset, "{}", layout.tag());
+        }
+    }
+}
+
+#[test]
+fn every_sample_is_found() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let granules = source.granules();
+    for layout in layouts() {
+        let mut wem = open(&source, &layout, "seek");
+        let packets: Vec<WwisePacket> = wem.packets().map(|p| p.unwrap()).collect();
+        for sample in samples(&granules) {
+            let found = wem.seek_to_sample(sample).unwrap_or_else(|e| panic!("{} {}: {}", layout.tag(), sample, e));
+            let expected = granules.iter().position(|&granule| granule as u64 > sample).unwrap();
+            let message = format!("{} sample {}", layout.tag(), sample);
+            assert_eq!(found.packet.header_offset, packets[expected].header_offset, "{}", message);
+            assert_eq!(found.previous.header_offset, packets[expected - 1].header_offset, "{}", message);
+            assert_eq!(found.start, granules[expected - 1] as u64, "{}", message);
+            assert_eq!(found.end, granules[expected] as u64, "{}", message);
+            assert!(found.start <= sample && sample < found.end, "{}", message);
+            // Indices count from the seek point the lookup started at.
+            if layout.seek_table && expected > SEEK_INTERVAL {
+                assert!(found.packet.index < expected as u64, "{}", message);
+            } else {
+                assert_eq!(found.packet.index, expected as u64, "{}", message);
+            }
+        }
+    }
+}
+
+#[test]
+fn bad_seek_table_falls_back_to_a_scan() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layout = Layout { seek_table: true, ..Layout::new(Vorb::Size32) };
+    let mut wem = open(&source, &layout, "seek");
+    let granules = source.granules();
+    let sample = granules[SEEK_INTERVAL * 3] as u64 + 10;
+    let expected = wem.seek_to_sample(sample).unwrap();
+
+    // Break the byte delta of the first entry, moving every entry off its packet.
+    let data = wem.data_offset as usize;
+    let mut bytes = wem_bytes(&wem);
+    bytes[data + 2] ^= 0x01;
+    let mut broken = open_wem(bytes, &layout, &wem.codebooks_name, Overrides::default()).unwrap();
+    assert_ne!(broken.seek_table().unwrap(), wem.seek_table().unwrap());
+    assert_eq!(broken.seek_to_sample(sample).unwrap().packet.header_offset, expected.packet.header_offset);
+}
+
+#[test]
+fn unparsable_seek_table_is_an_error() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layout = Layout { seek_table: true, ..Layout::new(Vorb::Size32) };
+    let mut wem = open(&source, &layout, "seek");
+    let sample = source.granules()[SEEK_INTERVAL * 3] as u64 + 10;
+    let expected = wem.seek_to_sample(sample).unwrap();
+
+    // Changed header fields rebuild the index: half an entry is an error,
+    // not an empty table, and the real size works again.
+    wem.setup_packet_offset += 2;
+    let err = wem.seek_to_sample(sample).unwrap_err();
+    assert!(err.to_string().contains("seek table"), "{}", err);
+    wem.setup_packet_offset -= 2;
+    assert_eq!(wem.seek_to_sample(sample).unwrap(), expected);
+}
+
+#[test]
+fn seek_index_follows_the_header_fields() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layout = Layout { seek_table: true, ..Layout::new(Vorb::Size32) };
+    let mut wem = open(&source, &layout, "seek");
+    let sample = source.granules()[SEEK_INTERVAL * 3] as u64 + 10;
+    let expected = wem.seek_to_sample(sample).unwrap();
+
+    // Without the seek table the packets are counted from the first one,
+    // not from a seek point.
+    wem.setup_packet_offset = 0;
+    let scanned = wem.seek_to_sample(sample).unwrap();
+    assert_eq!(scanned.packet.header_offset, expected.packet.header_offset);
+    assert!(scanned.packet.index > expected.packet.index);
+}
+
+#[test]
+fn samples_past_the_end_are_rejected() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    let mut wem = open(&source, &Layout::new(Vorb::Size2A), "seek");
+    assert!(wem.seek_to_sample(source.sample_count as u64 - 1).is_ok());
+    assert!(wem.seek_to_sample(source.sample_count as u64).is_err());
+}
+
+#[test]
+fn headers_are_read_once_for_every_lookup() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let granules = source.granules();
+    for vorb in [Vorb::Size2A, Vorb::Size32] {
+        let layout = Layout { seek_table: true, ..Layout::new(vorb) };
+        let built = open(&source, &layout, "seek");
+        let lowest = Rc::new(Cell::new(u64::MAX));
+        let reader = Lowest { inner: Cursor::new(wem_bytes(&built)), lowest: lowest.clone() };
+        let mut wem = WwiseRiffVorbis::from_reader(reader, "seek.wem", &layout.options(&built.codebooks_name))
+        .unwrap();
+        let first_audio = (wem.data_offset + wem.first_audio_packet_offset as i64) as u64;
+
+        let sample = granules[SEEK_INTERVAL * 2] as u64 + 10;
+        let expected = wem.seek_to_sample(sample).unwrap();
+        lowest.set(u64::MAX);
+        for _ in 0..3 {
+            assert_eq!(wem.seek_to_sample(sample).unwrap(), expected, "{}", layout.tag());
+        }
+        // Later lookups only read audio packets, from the seek point on.
+        assert!(lowest.get() > first_audio, "{}", layout.tag());
+    }
+}
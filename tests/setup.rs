@@ -0,0 +1,58 @@
+//! Setup headers parsed into `VorbisSetup` and written back.
+
+mod support;
+
+use support::{open, Layout, VorbisSource};
+use wem_converter::setup::{Floor, VorbisSetup};
+
+#[test]
+fn standard_setups_round_trip() {
+    for name in ["sine_mono.ogg", "sine_stereo.ogg"] {
+        let source = VorbisSource::load(name);
+        let setup = VorbisSetup::parse(&source.setup, source.channels).unwrap();
+        assert_eq!(setup.mode_blockflag(), source.mode_blockflag, "{}", name);
+        assert!(setup.floors.iter().all(|floor| matches!(floor, Floor::One(_))), "{}", name);
+        assert_eq!(setup.to_packet().unwrap(), source.setup, "{}", name);
+    }
+}
+
+#[test]
+fn every_layout_gives_the_source_setup() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let expected = VorbisSetup::parse(&source.setup, source.channels).unwrap();
+    for layout in Layout::all() {
+        let mut wem = open(&source, &layout, "setup");
+        assert_eq!(wem.vorbis_setup().unwrap(), expected, "{}", layout.tag());
+    }
+}
+
+#[test]
+fn changed_setups_are_written_and_checked() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let mut setup = VorbisSetup::parse(&source.setup, source.channels).unwrap();
+    setup.modes[0].blockflag = !setup.modes[0].blockflag;
+    let packet = setup.to_packet().unwrap();
+    assert_eq!(VorbisSetup::parse(&packet, source.channels).unwrap(), setup);
+
+    let mut broken = setup.clone();
+    broken.modes[0].mapping = broken.mappings.len() as u8;
+    assert!(broken.validate().is_err());
+    let mut broken = setup.clone();
+    broken.mappings[0].submaps[0].residue = broken.residues.len() as u8;
+    assert!(broken.validate().is_err());
+    let mut broken = setup;
+    broken.residues[0].classbook = broken.codebooks.len() as u8;
+    let packet = broken.to_packet().unwrap();
+    assert!(VorbisSetup::parse(&packet, source.channels).is_err());
+}
+
+#[test]
+fn damaged_packets_are_rejected() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    assert!(VorbisSetup::parse(&source.comment, source.channels).is_err());
+    assert!(VorbisSetup::parse(&source.setup[..source.setup.len() / 2], source.channels).is_err());
+    let mut packet = source.setup.clone();
+    // The first codebook's sync pattern.
+    packet[8] ^= 0xFF;
+    assert!(VorbisSetup::parse(&packet, source.channels).is_err());
+}
@@ -0,0 +1,63 @@
+//! Packet size, bitrate and block size statistics.
+
+mod support;
+
+use support::{open, Layout, Vorb, VorbisSource};
+
+#[test]
+fn stats_match_the_source_packets() {
+    for name in ["sine_mono.ogg", "sine_stereo.ogg"] {
+        let source = VorbisSource::load(name);
+        let sizes: Vec<u64> = source.audio.iter().map(|packet| packet.len() as u64).collect();
+        let total: u64 = sizes.iter().sum();
+        let long_blocks = source.audio.iter().filter(|packet| source.blockflag(packet)).count() as u64;
+
+        let rate = source.sample_rate as u64;
+        let mut per_second = vec![0; (source.sample_count as u64).div_ceil(rate) as usize];
+        let mut start = 0;
+        for (size, granule) in sizes.iter().zip(source.granules()) {
+            per_second[(start / rate) as usize] += size * 8;
+            start = granule as u64;
+        }
+
+        for vorb in [Vorb::InFmt, Vorb::Size2C, Vorb::Size32] {
+            let layout = Layout { mod_packets: false, ..Layout::new(vorb) };
+            let stats = open(&source, &layout, "stats").bitrate_stats().unwrap();
+            let message = format!("{} {}", name, layout.tag());
+            assert_eq!(stats.packet_count, sizes.len() as u64, "{}", message);
+            assert_eq!(stats.min_packet_size as u64, *sizes.iter().min().unwrap(), "{}", message);
+            assert_eq!(stats.max_packet_size as u64, *sizes.iter().max().unwrap(), "{}", message);
+            assert_eq!(stats.average_packet_size, total as f64 / sizes.len() as f64, "{}", message);
+            let bitrate = total as f64 * 8.0 * rate as f64 / source.sample_count as f64;
+            assert!((stats.average_bitrate - bitrate).abs() < 1e-6, "{}", message);
+            assert_eq!(stats.long_blocks, long_blocks, "{}", message);
+            assert_eq!(stats.short_blocks, sizes.len() as u64 - long_blocks, "{}", message);
+            assert_eq!(stats.bitrate_per_second, per_second, "{}", message);
+            assert_eq!(stats.bitrate_per_second.iter().sum::<u64>(), total * 8, "{}", message);
+        }
+    }
+}
+
+#[test]
+fn mod_packets_have_the_same_blocks() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let standard = open(&source, &Layout { mod_packets: false, ..Layout::new(Vorb::InFmt) }, "stats").bitrate_stats().unwrap();
+    for vorb in [Vorb::InFmt, Vorb::Size2A, Vorb::Size34] {
+        let modded = open(&source, &Layout::new(vorb), "stats").bitrate_stats().unwrap();
+        assert_eq!(modded.packet_count, standard.packet_count);
+        assert_eq!((modded.short_blocks, modded.long_blocks), (standard.short_blocks, standard.long_blocks));
+        assert_eq!(modded.bitrate_per_second.len(), standard.bitrate_per_second.len());
+        // Mod packets leave out a bit or three.
+        assert!(modded.average_packet_size <= standard.average_packet_size);
+        assert!(modded.max_packet_size <= standard.max_packet_size);
+    }
+}
+
+#[test]
+fn header_bitrate_is_reported_alongside() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    let stats = open(&source, &Layout::new(Vorb::Size2A), "stats").bitrate_stats().unwrap();
+    // The fixture's fmt chunk claims 16000 bytes per second.
+    assert_eq!(stats.header_bitrate, 128_000);
+    assert!(stats.average_bitrate > 0.0);
+}
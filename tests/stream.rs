@@ -0,0 +1,173 @@
+//! Conversion from forward-only readers, and the wems they can't handle.
+
+mod support;
+
+use support::{convert, insert_into_vorb, open, open_wem, wem_bytes, Layout, Vorb, VorbisSource};
+use wem_converter::errors::Result;
+use wem_converter::options::WemOptions;
+use wem_converter::overrides::Overrides;
+use wem_converter::stream::WemStream;
+use wem_converter::wwriff::ForcePacketFormat;
+
+/// Offset and total size of every chunk of a RIFF (little-endian) wem.
+fn chunks(wem: &[u8]) -> Vec<(usize, usize)> {
+    let mut chunks = Vec::new();
+    let mut offset = 12;
+    while offset < wem.len() {
+        let size = u32::from_le_bytes(wem[offset + 4..offset + 8].try_into().unwrap()) as usize;
+        chunks.push((offset, 8 + size));
+        offset += 8 + size;
+    }
+    chunks
+}
+
+/// `wem` with the chunk of type `id` moved to the end, after the data chunk.
+fn move_to_end(wem: &[u8], id: &[u8; 4]) -> Vec<u8> {
+    let mut out = wem[..12].to_vec();
+    let mut moved = Vec::new();
+    for (offset, size) in chunks(wem) {
+        let chunk = &wem[offset..offset + size];
+        if &chunk[..4] == id {
+            moved.extend_from_slice(chunk);
+        } else {
+            out.extend_from_slice(chunk);
+        }
+    }
+    assert!(!moved.is_empty());
+    out.extend_from_slice(&moved);
+    out
+}
+
+/// A RIFF header and `chunks` as (type, declared size, bytes). The RIFF size
+/// counts the declared sizes, so a chunk can claim more than it holds.
+fn riff(chunks: &[(&[u8; 4], u32, &[u8])]) -> Vec<u8> {
+    let size = 4 + chunks.iter().map(|&(_, size, _)| 8 + size).sum::<u32>();
+    let mut wem = b"RIFF".to_vec();
+    wem.extend_from_slice(&size.to_le_bytes());
+    wem.extend_from_slice(b"WAVE");
+    for &(id, size, bytes) in chunks {
+        wem.extend_from_slice(id);
+        wem.extend_from_slice(&size.to_le_bytes());
+        wem.extend_from_slice(bytes);
+    }
+    wem
+}
+
+fn riff_error(wem: &[u8]) -> String {
+    let options = WemOptions::new("", ForcePacketFormat::ModPackets);
+    WemStream::new(wem, &options).map(|_| ()).unwrap_err().to_string()
+}
+
+fn stream(wem: &[u8], codebooks: &str, layout: &Layout) -> Result<Vec<u8>> {
+    let stream = WemStream::new(wem, &layout.options(codebooks))?;
+    let mut ogg = Vec::new();
+    stream.generate_ogg_to(&mut ogg)?;
+    Ok(ogg)
+}
+
+fn error(result: Result<Vec<u8>>) -> String {
+    result.map(|ogg| ogg.len()).unwrap_err().to_string()
+}
+
+#[test]
+fn chunks_after_data_are_never_read() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    let layout = Layout::new(Vorb::Size34);
+    let mut wem = open(&source, &layout, "stream");
+    let expected = convert(&mut wem);
+
+    // A LIST chunk the RIFF size counts, cut off where the input ends.
+    let mut bytes = wem_bytes(&wem);
+    let riff_size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) + 8 + 0x100;
+    bytes[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    bytes.extend_from_slice(b"LIST");
+    bytes.extend_from_slice(&0x100u32.to_le_bytes());
+    assert!(stream(&bytes, &wem.codebooks_name, &layout).unwrap() == expected);
+}
+
+#[test]
+fn fmt_chunk_after_data_is_rejected() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    for vorb in [Vorb::InFmt, Vorb::Size2A, Vorb::Size28] {
+        let layout = Layout::new(vorb);
+        let mut wem = open(&source, &layout, "stream");
+        let expected = convert(&mut wem);
+        let moved = move_to_end(&wem_bytes(&wem), b"fmt ");
+        assert_eq!(
+            error(stream(&moved, &wem.codebooks_name, &layout)),
+            "Parse error: fmt chunk after data chunk, can't convert without seeking",
+            "{}",
+            layout.tag()
+        );
+
+        // A seekable reader still finds it.
+        let mut seekable = open_wem(moved, &layout, &wem.codebooks_name, Overrides::default()).unwrap();
+        assert!(convert(&mut seekable) == expected, "{}", layout.tag());
+    }
+}
+
+#[test]
+fn vorb_chunk_after_data_is_rejected() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    for vorb in [Vorb::Size2A, Vorb::Size32] {
+        let layout = Layout::new(vorb);
+        let mut wem = open(&source, &layout, "stream");
+        let expected = convert(&mut wem);
+        let moved = move_to_end(&wem_bytes(&wem), b"vorb");
+        assert_eq!(
+            error(stream(&moved, &wem.codebooks_name, &layout)),
+            "Parse error: vorb chunk after data chunk, can't convert without seeking",
+            "{}",
+            layout.tag()
+        );
+
+        let mut seekable = open_wem(moved, &layout, &wem.codebooks_name, Overrides::default()).unwrap();
+        assert!(convert(&mut seekable) == expected, "{}", layout.tag());
+    }
+}
+
+#[test]
+fn unknown_vorb_layouts_are_rejected() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    let layout = Layout::new(Vorb::Size32);
+    let wem = open(&source, &layout, "stream");
+    let widened = insert_into_vorb(&wem_bytes(&wem), false, 8, &[0xEE; 0x10]);
+    assert_eq!(
+        error(stream(&widened, &wem.codebooks_name, &layout)),
+        "Parse error: unknown vorb layout, can't detect it without seeking"
+    );
+}
+
+#[test]
+fn recovery_is_refused() {
+    let options = WemOptions { recovering: true, ..WemOptions::new("", ForcePacketFormat::ModPackets) };
+    let err = WemStream::new(&b"RIFF"[..], &options).map(|_| ()).unwrap_err();
+    assert_eq!(err.to_string(), "Parse error: can't recover a wem from a forward-only reader");
+}
+
+#[test]
+fn data_before_fmt_is_rejected_without_being_read() {
+    // The data chunk claims 4 KiB the input doesn't have.
+    let wem = riff(&[(b"data", 0x1000, &[]), (b"fmt ", 0x18, &[0; 0x18])]);
+    let data_end = 12 + 8;
+    assert_eq!(
+        riff_error(&wem[..data_end]),
+        "Parse error: fmt chunk after data chunk, can't convert without seeking"
+    );
+}
+
+#[test]
+fn data_before_vorb_is_rejected() {
+    let wem = riff(&[(b"fmt ", 0x18, &[0; 0x18]), (b"data", 0x1000, &[]), (b"vorb", 0x2A, &[0; 0x2A])]);
+    assert_eq!(riff_error(&wem), "Parse error: vorb chunk after data chunk, can't convert without seeking");
+}
+
+#[test]
+fn truncated_headers_fail_cleanly() {
+    let wem = riff(&[(b"fmt ", 0x42, &[0; 0x42]), (b"data", 0x1000, &[])]);
+    for len in [0, 4, 11, 20, 40] {
+        assert_eq!(riff_error(&wem[..len]), "Parse error: unexpected end of stream", "{} bytes", len);
+    }
+    assert_eq!(riff_error(b"RIFX\0\0\0\x04WAVX"), "Parse error: missing WAVE");
+    assert_eq!(riff_error(b"OggS\0\0\0\0\0\0\0\0"), "Parse error: missing RIFF");
+}
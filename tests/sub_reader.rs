@@ -0,0 +1,101 @@
+//! Windows into larger readers, for wems embedded in banks and archives.
+
+mod support;
+
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom};
+
+use support::{convert, open, wem_bytes, Layout, Vorb, VorbisSource};
+use wem_converter::sub_reader::SubReader;
+use wem_converter::wwriff::WwiseRiffVorbis;
+
+/// Bytes 0, 1, 2, ... of a 100 byte reader, windowed to 40..70.
+fn window() -> SubReader<Cursor<Vec<u8>>> {
+    SubReader::new(Cursor::new((0..100).collect()), 40, 30).unwrap()
+}
+
+fn read_byte(reader: &mut impl Read) -> Option<u8> {
+    let mut byte = [0];
+    match reader.read(&mut byte).unwrap() {
+        0 => None,
+        _ => Some(byte[0]),
+    }
+}
+
+#[test]
+fn seeks_are_relative_to_the_window() {
+    let mut reader = window();
+    assert_eq!(reader.seek(SeekFrom::End(0)).unwrap(), 30);
+    assert_eq!(read_byte(&mut reader), None);
+    assert_eq!(reader.seek(SeekFrom::End(-1)).unwrap(), 29);
+    assert_eq!(read_byte(&mut reader), Some(69));
+    assert_eq!(reader.seek(SeekFrom::End(-30)).unwrap(), 0);
+    assert_eq!(read_byte(&mut reader), Some(40));
+
+    assert_eq!(reader.seek(SeekFrom::Current(-1)).unwrap(), 0);
+    assert_eq!(reader.seek(SeekFrom::Current(29)).unwrap(), 29);
+    assert_eq!(read_byte(&mut reader), Some(69));
+    assert_eq!(reader.stream_position().unwrap(), 30);
+    assert_eq!(read_byte(&mut reader), None);
+}
+
+#[test]
+fn seeks_before_the_window_fail_and_leave_the_position() {
+    let mut reader = window();
+    reader.seek(SeekFrom::Start(5)).unwrap();
+    for pos in [SeekFrom::End(-31), SeekFrom::Current(-6)] {
+        let err = reader.seek(pos).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(reader.stream_position().unwrap(), 5);
+    }
+    assert_eq!(read_byte(&mut reader), Some(45));
+}
+
+#[test]
+fn reads_stop_at_the_end_of_the_window() {
+    let mut reader = window();
+    let mut all = Vec::new();
+    reader.read_to_end(&mut all).unwrap();
+    assert_eq!(all, (40..70).collect::<Vec<u8>>());
+
+    // A read that starts inside and would run past the end is cut short.
+    reader.seek(SeekFrom::End(-3)).unwrap();
+    let mut buf = [0; 10];
+    assert_eq!(reader.read(&mut buf).unwrap(), 3);
+    assert_eq!(buf[..3], [67, 68, 69]);
+    assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+    // Seeking past the end is allowed, as for files, but reads nothing.
+    assert_eq!(reader.seek(SeekFrom::Current(10)).unwrap(), 40);
+    assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+    assert!(reader.read_exact(&mut [0; 31]).is_err());
+}
+
+#[test]
+fn windows_past_the_reader_are_rejected() {
+    let inner = || Cursor::new(vec![0u8; 100]);
+    assert!(SubReader::new(inner(), 0, 100).is_ok());
+    assert!(SubReader::new(inner(), 100, 0).is_ok());
+    assert_eq!(SubReader::new(inner(), 71, 30).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    assert_eq!(SubReader::new(inner(), u64::MAX, 2).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn embedded_wems_convert_in_place() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    let layout = Layout::new(Vorb::Size2A);
+    let mut wem = open(&source, &layout, "sub_reader");
+    let bytes = wem_bytes(&wem);
+    let expected = convert(&mut wem);
+
+    let mut bank = vec![0xAA; 123];
+    bank.extend_from_slice(&bytes);
+    bank.extend_from_slice(&[0xBB; 77]);
+    let embedded = SubReader::new(Cursor::new(bank), 123, bytes.len() as u64).unwrap();
+    let mut embedded = WwiseRiffVorbis::from_reader(embedded, "bank.bnk", &layout.options(&wem.codebooks_name))
+    .unwrap();
+    embedded.verify = true;
+    let mut ogg = Vec::new();
+    embedded.generate_ogg_to(&mut ogg).unwrap();
+    assert!(ogg == expected);
+}
@@ -0,0 +1,767 @@
+//! Builds wem files from an Ogg Vorbis stream, in every layout the converter
+//! understands, so conversions can be checked against the original packets.
+//! The sources live in `tests/data`.
+
+#![allow(dead_code)]
+
+use std::io::{Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use wem_converter::bit_stream::{BitStream, BitWriter};
+use wem_converter::codebook::{book_maptype1_quantvals, ilog};
+use wem_converter::errors::Result;
+use wem_converter::ogg::{parse_identification, OggPacketReader};
+use wem_converter::options::WemOptions;
+use wem_converter::overrides::Overrides;
+use wem_converter::wwriff::{ForcePacketFormat, WwiseRiffVorbis};
+
+/// A wem opened from memory.
+pub type Wem = WwiseRiffVorbis<Cursor<Vec<u8>>>;
+
+/// The packets of a standard Ogg Vorbis stream.
+#[derive(Debug, Clone)]
+pub struct VorbisSource {
+    pub identification: Vec<u8>,
+    pub comment: Vec<u8>,
+    pub setup: Vec<u8>,
+    pub audio: Vec<Vec<u8>>,
+    pub channels: u8,
+    pub sample_rate: u32,
+    pub blocksize_0_pow: u8,
+    pub blocksize_1_pow: u8,
+    /// Granule of the last page.
+    pub sample_count: u32,
+    /// Block flag of every mode in the setup header.
+    pub mode_blockflag: Vec<bool>,
+}
+
+impl VorbisSource {
+    /// Loads `tests/data/<name>`.
+    pub fn load(name: &str) -> Self {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data").join(name);
+        Self::from_ogg(&std::fs::read(path).unwrap())
+    }
+
+    pub fn from_ogg(ogg: &[u8]) -> Self {
+        let mut reader = OggPacketReader::new(ogg);
+        let mut packets = Vec::new();
+        let mut sample_count = 0;
+        while let Some(packet) = reader.next_packet().unwrap() {
+            if let Some(granule) = packet.granule {
+                sample_count = granule as u32;
+            }
+            packets.push(packet.data);
+        }
+        let mut packets = packets.into_iter();
+        let identification = packets.next().unwrap();
+        let comment = packets.next().unwrap();
+        let setup = packets.next().unwrap();
+        let ident = parse_identification(&identification).unwrap();
+        let mut source = VorbisSource {
+            identification,
+            comment,
+            setup,
+            audio: packets.collect(),
+            channels: ident.channels,
+            sample_rate: ident.sample_rate,
+            blocksize_0_pow: ident.blocksize_0_pow,
+            blocksize_1_pow: ident.blocksize_1_pow,
+            sample_count,
+            mode_blockflag: Vec::new(),
+        };
+        source.mode_blockflag = transcode_setup(&source, SetupForm::Full, &mut Vec::new()).1;
+        source
+    }
+
+    fn mode_bits(&self) -> u8 {
+        ilog(self.mode_blockflag.len() as u32 - 1) as u8
+    }
+
+    /// Block flag of the mode `packet` uses.
+    pub fn blockflag(&self, packet: &[u8]) -> bool {
+        let mode = (packet[0] as u32 >> 1) & ((1 << self.mode_bits()) - 1);
+        self.mode_blockflag[mode as usize]
+    }
+
+    /// `packet` with the next window flag of a long block cleared. Mod packets
+    /// don't store it, so the converter can only guess it for the last packet.
+    pub fn clear_next_window_flag(&self, packet: &[u8]) -> Vec<u8> {
+        let mut packet = packet.to_vec();
+        if !packet.is_empty() && self.blockflag(&packet) {
+            let bit = 1 + self.mode_bits() as usize + 1;
+            packet[bit / 8] &= !(1 << (bit % 8));
+        }
+        packet
+    }
+
+    /// Absolute granule after every audio packet, ending at `sample_count`.
+    pub fn granules(&self) -> Vec<u32> {
+        let blocksizes = [1u32 << self.blocksize_0_pow, 1u32 << self.blocksize_1_pow];
+        let mut prev = None;
+        let mut granule = 0;
+        let mut out: Vec<u32> = self
+            .audio
+            .iter()
+            .map(|packet| {
+                let blocksize = blocksizes[self.blockflag(packet) as usize];
+                if let Some(prev) = prev {
+                    granule += prev / 4 + blocksize / 4;
+                }
+                prev = Some(blocksize);
+                granule
+            })
+            .collect();
+        if let Some(last) = out.last_mut() {
+            *last = (*last).min(self.sample_count);
+        }
+        out
+    }
+}
+
+/// Audio packets between two seek table entries.
+pub const SEEK_INTERVAL: usize = 8;
+
+/// The vorb chunk layouts, by size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vorb {
+    /// No vorb chunk; its fields live in a 0x42 byte fmt chunk.
+    InFmt,
+    Size28,
+    Size2A,
+    Size2C,
+    Size32,
+    Size34,
+}
+
+impl Vorb {
+    pub const ALL: [Vorb; 6] = [Vorb::InFmt, Vorb::Size28, Vorb::Size2A, Vorb::Size2C, Vorb::Size32, Vorb::Size34];
+
+    fn size(self) -> usize {
+        match self {
+            Vorb::InFmt | Vorb::Size2A => 0x2A,
+            Vorb::Size28 => 0x28,
+            Vorb::Size2C => 0x2C,
+            Vorb::Size32 => 0x32,
+            Vorb::Size34 => 0x34,
+        }
+    }
+
+    /// Size of every packet header in the data chunk.
+    pub fn packet_header_size(self) -> usize {
+        match self {
+            Vorb::InFmt | Vorb::Size2A => 2,
+            Vorb::Size32 | Vorb::Size34 => 6,
+            Vorb::Size28 | Vorb::Size2C => 8,
+        }
+    }
+
+    /// Whether the full Vorbis header triad is stored in the data chunk.
+    pub fn header_triad(self) -> bool {
+        self.packet_header_size() == 8
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    pub vorb: Vorb,
+    pub big_endian: bool,
+    /// Setup stored with standard codebooks and floors/residues/mappings/modes.
+    pub full_setup: bool,
+    /// Codebooks stored in the setup rather than referenced by id.
+    pub inline_codebooks: bool,
+    /// Audio packets with the packet type and window flags stripped.
+    pub mod_packets: bool,
+    /// Seek table placed before the setup packet, with an entry every
+    /// `SEEK_INTERVAL` audio packets.
+    pub seek_table: bool,
+}
+
+impl Layout {
+    pub fn new(vorb: Vorb) -> Self {
+        Layout {
+            vorb,
+            big_endian: false,
+            full_setup: false,
+            inline_codebooks: false,
+            mod_packets: !vorb.header_triad(),
+            seek_table: false,
+        }
+    }
+
+    /// What the converter must be told to open this layout with a codebook
+    /// library at `codebooks`.
+    pub fn options(&self, codebooks: &str) -> WemOptions {
+        let force_packet_format = if self.mod_packets {
+            ForcePacketFormat::ModPackets
+        } else {
+            ForcePacketFormat::NoModPackets
+        };
+        WemOptions {
+            inline_codebooks: self.inline_codebooks,
+            full_setup: self.full_setup,
+            ..WemOptions::new(codebooks, force_packet_format)
+        }
+    }
+
+    /// Short name for messages and scratch files, e.g. `2A-rifx-full-inline-mod`.
+    pub fn tag(&self) -> String {
+        let vorb = match self.vorb {
+            Vorb::InFmt => "fmt42",
+            Vorb::Size28 => "28",
+            Vorb::Size2A => "2A",
+            Vorb::Size2C => "2C",
+            Vorb::Size32 => "32",
+            Vorb::Size34 => "34",
+        };
+        let mut tag = format!(
+            "{}-{}-{}-{}-{}",
+            vorb,
+            if self.big_endian { "rifx" } else { "riff" },
+            if self.full_setup { "full" } else { "stripped" },
+            if self.inline_codebooks { "inline" } else { "external" },
+            if self.mod_packets { "mod" } else { "std" },
+        );
+        if self.seek_table {
+            tag.push_str("-seek");
+        }
+        tag
+    }
+
+    /// Every combination of layout options the converter supports.
+    pub fn all() -> Vec<Layout> {
+        let mut layouts = Vec::new();
+        for vorb in Vorb::ALL {
+            for big_endian in [false, true] {
+                if vorb.header_triad() {
+                    layouts.push(Layout { big_endian, ..Layout::new(vorb) });
+                    continue;
+                }
+                for full_setup in [false, true] {
+                    for inline_codebooks in [false, true] {
+                        for mod_packets in [false, true] {
+                            layouts.push(Layout {
+                                vorb,
+                                big_endian,
+                                full_setup,
+                                inline_codebooks,
+                                mod_packets,
+                                seek_table: false,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        layouts
+    }
+}
+
+/// A generated wem and the codebook library its setup refers to.
+pub struct BuiltWem {
+    pub wem: Vec<u8>,
+    pub codebooks: Vec<u8>,
+}
+
+impl BuiltWem {
+    /// Writes the codebook library to a scratch file named after `tag` and
+    /// returns its path. Every call gets its own file, so tests running in
+    /// parallel never read a library another one is rewriting.
+    pub fn write_codebooks(&self, tag: &str) -> String {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("{}-{}.bin", tag, NEXT.fetch_add(1, Ordering::Relaxed));
+        let path: PathBuf = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+        std::fs::write(&path, &self.codebooks).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+}
+
+/// Builds `layout` from `source` and opens it. `tag` names the scratch
+/// codebook library.
+pub fn open(source: &VorbisSource, layout: &Layout, tag: &str) -> Wem {
+    let built = build_wem(source, layout);
+    let codebooks = built.write_codebooks(&format!("{}-{}", tag, layout.tag()));
+    open_wem(built.wem, layout, &codebooks, Overrides::default()).unwrap_or_else(|e| panic!("{}: {}", layout.tag(), e))
+}
+
+/// Opens `wem` bytes built with `layout`, whose setup refers to the codebook
+/// library at `codebooks`.
+pub fn open_wem(wem: Vec<u8>, layout: &Layout, codebooks: &str, overrides: Overrides) -> Result<Wem> {
+    WwiseRiffVorbis::from_reader(Cursor::new(wem), "fixture.wem", &WemOptions { overrides, ..layout.options(codebooks) })
+}
+
+/// The bytes `wem` was opened from.
+pub fn wem_bytes(wem: &Wem) -> Vec<u8> {
+    wem.infile.get_ref().get_ref().clone()
+}
+
+/// Converts `wem` with verification on.
+pub fn convert<R: Read + Seek>(wem: &mut WwiseRiffVorbis<R>) -> Vec<u8> {
+    wem.verify = true;
+    let mut ogg = Vec::new();
+    wem.generate_ogg_to(&mut ogg).unwrap();
+    ogg
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SetupForm {
+    /// Stripped setup, codebooks replaced by ids into the library.
+    StrippedExternal,
+    /// Stripped setup with compact inline codebooks.
+    StrippedInline,
+    /// Standard codebooks and floors, external codebook ids.
+    FullExternal,
+    /// Standard codebooks and floors, without the type/"vorbis" prefix and time domain section.
+    Full,
+}
+
+pub fn build_wem(source: &VorbisSource, layout: &Layout) -> BuiltWem {
+    let le = !layout.big_endian;
+    let u16b = |v: u16| if le { v.to_le_bytes() } else { v.to_be_bytes() };
+    let u32b = |v: u32| if le { v.to_le_bytes() } else { v.to_be_bytes() };
+    let header_size = layout.vorb.packet_header_size();
+
+    let mut library = Vec::new();
+    let granules = source.granules();
+    let push_packet = |data: &mut Vec<u8>, payload: &[u8], granule: u32| {
+        match header_size {
+            2 => data.extend_from_slice(&u16b(payload.len() as u16)),
+            6 => {
+                data.extend_from_slice(&u16b(payload.len() as u16));
+                data.extend_from_slice(&u32b(granule));
+            }
+            _ => {
+                data.extend_from_slice(&u32b(payload.len() as u32));
+                data.extend_from_slice(&u32b(granule));
+            }
+        }
+        data.extend_from_slice(payload);
+    };
+
+    let mut audio = Vec::new();
+    let mut audio_offsets = Vec::new();
+    for (packet, &granule) in source.audio.iter().zip(&granules) {
+        audio_offsets.push(audio.len() as u32);
+        if layout.mod_packets {
+            push_packet(&mut audio, &to_mod_packet(source, packet), granule);
+        } else {
+            push_packet(&mut audio, packet, granule);
+        }
+    }
+
+    let mut data = Vec::new();
+    if layout.seek_table {
+        // Granule and byte deltas to every SEEK_INTERVAL-th audio packet.
+        let mut last = (0, 0);
+        for i in (SEEK_INTERVAL..source.audio.len() - 1).step_by(SEEK_INTERVAL) {
+            let (granule, offset) = (granules[i], audio_offsets[i]);
+            if header_size == 8 {
+                data.extend_from_slice(&u32b(granule - last.0));
+                data.extend_from_slice(&u32b(offset - last.1));
+            } else {
+                data.extend_from_slice(&u16b((granule - last.0) as u16));
+                data.extend_from_slice(&u16b((offset - last.1) as u16));
+            }
+            last = (granule, offset);
+        }
+    }
+    let setup_packet_offset = data.len() as u32;
+
+    if layout.vorb.header_triad() {
+        push_packet(&mut data, &source.identification, 0);
+        push_packet(&mut data, &source.comment, 0);
+        push_packet(&mut data, &source.setup, 0);
+    } else {
+        let form = match (layout.full_setup, layout.inline_codebooks) {
+            (false, false) => SetupForm::StrippedExternal,
+            (false, true) => SetupForm::StrippedInline,
+            (true, false) => SetupForm::FullExternal,
+            (true, true) => SetupForm::Full,
+        };
+        let (setup, _) = transcode_setup(source, form, &mut library);
+        push_packet(&mut data, &setup, 0);
+    }
+    let first_audio_packet_offset = data.len() as u32;
+    data.extend_from_slice(&audio);
+
+    let mut vorb = vec![0u8; layout.vorb.size()];
+    let put = |vorb: &mut Vec<u8>, at: usize, bytes: &[u8]| vorb[at..at + bytes.len()].copy_from_slice(bytes);
+    put(&mut vorb, 0, &u32b(source.sample_count));
+    match layout.vorb {
+        Vorb::InFmt | Vorb::Size2A => {
+            put(&mut vorb, 0x04, &u32b(if layout.mod_packets { 0x3F } else { 0x4A }));
+            put(&mut vorb, 0x10, &u32b(setup_packet_offset));
+            put(&mut vorb, 0x14, &u32b(first_audio_packet_offset));
+            put(&mut vorb, 0x24, &u32b(0x1234_5678));
+            put(&mut vorb, 0x28, &[source.blocksize_0_pow, source.blocksize_1_pow]);
+        }
+        Vorb::Size28 | Vorb::Size2C => {
+            put(&mut vorb, 0x18, &u32b(setup_packet_offset));
+            put(&mut vorb, 0x1C, &u32b(first_audio_packet_offset));
+        }
+        Vorb::Size32 | Vorb::Size34 => {
+            put(&mut vorb, 0x18, &u32b(setup_packet_offset));
+            put(&mut vorb, 0x1C, &u32b(first_audio_packet_offset));
+            put(&mut vorb, 0x2C, &u32b(0x1234_5678));
+            put(&mut vorb, 0x30, &[source.blocksize_0_pow, source.blocksize_1_pow]);
+        }
+    }
+
+    let mut fmt = Vec::new();
+    fmt.extend_from_slice(&u16b(0xFFFF));
+    fmt.extend_from_slice(&u16b(source.channels as u16));
+    fmt.extend_from_slice(&u32b(source.sample_rate));
+    fmt.extend_from_slice(&u32b(16000));
+    fmt.extend_from_slice(&u16b(0));
+    fmt.extend_from_slice(&u16b(0));
+    let extra_len = if layout.vorb == Vorb::InFmt { 0x30 } else { 6 };
+    fmt.extend_from_slice(&u16b(extra_len));
+    fmt.extend_from_slice(&u16b(0));
+    fmt.extend_from_slice(&u32b(channel_mask(source.channels)));
+    if layout.vorb == Vorb::InFmt {
+        fmt.extend_from_slice(&vorb);
+    }
+
+    let mut chunks = Vec::new();
+    let mut push_chunk = |id: &[u8; 4], body: &[u8]| {
+        chunks.extend_from_slice(id);
+        chunks.extend_from_slice(&u32b(body.len() as u32));
+        // Wwise doesn't pad odd sized chunks
+        chunks.extend_from_slice(body);
+    };
+    push_chunk(b"fmt ", &fmt);
+    if layout.vorb != Vorb::InFmt {
+        push_chunk(b"vorb", &vorb);
+    }
+    push_chunk(b"data", &data);
+
+    let mut wem = Vec::new();
+    wem.extend_from_slice(if le { b"RIFF" } else { b"RIFX" });
+    wem.extend_from_slice(&u32b(chunks.len() as u32 + 4));
+    wem.extend_from_slice(b"WAVE");
+    wem.extend_from_slice(&chunks);
+
+    BuiltWem {
+        wem,
+        codebooks: pack_library(&library),
+    }
+}
+
+/// Inserts `bytes` at offset `at` of the vorb chunk of `wem`, growing the
+/// chunk and the RIFF to match.
+pub fn insert_into_vorb(wem: &[u8], big_endian: bool, at: usize, bytes: &[u8]) -> Vec<u8> {
+    let read = |offset: usize| {
+        let field = wem[offset..offset + 4].try_into().unwrap();
+        if big_endian { u32::from_be_bytes(field) } else { u32::from_le_bytes(field) }
+    };
+    let write = |out: &mut Vec<u8>, offset: usize, value: u32| {
+        let field = if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+        out[offset..offset + 4].copy_from_slice(&field);
+    };
+    let mut chunk = 12;
+    while &wem[chunk..chunk + 4] != b"vorb" {
+        chunk += 8 + read(chunk + 4) as usize;
+    }
+    let mut out = wem[..chunk + 8 + at].to_vec();
+    out.extend_from_slice(bytes);
+    out.extend_from_slice(&wem[chunk + 8 + at..]);
+    write(&mut out, 4, read(4) + bytes.len() as u32);
+    write(&mut out, chunk + 4, read(chunk + 4) + bytes.len() as u32);
+    out
+}
+
+/// Offset of the body of the vorb chunk in `wem`.
+pub fn vorb_offset(wem: &[u8]) -> usize {
+    wem.windows(4).position(|w| w == b"vorb").unwrap() + 8
+}
+
+/// WAVEFORMATEXTENSIBLE channel mask Wwise stores for the usual layouts.
+fn channel_mask(channels: u8) -> u32 {
+    match channels {
+        1 => 0x4,
+        2 => 0x3,
+        4 => 0x33,
+        5 => 0x37,
+        6 => 0x3F,
+        _ => 0,
+    }
+}
+
+/// Strips the packet type bit and the window flags of long blocks.
+fn to_mod_packet(source: &VorbisSource, packet: &[u8]) -> Vec<u8> {
+    if packet.is_empty() {
+        return Vec::new();
+    }
+    let mode_bits = source.mode_bits();
+    let mut ss = BitStream::new(packet);
+    let mut out = BitWriter::new();
+    assert_eq!(ss.read_bits(1).unwrap(), 0, "not an audio packet");
+    let mode = ss.read_bits(mode_bits).unwrap();
+    out.write_bits(mode, mode_bits);
+    if source.mode_blockflag[mode as usize] {
+        ss.read_bits(2).unwrap();
+    }
+    let mut bits_left = packet.len() as u64 * 8 - ss.get_total_bits_read();
+    while bits_left > 0 {
+        let n = bits_left.min(32) as u8;
+        out.write_bits(ss.read_bits(n).unwrap(), n);
+        bits_left -= n as u64;
+    }
+    let mut bytes = out.take_bytes();
+    // drop padding that only held the removed bits
+    while bytes.len() > 1 && bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+    bytes
+}
+
+/// Packs compact codebooks the way `packed_codebooks.bin` is laid out.
+fn pack_library(codebooks: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut offsets = Vec::new();
+    for cb in codebooks {
+        offsets.push(data.len() as u32);
+        data.extend_from_slice(cb);
+    }
+    let offset_offset = data.len() as u32;
+    for offset in offsets {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(&offset_offset.to_le_bytes());
+    data
+}
+
+/// Copies `bits` bits from `ss` to `out`.
+fn copy_bits(ss: &mut BitStream<&[u8]>, out: &mut BitWriter, bits: u8) -> u32 {
+    let v = ss.read_bits(bits).unwrap();
+    out.write_bits(v, bits);
+    v
+}
+
+/// Re-encodes the standard setup header of `source` in the given Wwise form.
+/// Compact codebooks are appended to `library` for the external forms.
+/// Also returns the block flag of every mode.
+fn transcode_setup(source: &VorbisSource, form: SetupForm, library: &mut Vec<Vec<u8>>) -> (Vec<u8>, Vec<bool>) {
+    let stripped = matches!(form, SetupForm::StrippedExternal | SetupForm::StrippedInline);
+    let mut ss = BitStream::new(&source.setup[7..]);
+    let mut out = BitWriter::new();
+    let channels = source.channels as u32;
+
+    let codebook_count = copy_bits(&mut ss, &mut out, 8) + 1;
+    for _ in 0..codebook_count {
+        let (standard, compact) = read_codebook(&mut ss);
+        match form {
+            SetupForm::StrippedExternal | SetupForm::FullExternal => {
+                out.write_bits(library.len() as u32, 10);
+                let mut bytes = compact.bytes.clone();
+                // the library stores bits / 8 + 1 bytes per codebook
+                bytes.resize(compact.bits as usize / 8 + 1, 0);
+                library.push(bytes);
+            }
+            SetupForm::StrippedInline => out.write_bytes_bits(&compact),
+            SetupForm::Full => out.write_bytes_bits(&standard),
+        }
+    }
+
+    // time domain transforms are left out, the converter writes them itself
+    let time_count = ss.read_bits(6).unwrap() + 1;
+    for _ in 0..time_count {
+        assert_eq!(ss.read_bits(16).unwrap(), 0);
+    }
+
+    let typed = |out: &mut BitWriter, value: u32, standard_bits: u8, stripped_bits: u8| {
+        out.write_bits(value, if stripped { stripped_bits } else { standard_bits });
+    };
+
+    let floor_count = copy_bits(&mut ss, &mut out, 6) + 1;
+    for _ in 0..floor_count {
+        let floor_type = ss.read_bits(16).unwrap();
+        assert_eq!(floor_type, 1, "only floor 1 can be stored by Wwise");
+        typed(&mut out, floor_type, 16, 0);
+        let partitions = copy_bits(&mut ss, &mut out, 5);
+        let classes: Vec<u32> = (0..partitions).map(|_| copy_bits(&mut ss, &mut out, 4)).collect();
+        let maximum_class = classes.iter().copied().max().unwrap_or(0);
+        let mut dimensions = Vec::new();
+        for _ in 0..=maximum_class {
+            dimensions.push(copy_bits(&mut ss, &mut out, 3) + 1);
+            let subclasses = copy_bits(&mut ss, &mut out, 2);
+            if subclasses != 0 {
+                copy_bits(&mut ss, &mut out, 8);
+            }
+            for _ in 0..(1 << subclasses) {
+                copy_bits(&mut ss, &mut out, 8);
+            }
+        }
+        copy_bits(&mut ss, &mut out, 2);
+        let rangebits = copy_bits(&mut ss, &mut out, 4) as u8;
+        for class in classes {
+            for _ in 0..dimensions[class as usize] {
+                copy_bits(&mut ss, &mut out, rangebits);
+            }
+        }
+    }
+
+    let residue_count = copy_bits(&mut ss, &mut out, 6) + 1;
+    for _ in 0..residue_count {
+        let residue_type = ss.read_bits(16).unwrap();
+        typed(&mut out, residue_type, 16, 2);
+        copy_bits(&mut ss, &mut out, 24);
+        copy_bits(&mut ss, &mut out, 24);
+        copy_bits(&mut ss, &mut out, 24);
+        let classifications = copy_bits(&mut ss, &mut out, 6) + 1;
+        copy_bits(&mut ss, &mut out, 8);
+        let mut cascade = Vec::new();
+        for _ in 0..classifications {
+            let low = copy_bits(&mut ss, &mut out, 3);
+            let high = if copy_bits(&mut ss, &mut out, 1) != 0 { copy_bits(&mut ss, &mut out, 5) } else { 0 };
+            cascade.push(high * 8 + low);
+        }
+        for c in cascade {
+            for k in 0..8 {
+                if c & (1 << k) != 0 {
+                    copy_bits(&mut ss, &mut out, 8);
+                }
+            }
+        }
+    }
+
+    let mapping_count = copy_bits(&mut ss, &mut out, 6) + 1;
+    let channel_bits = ilog(channels - 1) as u8;
+    for _ in 0..mapping_count {
+        let mapping_type = ss.read_bits(16).unwrap();
+        typed(&mut out, mapping_type, 16, 0);
+        let submaps = if copy_bits(&mut ss, &mut out, 1) != 0 { copy_bits(&mut ss, &mut out, 4) + 1 } else { 1 };
+        if copy_bits(&mut ss, &mut out, 1) != 0 {
+            let steps = copy_bits(&mut ss, &mut out, 8) + 1;
+            for _ in 0..steps {
+                copy_bits(&mut ss, &mut out, channel_bits);
+                copy_bits(&mut ss, &mut out, channel_bits);
+            }
+        }
+        copy_bits(&mut ss, &mut out, 2);
+        if submaps > 1 {
+            for _ in 0..channels {
+                copy_bits(&mut ss, &mut out, 4);
+            }
+        }
+        for _ in 0..submaps {
+            copy_bits(&mut ss, &mut out, 8);
+            copy_bits(&mut ss, &mut out, 8);
+            copy_bits(&mut ss, &mut out, 8);
+        }
+    }
+
+    let mode_count = copy_bits(&mut ss, &mut out, 6) + 1;
+    let mut mode_blockflag = Vec::new();
+    for _ in 0..mode_count {
+        mode_blockflag.push(copy_bits(&mut ss, &mut out, 1) != 0);
+        let window = ss.read_bits(16).unwrap();
+        typed(&mut out, window, 16, 0);
+        let transform = ss.read_bits(16).unwrap();
+        typed(&mut out, transform, 16, 0);
+        copy_bits(&mut ss, &mut out, 8);
+    }
+    let framing = ss.read_bits(1).unwrap();
+    assert_eq!(framing, 1);
+    if !stripped {
+        out.write_bits(framing, 1);
+    }
+    (out.take_bytes(), mode_blockflag)
+}
+
+/// A bit string that doesn't end on a byte boundary.
+pub struct Bits {
+    bytes: Vec<u8>,
+    bits: u64,
+}
+
+trait WriteBits {
+    fn write_bytes_bits(&mut self, bits: &Bits);
+}
+
+impl WriteBits for BitWriter {
+    fn write_bytes_bits(&mut self, bits: &Bits) {
+        let mut ss = BitStream::new(&bits.bytes[..]);
+        let mut left = bits.bits;
+        while left > 0 {
+            let n = left.min(32) as u8;
+            self.write_bits(ss.read_bits(n).unwrap(), n);
+            left -= n as u64;
+        }
+    }
+}
+
+/// Reads one standard codebook, returning it re-encoded as a standard bit
+/// string and as a compact (Wwise) codebook padded the way the library stores it.
+fn read_codebook(ss: &mut BitStream<&[u8]>) -> (Bits, Bits) {
+    let mut standard = BitWriter::new();
+    let mut compact = BitWriter::new();
+    let mut standard_bits = 0u64;
+    let mut compact_bits = 0u64;
+    let mut both = |value: u32, standard_width: u8, compact_width: u8| {
+        standard.write_bits(value, standard_width);
+        standard_bits += standard_width as u64;
+        compact.write_bits(value, compact_width);
+        compact_bits += compact_width as u64;
+    };
+
+    assert_eq!(ss.read_bits(24).unwrap(), 0x564342);
+    both(0x564342, 24, 0);
+    let dimensions = ss.read_bits(16).unwrap();
+    let entries = ss.read_bits(24).unwrap();
+    assert!(dimensions < 16 && entries < 1 << 14, "codebook too large for the compact form");
+    both(dimensions, 16, 4);
+    both(entries, 24, 14);
+
+    let ordered = ss.read_bits(1).unwrap();
+    both(ordered, 1, 1);
+    if ordered != 0 {
+        both(ss.read_bits(5).unwrap(), 5, 5);
+        let mut current = 0;
+        while current < entries {
+            let bits = ilog(entries - current) as u8;
+            let number = ss.read_bits(bits).unwrap();
+            both(number, bits, bits);
+            current += number;
+        }
+    } else {
+        let sparse = ss.read_bits(1).unwrap();
+        let mut lengths = Vec::new();
+        for _ in 0..entries {
+            let present = if sparse != 0 { ss.read_bits(1).unwrap() != 0 } else { true };
+            lengths.push(if present { Some(ss.read_bits(5).unwrap()) } else { None });
+        }
+        let longest = lengths.iter().flatten().copied().max().unwrap_or(0);
+        let length_bits = (ilog(longest) as u8).max(1);
+        both(length_bits as u32, 0, 3);
+        both(sparse, 1, 1);
+        for length in lengths {
+            if sparse != 0 {
+                both(length.is_some() as u32, 1, 1);
+            }
+            if let Some(length) = length {
+                both(length, 5, length_bits);
+            }
+        }
+    }
+
+    let lookup_type = ss.read_bits(4).unwrap();
+    assert!(lookup_type <= 1, "lookup type 2 has no compact form");
+    both(lookup_type, 4, 1);
+    if lookup_type == 1 {
+        both(ss.read_bits(32).unwrap(), 32, 32);
+        both(ss.read_bits(32).unwrap(), 32, 32);
+        let value_length = ss.read_bits(4).unwrap();
+        both(value_length, 4, 4);
+        both(ss.read_bits(1).unwrap(), 1, 1);
+        for _ in 0..book_maptype1_quantvals(entries, dimensions) {
+            let bits = value_length as u8 + 1;
+            both(ss.read_bits(bits).unwrap(), bits, bits);
+        }
+    }
+
+    (
+        Bits { bytes: standard.take_bytes(), bits: standard_bits },
+        Bits { bytes: compact.take_bytes(), bits: compact_bits },
+    )
+}
@@ -0,0 +1,128 @@
+//! Structural validation of Ogg Vorbis streams, on a libogg file and
+//! damaged copies of it.
+
+use std::path::Path;
+
+use wem_converter::bit_stream::checksum;
+use wem_converter::ogg::{validate_ogg, OggPage, OggPageReader, Report};
+
+fn load(name: &str) -> Vec<u8> {
+    std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data").join(name)).unwrap()
+}
+
+fn pages(ogg: &[u8]) -> Vec<OggPage> {
+    let mut reader = OggPageReader::new(ogg);
+    let mut pages = Vec::new();
+    while let Some(page) = reader.next_page().unwrap() {
+        pages.push(page);
+    }
+    pages
+}
+
+fn validate(ogg: &[u8]) -> Report {
+    validate_ogg(ogg).unwrap()
+}
+
+/// Recomputes the checksum of `page` after its bytes in `ogg` were edited.
+fn fix_crc(ogg: &mut [u8], page: &OggPage) {
+    let page_bytes = &mut ogg[page.offset as usize..page.offset as usize + page.size()];
+    page_bytes[22..26].fill(0);
+    let crc = checksum(page_bytes);
+    page_bytes[22..26].copy_from_slice(&crc.to_le_bytes());
+}
+
+#[test]
+fn libogg_files_are_valid() {
+    for name in ["sine_mono.ogg", "sine_stereo.ogg"] {
+        let ogg = load(name);
+        let report = validate(&ogg);
+        assert!(report.is_valid(), "{}: {:?}", name, report.issues);
+        assert_eq!(report.bytes, ogg.len() as u64);
+        assert_eq!(report.pages, pages(&ogg).len() as u64);
+        assert_eq!(report.streams.len(), 1);
+        let stream = &report.streams[0];
+        assert!(stream.eos);
+        assert_eq!(stream.audio_packets + 3, stream.packets);
+        assert_eq!(stream.identification.as_ref().unwrap().channels, if name == "sine_mono.ogg" { 1 } else { 2 });
+    }
+}
+
+#[test]
+fn bad_crc_is_reported_on_its_page() {
+    let mut ogg = load("sine_stereo.ogg");
+    let page = &pages(&ogg)[3];
+    ogg[page.offset as usize + page.size() - 1] ^= 0x10;
+
+    let report = validate(&ogg);
+    assert_eq!(report.issues.len(), 1, "{:?}", report.issues);
+    assert_eq!(report.issues[0].offset, page.offset);
+    assert_eq!(report.issues[0].message, "CRC mismatch");
+    assert_eq!(report.pages, pages(&ogg).len() as u64);
+}
+
+#[test]
+fn broken_page_sequence_is_reported() {
+    let ogg = load("sine_stereo.ogg");
+    let all = pages(&ogg);
+
+    // A page renumbered, with its checksum kept right.
+    let mut renumbered = ogg.clone();
+    let page = &all[2];
+    renumbered[page.offset as usize + 18..page.offset as usize + 22].copy_from_slice(&9u32.to_le_bytes());
+    fix_crc(&mut renumbered, page);
+    let report = validate(&renumbered);
+    assert_eq!(report.issues[0].offset, page.offset);
+    assert_eq!(report.issues[0].message, "page sequence number 9 where 2 was expected");
+    assert_eq!(report.issues[1].offset, all[3].offset);
+    assert_eq!(report.issues[1].message, "page sequence number 3 where 10 was expected");
+
+    // A page left out: the number of the next one skips ahead.
+    let mut dropped = ogg[..page.offset as usize].to_vec();
+    dropped.extend_from_slice(&ogg[page.offset as usize + page.size()..]);
+    let report = validate(&dropped);
+    assert!(!report.is_valid());
+    assert_eq!(report.issues[0].offset, page.offset);
+    assert_eq!(report.issues[0].message, "page sequence number 3 where 2 was expected");
+}
+
+#[test]
+fn missing_eos_and_headers_are_reported() {
+    let ogg = load("sine_mono.ogg");
+    let all = pages(&ogg);
+
+    let cut = all[all.len() - 1].offset as usize;
+    let report = validate(&ogg[..cut]);
+    let messages: Vec<&str> = report.issues.iter().map(|issue| issue.message.as_str()).collect();
+    assert_eq!(messages, ["stream ends without EOS page"]);
+
+    let report = validate(&ogg[..all[1].offset as usize]);
+    let messages: Vec<&str> = report.issues.iter().map(|issue| issue.message.as_str()).collect();
+    assert!(messages.contains(&"missing Vorbis header packets"), "{:?}", messages);
+}
+
+#[test]
+fn header_packets_cut_short_are_reported() {
+    let ogg = load("sine_mono.ogg");
+    let page = &pages(&ogg)[1];
+    // The comment packet ends at the first lacing value under 255; the
+    // setup packet fills the rest of the page. Keep 20 bytes of the setup.
+    let comment_laces = page.lacing.iter().position(|&lace| lace < 255).unwrap() + 1;
+    assert!(*page.lacing.last().unwrap() < 255);
+    let comment_len: usize = page.lacing[..comment_laces].iter().map(|&lace| lace as usize).sum();
+    let mut lacing = page.lacing[..comment_laces].to_vec();
+    lacing.push(20);
+
+    let header = page.offset as usize;
+    let mut cut = ogg[..header + 26].to_vec();
+    cut.push(lacing.len() as u8);
+    cut.extend_from_slice(&lacing);
+    cut.extend_from_slice(&page.data[..comment_len + 20]);
+    let cut_page = pages(&cut)[1].clone();
+    fix_crc(&mut cut, &cut_page);
+    cut.extend_from_slice(&ogg[header + page.size()..]);
+
+    let report = validate(&cut);
+    let issues: Vec<_> = report.issues.iter().filter(|issue| issue.offset == page.offset).collect();
+    assert_eq!(issues.len(), 1, "{:?}", report.issues);
+    assert_eq!(issues[0].message, "Out of bits");
+}
@@ -0,0 +1,124 @@
+//! Verification of generated streams against the wem header, on wems whose
+//! packets no longer agree with it.
+
+mod support;
+
+use support::{open, open_wem, vorb_offset, wem_bytes, Layout, Vorb, VorbisSource, Wem};
+use wem_converter::bit_stream::{BitOggStream, BitOggStreamT};
+use wem_converter::errors::{ParseError, Result, VerifyError};
+use wem_converter::ogg::OggPacketReader;
+use wem_converter::overrides::Overrides;
+
+fn convert(wem: &mut Wem) -> Result<Vec<u8>> {
+    wem.verify = true;
+    let mut ogg = Vec::new();
+    wem.generate_ogg_to(&mut ogg)?;
+    Ok(ogg)
+}
+
+fn verify_error(result: Result<Vec<u8>>) -> VerifyError {
+    match result {
+        Err(ParseError::Verify(e)) => e,
+        other => panic!("expected a verification error, got {:?}", other.map(|ogg| ogg.len())),
+    }
+}
+
+/// `wem` reopened with the sample count in its vorb chunk replaced.
+fn with_sample_count(wem: &Wem, layout: &Layout, sample_count: u32) -> Wem {
+    let mut bytes = wem_bytes(wem);
+    let vorb = vorb_offset(&bytes);
+    bytes[vorb..vorb + 4].copy_from_slice(&sample_count.to_le_bytes());
+    open_wem(bytes, layout, &wem.codebooks_name, Overrides::default()).unwrap()
+}
+
+#[test]
+fn sample_count_is_checked_without_packet_granules() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let granules = source.granules();
+    for mod_packets in [false, true] {
+        // The output's last granule is cut to whatever the header says, so
+        // only the block sizes show that this count ends with the packet
+        // before the last.
+        let layout = Layout { mod_packets, ..Layout::new(Vorb::Size2A) };
+        let wem = open(&source, &layout, "verify");
+        let lowered = granules[granules.len() - 2];
+        let err = verify_error(convert(&mut with_sample_count(&wem, &layout, lowered)));
+        assert!(
+            matches!(err, VerifyError::SampleCountMismatch { sample_count, start, end }
+                if sample_count == lowered as u64 && start == sample_count && end > source.sample_count as u64),
+            "{}: {}",
+            layout.tag(),
+            err
+        );
+    }
+
+    let layout = Layout::new(Vorb::Size32);
+    let wem = open(&source, &layout, "verify");
+    let err = verify_error(convert(&mut with_sample_count(&wem, &layout, source.sample_count - 1)));
+    assert_eq!(
+        err,
+        VerifyError::GranuleMismatch {
+            expected: source.sample_count as u64 - 1,
+            actual: Some(source.sample_count as u64)
+        }
+    );
+}
+
+#[test]
+fn a_missing_packet_leaves_data_unaccounted_for() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layout = Layout::new(Vorb::Size32);
+    let mut wem = open(&source, &layout, "verify");
+    let ogg = convert(&mut wem).unwrap();
+
+    // The same stream re-framed without its 11th audio packet.
+    let mut reader = OggPacketReader::new(&ogg[..]);
+    let mut packets = Vec::new();
+    while let Some(packet) = reader.next_packet().unwrap() {
+        packets.push(packet);
+    }
+    let dropped = packets.remove(3 + 10);
+    let mut reframed = Vec::new();
+    {
+        let mut os = BitOggStream::new(&mut reframed);
+        let last = packets.len() - 1;
+        for (i, packet) in packets.iter().enumerate() {
+            os.write_all(&packet.data).unwrap();
+            os.set_granule(packet.granule.unwrap_or(0) as u32);
+            os.flush_page(false, i == last).unwrap();
+        }
+    }
+
+    let expected = (wem.data_size - wem.first_audio_packet_offset as i64) as u64;
+    let err = wem.verify_ogg(&reframed).unwrap_err();
+    assert!(
+        matches!(err, ParseError::Verify(VerifyError::DataSizeMismatch { expected: e, actual })
+            if e == expected && actual == expected - 6 - dropped.data.len() as u64 + 1),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn seek_table_entries_must_point_at_packets() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    for vorb in [Vorb::Size2A, Vorb::Size32] {
+        let layout = Layout { seek_table: true, ..Layout::new(vorb) };
+        let wem = open(&source, &layout, "verify");
+        let mut bytes = wem_bytes(&wem);
+        // Move the first entry, and every one after it, a byte off its packet.
+        bytes[wem.data_offset as usize + 2] ^= 0x01;
+        let mut broken = open_wem(bytes, &layout, &wem.codebooks_name, Overrides::default()).unwrap();
+        let offset = broken.seek_table().unwrap()[0].offset;
+        assert_eq!(verify_error(convert(&mut broken)), VerifyError::SeekTableMismatch { offset }, "{}", layout.tag());
+    }
+}
+
+#[test]
+fn intact_wems_verify() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    for vorb in Vorb::ALL {
+        let layout = Layout { seek_table: vorb != Vorb::InFmt, ..Layout::new(vorb) };
+        convert(&mut open(&source, &layout, "verify")).unwrap_or_else(|e| panic!("{}: {}", layout.tag(), e));
+    }
+}
@@ -0,0 +1,77 @@
+//! Wwise version estimates for the layouts the fixture generator writes.
+
+mod support;
+
+use support::{open, open_wem, wem_bytes, Layout, Vorb, VorbisSource};
+use wem_converter::overrides::Overrides;
+use wem_converter::version::{VersionRange, WwiseVersion};
+
+fn range(earliest: Option<(u16, u8)>, latest: Option<(u16, u8)>) -> VersionRange {
+    VersionRange {
+        earliest: earliest.map(|(year, release)| WwiseVersion::new(year, release)),
+        latest: latest.map(|(year, release)| WwiseVersion::new(year, release)),
+    }
+}
+
+#[test]
+fn every_layout_gets_a_consistent_range() {
+    let expected = [
+        (Vorb::InFmt, true, range(Some((2012, 1)), None)),
+        (Vorb::InFmt, false, range(Some((2012, 1)), None)),
+        (Vorb::Size2A, true, range(Some((2011, 1)), Some((2012, 1)))),
+        (Vorb::Size2A, false, range(Some((2011, 1)), Some((2012, 1)))),
+        (Vorb::Size28, false, range(None, Some((2009, 3)))),
+        (Vorb::Size2C, false, range(None, Some((2009, 3)))),
+        (Vorb::Size32, true, range(Some((2011, 1)), Some((2012, 1)))),
+        (Vorb::Size34, true, range(Some((2010, 1)), Some((2011, 3)))),
+    ];
+    for (vorb, mod_packets, expected) in expected {
+        let layout = Layout { mod_packets, ..Layout::new(vorb) };
+        let estimate = open(&VorbisSource::load("sine_mono.ogg"), &layout, "version").detect_wwise_version();
+        assert_eq!(estimate.range, Some(expected), "{}: {:?}", layout.tag(), estimate.evidence);
+        for evidence in &estimate.evidence {
+            assert!(evidence.range.intersect(&expected).is_some(), "{}", evidence.clue);
+        }
+    }
+}
+
+#[test]
+fn evidence_names_the_clues() {
+    let layout = Layout::new(Vorb::InFmt);
+    let estimate = open(&VorbisSource::load("sine_mono.ogg"), &layout, "version").detect_wwise_version();
+    let clues: Vec<&str> = estimate.evidence.iter().map(|evidence| evidence.clue.as_str()).collect();
+    assert_eq!(
+        clues,
+        [
+            "vorb fields in a 0x42 byte fmt chunk",
+            "channel mask 0x4 in the fmt chunk",
+            "2 byte packet headers",
+            "modified packets (mod signal 0x3F)",
+        ]
+    );
+    assert_eq!(estimate.range.unwrap().to_string(), "2012.1 or later");
+}
+
+#[test]
+fn contradicting_clues_give_no_range() {
+    // 8 byte packet headers are far older than the 0x34 vorb chunk.
+    let layout = Layout::new(Vorb::Size34);
+    let overrides = Overrides { old_packet_headers: Some(true), ..Overrides::default() };
+    let wem = open(&VorbisSource::load("sine_mono.ogg"), &layout, "version");
+    let wem = open_wem(wem_bytes(&wem), &layout, &wem.codebooks_name, overrides).unwrap();
+    let estimate = wem.detect_wwise_version();
+    assert_eq!(estimate.range, None);
+}
+
+#[test]
+fn ranges_intersect_and_print() {
+    let old = range(None, Some((2010, 2)));
+    let new = range(Some((2011, 2)), None);
+    assert_eq!(old.intersect(&new), None);
+    assert_eq!(old.intersect(&VersionRange::ANY), Some(old));
+    assert!(new.contains(WwiseVersion::new(2019, 2)));
+    assert!(!new.contains(WwiseVersion::new(2011, 1)));
+    assert_eq!(old.to_string(), "2010.2 or earlier");
+    assert_eq!(new.to_string(), "2011.2 or later");
+    assert_eq!(VersionRange::ANY.to_string(), "any version");
+}
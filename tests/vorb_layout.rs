@@ -0,0 +1,91 @@
+//! Detection of vorb chunk layouts the converter has no table entry for.
+
+mod support;
+
+use support::{build_wem, convert, insert_into_vorb, open_wem, vorb_offset, Layout, Vorb, VorbisSource};
+use wem_converter::overrides::Overrides;
+use wem_converter::vorb::VorbLayout;
+
+/// Bytes inserted after the first two fields of the vorb chunk, moving every
+/// other field to an offset no known layout uses.
+const INSERTED: usize = 0x10;
+
+/// Inserts `INSERTED` bytes of `fill` at offset 8 of the vorb chunk.
+fn widen_vorb(wem: &[u8], big_endian: bool, fill: u8) -> Vec<u8> {
+    insert_into_vorb(wem, big_endian, 8, &[fill; INSERTED])
+}
+
+#[test]
+fn known_layouts_are_looked_up() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    for vorb in Vorb::ALL {
+        let layout = Layout::new(vorb);
+        let built = build_wem(&source, &layout);
+        let codebooks = built.write_codebooks(&format!("vorb-{}", layout.tag()));
+        let wem = open_wem(built.wem, &layout, &codebooks, Overrides::default()).unwrap();
+        let found = wem.vorb_layout.unwrap();
+        assert!(found.is_known(), "{}", layout.tag());
+        assert_eq!(Some(found), VorbLayout::known(wem.vorb_size));
+    }
+}
+
+#[test]
+fn shifted_layouts_are_detected() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    for layout in Layout::all().iter().filter(|layout| layout.vorb != Vorb::InFmt) {
+        let built = build_wem(&source, layout);
+        let codebooks = built.write_codebooks(&format!("vorb-{}", layout.tag()));
+        let widened = widen_vorb(&built.wem, layout.big_endian, 0xEE);
+
+        let wem = open_wem(widened.clone(), layout, &codebooks, Overrides::default()).unwrap();
+        let known = VorbLayout::known(wem.vorb_size - INSERTED as i64);
+        let shift = |at: Option<i64>| at.map(|at| at + INSERTED as i64);
+        let expected = known.map(|known| VorbLayout {
+            size: wem.vorb_size,
+            setup_packet_offset: known.setup_packet_offset + INSERTED as i64,
+            first_audio_packet_offset: known.first_audio_packet_offset + INSERTED as i64,
+            uid: shift(known.uid),
+            blocksizes: shift(known.blocksizes),
+            ..known
+        });
+        assert_eq!(wem.vorb_layout, expected, "{}", layout.tag());
+        assert!(!wem.vorb_layout.unwrap().is_known());
+
+        let expected = convert(&mut open_wem(built.wem, layout, &codebooks, Overrides::default()).unwrap());
+        assert!(convert(&mut open_wem(widened, layout, &codebooks, Overrides::default()).unwrap()) == expected, "{}", layout.tag());
+    }
+}
+
+#[test]
+fn blocksizes_are_found_away_from_the_usual_offset() {
+    let source = VorbisSource::load("sine_stereo.ogg");
+    let layout = Layout::new(Vorb::Size34);
+    let built = build_wem(&source, &layout);
+    let codebooks = built.write_codebooks("vorb-blocksizes");
+    let mut widened = widen_vorb(&built.wem, false, 0);
+    // Move uid and blocksizes two bytes later, into the chunk's spare bytes.
+    let vorb = vorb_offset(&widened);
+    let fields = vorb + 0x2C + INSERTED;
+    widened.copy_within(fields..fields + 6, fields + 2);
+    widened[fields..fields + 2].fill(0);
+
+    let wem = open_wem(widened.clone(), &layout, &codebooks, Overrides::default()).unwrap();
+    assert_eq!(wem.vorb_layout.unwrap().blocksizes, Some(0x30 + INSERTED as i64 + 2));
+    assert_eq!((wem.blocksize_0_pow, wem.blocksize_1_pow), (source.blocksize_0_pow, source.blocksize_1_pow));
+    let expected = convert(&mut open_wem(built.wem, &layout, &codebooks, Overrides::default()).unwrap());
+    assert!(convert(&mut open_wem(widened, &layout, &codebooks, Overrides::default()).unwrap()) == expected);
+}
+
+#[test]
+fn unrecognizable_layouts_are_rejected() {
+    let source = VorbisSource::load("sine_mono.ogg");
+    let layout = Layout::new(Vorb::Size32);
+    let built = build_wem(&source, &layout);
+    let mut widened = widen_vorb(&built.wem, false, 0);
+    // Point the setup offset past the data chunk.
+    let vorb = vorb_offset(&widened);
+    widened[vorb + 0x18 + INSERTED..vorb + 0x1C + INSERTED].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    let err = open_wem(widened, &layout, "", Overrides::default()).unwrap_err();
+    assert_eq!(err.to_string(), "Parse error: unrecognized vorb layout");
+}